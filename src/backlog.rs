@@ -0,0 +1,52 @@
+use super::states::{BacklogSelection, BulkAction};
+
+/// Seconds a backlog message stays in selection mode
+pub const SELECTION_TTL: usize = 24 * 3600;
+
+pub fn selection_key(chat: i64, message: i64) -> String {
+    format!("backlog:{}:{}", chat, message)
+}
+
+impl BulkAction {
+    pub fn all() -> Vec<BulkAction> {
+        vec![
+            BulkAction::Tag,
+            BulkAction::Assign,
+            BulkAction::State,
+            BulkAction::Command,
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BulkAction::Tag => "tag",
+            BulkAction::Assign => "assign",
+            BulkAction::State => "state",
+            BulkAction::Command => "command",
+        }
+    }
+
+    /// Command the composer starts with, completed by the user
+    pub fn prefix(self) -> &'static str {
+        match self {
+            BulkAction::Tag => "tag ",
+            BulkAction::Assign => "Assignee ",
+            BulkAction::State => "State ",
+            BulkAction::Command => "",
+        }
+    }
+}
+
+impl BacklogSelection {
+    pub fn contains(&self, issue: &str) -> bool {
+        self.issues.iter().any(|i| i == issue)
+    }
+
+    pub fn toggle(&mut self, issue: &str) {
+        if self.contains(issue) {
+            self.issues.retain(|i| i != issue);
+        } else {
+            self.issues.push(issue.to_string());
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use chrono::{TimeZone, Utc};
+use chrono_tz::Tz;
+use futures::stream::{self, StreamExt};
 use oauth2::basic::BasicClient;
 use oauth2::{CsrfToken, Scope};
 use redis;
@@ -5,49 +8,158 @@ use redis::Commands;
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::convert::TryInto;
+use std::time::{SystemTime, UNIX_EPOCH};
 use telegram_bot::prelude::*;
 use telegram_bot::types::*;
-use telegram_bot::{Api, UpdatesStream};
+use telegram_bot::{
+    Api, EditMessageText, GetChatAdministrators, GetChatMembersCount, GetFile, GetMe,
+    InputFileUpload, SendDocument, SendPhoto, SendPoll, StopPoll, UpdatesStream,
+};
 use tera::{Context, Tera};
 use ttl_cache::TtlCache;
 use youtrack_rs::client::{Executor, YouTrack};
 
+use super::backlog::{self, SELECTION_TTL};
+use super::burndown::{issue_weight, Burndown, IssueTimeline};
+use super::charts;
 use super::commands::*;
+use super::digests::{self, DigestIssue, STALE_DAYS};
 use super::errors::*;
+use super::estimation::{self, ESTIMATION_FIELD};
+use super::files;
+use super::fuzzy::{self, Lookup, Named};
+use super::hooks::HookPayload;
+use super::issue_templates::*;
 use super::models::*;
 use super::opts::*;
+use super::prioritization::{self, poll_option};
+use super::scheduler;
+use super::standups;
 use super::states::*;
+use super::timesheet::{self, format_minutes, Timesheet};
+use super::triage::{self, NEED_INFO_COMMENT};
 
-fn make_reply_keyboard<T>(values: Vec<T>, f: fn(&T) -> String) -> ReplyKeyboardMarkup {
-    let mut kb = ReplyKeyboardMarkup::new();
-    kb.one_time_keyboard().resize_keyboard();
+const PICKER_PAGE_SIZE: usize = 8;
+// Seconds to remember which issue a bot message refers to
+const ISSUE_MESSAGE_TTL: usize = 30 * 24 * 3600;
+const BOARD_PAGE_SIZE: usize = 5;
+// Finished sprints shown by /velocity
+const VELOCITY_SPRINTS: usize = 6;
+// Sprint size /burndown handles, and its concurrent history requests
+const BURNDOWN_MAX_ISSUES: usize = 100;
+const BURNDOWN_REQUESTS: usize = 8;
+// Telegram rejects longer messages
+const MAX_MESSAGE_LENGTH: usize = 4096;
 
-    for chunk in values.chunks(3) {
-        let mut row: Vec<KeyboardButton> = Vec::new();
-        for val in chunk.iter() {
-            row.push(KeyboardButton::new(f(val)));
-        }
+fn picker_keyboard<T: Named>(
+    values: &[T],
+    step: PickerStep,
+    page: usize,
+    extra: &[InlineKeyboardButton],
+) -> InlineKeyboardMarkup {
+    let mut kb = InlineKeyboardMarkup::new();
+    let start = page * PICKER_PAGE_SIZE;
+
+    let mut values_buttons: Vec<InlineKeyboardButton> = Vec::new();
+    for (index, value) in values.iter().enumerate().skip(start).take(PICKER_PAGE_SIZE) {
+        values_buttons
+            .push(CallbackParams::PickerSelect(PickerParams { step, index }).button(value.title()));
+    }
+    for row in values_buttons.chunks(2) {
+        kb.add_row(row.to_vec());
+    }
+
+    let mut row: Vec<InlineKeyboardButton> = Vec::new();
+    if page > 0 {
+        row.push(
+            CallbackParams::PickerPage(PickerParams {
+                step,
+                index: page - 1,
+            })
+            .button("prev"),
+        );
+    }
+    if start + PICKER_PAGE_SIZE < values.len() {
+        row.push(
+            CallbackParams::PickerPage(PickerParams {
+                step,
+                index: page + 1,
+            })
+            .button("next"),
+        );
+    }
+    if !row.is_empty() {
         kb.add_row(row);
     }
+    if !extra.is_empty() {
+        kb.add_row(extra.to_vec());
+    }
+    kb
+}
+
+fn suggestions_keyboard<T: Named>(
+    values: &[T],
+    step: PickerStep,
+    found: &[usize],
+) -> InlineKeyboardMarkup {
+    let mut kb = InlineKeyboardMarkup::new();
+    for &index in found.iter() {
+        kb.add_row(vec![CallbackParams::PickerSelect(PickerParams {
+            step,
+            index,
+        })
+        .button(values[index].title())]);
+    }
     kb
 }
 
-fn backlog_keyboard(issues: &Issues, params: &BacklogParams) -> InlineKeyboardMarkup {
+/// Issue buttons toggle votes, or select issues for bulk actions in selection mode
+fn backlog_keyboard(
+    issues: &Issues,
+    params: &BacklogParams,
+    selection: Option<&BacklogSelection>,
+) -> InlineKeyboardMarkup {
     let mut kb = InlineKeyboardMarkup::new();
     let mut row: Vec<InlineKeyboardButton> = Vec::new();
 
-    let mut issues_buttons: Vec<InlineKeyboardButton> = Vec::new();
-    for issue in issues.iter() {
-        issues_buttons.push(
-            CallbackParams::VoteForIssue(VoteForIssueParams {
+    if let Some(selection) = selection {
+        for issue in issues.iter() {
+            kb.add_row(vec![CallbackParams::SelectIssue(SelectIssueParams {
                 id: issue.id_readable.clone(),
-                has_vote: issue.voters.has_vote,
+                selected: selection.contains(&issue.id_readable),
+                skip: params.skip,
             })
-            .into(),
-        );
-    }
-    for row in issues_buttons.chunks(3) {
-        kb.add_row(row.to_vec());
+            .into()]);
+        }
+        let mut actions: Vec<InlineKeyboardButton> =
+            vec![CallbackParams::BulkVote(params.clone()).into()];
+        actions.extend(BulkAction::all().into_iter().map(|action| {
+            CallbackParams::BulkCommand(BulkCommandParams {
+                action,
+                skip: params.skip,
+            })
+            .into()
+        }));
+        actions.push(CallbackParams::BacklogSelectDone(params.clone()).into());
+        for actions_row in actions.chunks(3) {
+            kb.add_row(actions_row.to_vec());
+        }
+    } else {
+        for issue in issues.iter() {
+            let sprint = IssueParams {
+                id: issue.id_readable.clone(),
+            };
+            kb.add_row(vec![
+                CallbackParams::VoteForIssue(VoteForIssueParams {
+                    id: issue.id_readable.clone(),
+                    has_vote: issue.voters.has_vote,
+                    skip: params.skip,
+                })
+                .into(),
+                CallbackParams::AddToSprint(sprint.clone()).button("current sprint"),
+                CallbackParams::MoveToNextSprint(sprint).button("next sprint"),
+            ]);
+        }
     }
 
     row.push(CallbackParams::BacklogStop {}.into());
@@ -65,10 +177,323 @@ fn backlog_keyboard(issues: &Issues, params: &BacklogParams) -> InlineKeyboardMa
             }
         }
     }
+    if selection.is_none() && issues.len() > 0 {
+        row.push(CallbackParams::BacklogSelect(params.clone()).into());
+    }
+    kb.add_row(row);
+    kb
+}
+
+fn assignee_extra_buttons() -> Vec<InlineKeyboardButton> {
+    vec![
+        CallbackParams::AssignToMe.into(),
+        CallbackParams::Unassigned.into(),
+    ]
+}
+
+fn issue_tags_keyboard(tags: &IssueTags, selected: &IssueTags) -> InlineKeyboardMarkup {
+    let mut kb = InlineKeyboardMarkup::new();
+
+    let mut tags_buttons: Vec<InlineKeyboardButton> = Vec::new();
+    for tag in tags.iter() {
+        let text = if selected.iter().any(|t| t.id == tag.id) {
+            format!("{} {}", emoji!("white_check_mark"), tag.name)
+        } else {
+            tag.name.clone()
+        };
+        tags_buttons.push(
+            CallbackParams::ToggleIssueTag(IssueTagParams { id: tag.id.clone() }).button(text),
+        );
+    }
+    for row in tags_buttons.chunks(3) {
+        kb.add_row(row.to_vec());
+    }
+    kb.add_row(vec![CallbackParams::IssueTagsDone.into()]);
+    kb
+}
+
+fn command_keyboard(assist: &CommandAssist) -> InlineKeyboardMarkup {
+    let mut kb = InlineKeyboardMarkup::new();
+    let buttons: Vec<InlineKeyboardButton> = assist
+        .suggestions
+        .iter()
+        .take(PICKER_PAGE_SIZE)
+        .enumerate()
+        .map(|(index, s)| {
+            let text = match &s.description {
+                Some(desc) if desc != &s.option => format!("{} ({})", s.option, desc),
+                _ => s.option.clone(),
+            };
+            CallbackParams::CommandSuggestion(SuggestionParams { index }).button(text)
+        })
+        .collect();
+    for row in buttons.chunks(2) {
+        kb.add_row(row.to_vec());
+    }
+    kb.add_row(vec![
+        CallbackParams::CommandApply.into(),
+        CallbackParams::CommandStop.into(),
+    ]);
+    kb
+}
+
+fn issue_card_keyboard(card: &IssueCard, states: &[StateBundleElement]) -> InlineKeyboardMarkup {
+    let mut kb = InlineKeyboardMarkup::new();
+    let state_buttons: Vec<InlineKeyboardButton> = states
+        .iter()
+        .map(|state| {
+            CallbackParams::SetIssueState(IssueStateParams {
+                issue: card.id_readable.clone(),
+                state: state.id.clone(),
+            })
+            .button(&state.name)
+        })
+        .collect();
+    for row in state_buttons.chunks(3) {
+        kb.add_row(row.to_vec());
+    }
+    let issue = IssueParams {
+        id: card.id_readable.clone(),
+    };
+    let mut actions = Vec::new();
+    if card.field("Assignee").is_some() {
+        actions.push(CallbackParams::AssignIssueToMe(issue.clone()).into());
+    }
+    actions.push(CallbackParams::ShowHistory(issue.clone()).into());
+    kb.add_row(actions);
+    kb.add_row(vec![
+        CallbackParams::AddToSprint(issue.clone()).into(),
+        CallbackParams::MoveToNextSprint(issue).into(),
+    ]);
+    kb
+}
+
+/// Scale values while voting, the voted values to apply once revealed
+fn estimation_keyboard(session: &EstimationSession) -> InlineKeyboardMarkup {
+    let mut kb = InlineKeyboardMarkup::new();
+    let issue = session.issue.id_readable.clone();
+    let values = session.scale.values();
+    if session.revealed {
+        let row: Vec<InlineKeyboardButton> = session
+            .distribution()
+            .iter()
+            .filter(|(value, _)| session.scale.presentation(value).is_some())
+            .filter_map(|(value, _)| {
+                let index = values.iter().position(|v| *v == value.as_str())?;
+                let params = EstimationVoteParams {
+                    issue: issue.clone(),
+                    value: index,
+                };
+                Some(CallbackParams::EstimationApply(params).button(format!("set {}", value)))
+            })
+            .collect();
+        if !row.is_empty() {
+            kb.add_row(row);
+        }
+    } else {
+        let buttons: Vec<InlineKeyboardButton> = values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                let params = EstimationVoteParams {
+                    issue: issue.clone(),
+                    value: index,
+                };
+                CallbackParams::EstimationVote(params).button(value)
+            })
+            .collect();
+        for row in buttons.chunks(4) {
+            kb.add_row(row.to_vec());
+        }
+        kb.add_row(vec![CallbackParams::EstimationReveal(EstimationParams {
+            issue,
+        })
+        .into()]);
+    }
+    kb
+}
+
+fn triage_keyboard(card: &IssueCard) -> InlineKeyboardMarkup {
+    let mut kb = InlineKeyboardMarkup::new();
+    let issue = card.id_readable.clone();
+    let fields: Vec<InlineKeyboardButton> = TriageField::all()
+        .into_iter()
+        .filter(|field| card.field(field.name()).is_some())
+        .map(|field| {
+            CallbackParams::TriageMenu(TriageFieldParams {
+                issue: issue.clone(),
+                field,
+                page: 0,
+            })
+            .into()
+        })
+        .collect();
+    if !fields.is_empty() {
+        kb.add_row(fields);
+    }
+    let params = TriageParams { issue };
+    kb.add_row(vec![
+        CallbackParams::TriageDuplicate(params.clone()).into(),
+        CallbackParams::TriageNeedInfo(params.clone()).into(),
+    ]);
+    kb.add_row(vec![
+        CallbackParams::TriageSkip(params.clone()).into(),
+        CallbackParams::TriageStop(params).into(),
+    ]);
+    kb
+}
+
+/// Values the triage field can be set to, paged like the picker
+fn triage_values_keyboard(
+    issue: &str,
+    field: TriageField,
+    values: &[String],
+    page: usize,
+) -> InlineKeyboardMarkup {
+    let mut kb = InlineKeyboardMarkup::new();
+    let start = page * PICKER_PAGE_SIZE;
+    let buttons: Vec<InlineKeyboardButton> = values
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take(PICKER_PAGE_SIZE)
+        .map(|(value, title)| {
+            CallbackParams::TriageSet(TriageValueParams {
+                issue: issue.to_string(),
+                field,
+                value,
+            })
+            .button(title)
+        })
+        .collect();
+    for row in buttons.chunks(2) {
+        kb.add_row(row.to_vec());
+    }
+
+    let menu = |page| {
+        CallbackParams::TriageMenu(TriageFieldParams {
+            issue: issue.to_string(),
+            field,
+            page,
+        })
+    };
+    let mut row: Vec<InlineKeyboardButton> = Vec::new();
+    if page > 0 {
+        row.push(menu(page - 1).button("prev"));
+    }
+    if start + PICKER_PAGE_SIZE < values.len() {
+        row.push(menu(page + 1).button("next"));
+    }
+    row.push(
+        CallbackParams::TriageBack(TriageParams {
+            issue: issue.to_string(),
+        })
+        .into(),
+    );
     kb.add_row(row);
     kb
 }
 
+fn settings_keyboard(settings: &NotificationSettings) -> InlineKeyboardMarkup {
+    let mut kb = InlineKeyboardMarkup::new();
+    let categories: Vec<InlineKeyboardButton> =
+        NotificationCategory::all()
+            .into_iter()
+            .map(|category| {
+                let mark = if settings.is_enabled(category) {
+                    emoji!("white_check_mark")
+                } else {
+                    emoji!("x")
+                };
+                CallbackParams::SettingsToggle(NotificationCategoryParams { category })
+                    .button(format!("{} {}", mark, category.name()))
+            })
+            .collect();
+    for row in categories.chunks(2) {
+        kb.add_row(row.to_vec());
+    }
+    let quiet = match &settings.quiet_hours {
+        Some(quiet) => quiet.describe(),
+        None => "off".to_string(),
+    };
+    let batching = match settings.batching {
+        Batching::Immediate => "immediately",
+        Batching::Hourly => "hourly summary",
+    };
+    kb.add_row(vec![
+        CallbackParams::SettingsQuiet.button(format!("quiet hours: {}", quiet))
+    ]);
+    kb.add_row(vec![
+        CallbackParams::SettingsBatching.button(format!("delivery: {}", batching))
+    ]);
+    kb.add_row(vec![CallbackParams::SettingsDone.into()]);
+    kb
+}
+
+/// Estimation minutes as hours, or issue count for boards without estimation
+fn chart_value(value: i64, estimation_field: Option<&str>) -> f64 {
+    match estimation_field {
+        Some(_) => value as f64 / 60.0,
+        None => value as f64,
+    }
+}
+
+/// Caption line explaining the chart grid
+fn grid_legend(values: &[f64], estimation_field: Option<&str>, columns: &str) -> String {
+    let row = charts::top(values) / charts::GRID_ROWS as f64;
+    let row = match estimation_field {
+        Some(_) => (row * 60.0) as i64,
+        None => row as i64,
+    };
+    format!(
+        "Ticks mark {}, gridlines every {}",
+        columns,
+        format_amount(row, estimation_field)
+    )
+}
+
+fn format_amount(value: i64, estimation_field: Option<&str>) -> String {
+    match estimation_field {
+        Some(_) => format_minutes(value.max(0) as u64),
+        None => format!("{} issues", value),
+    }
+}
+
+fn standup_question(index: usize) -> String {
+    format!(
+        "{}/{}. {}",
+        index + 1,
+        standups::QUESTIONS.len(),
+        standups::QUESTIONS[index]
+    )
+}
+
+fn board_keyboard(agile: &Agile, counts: &[usize]) -> InlineKeyboardMarkup {
+    let mut kb = InlineKeyboardMarkup::new();
+    let columns: Vec<InlineKeyboardButton> = agile
+        .columns()
+        .iter()
+        .zip(counts)
+        .enumerate()
+        .map(|(column, (c, count))| {
+            CallbackParams::BoardColumn(BoardColumnParams {
+                agile: agile.id.clone(),
+                column,
+                skip: 0,
+            })
+            .button(format!("{} ({})", c.presentation, count))
+        })
+        .collect();
+    for row in columns.chunks(2) {
+        kb.add_row(row.to_vec());
+    }
+    kb.add_row(vec![CallbackParams::BoardPin(BoardParams {
+        agile: agile.id.clone(),
+    })
+    .into()]);
+    kb
+}
+
 macro_rules! match_user_state {
     ($s:ty, $var:ident, $($value:path),+) => {
         paste::expr! {
@@ -88,20 +513,114 @@ pub struct Bot {
     backlog_query: String,
     csrf_tokens: HashMap<String, UserId>,
     yt_tokens: TtlCache<UserId, YouTrack>,
+    telegram_token: String,
+    telegram_client: files::HttpClient,
     redis: redis::Client,
+    wizard_timeout: u64,
+    state_ttl: usize,
+    issue_templates: IssueTemplates,
+    // Fetched on the first update, commands addressed to other bots are ignored
+    username: Option<String>,
 }
 
 unsafe impl Send for Bot {}
 
 use url::form_urlencoded::byte_serialize;
 
-fn markdown_escape(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
-    let mut s = try_get_value!("escape_html", "value", String, value);
+fn now_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn full_name(user: &User) -> String {
+    match &user.last_name {
+        Some(last_name) => format!("{} {}", user.first_name, last_name),
+        None => user.first_name.clone(),
+    }
+}
+
+fn state_key(chat: ChatId, uid: UserId) -> String {
+    format!("state:{}:{}", chat, uid)
+}
+
+/// Private chats share ids with their users, and group ids are negative
+fn settings_key(chat: i64) -> String {
+    if chat > 0 {
+        format!("user_settings:{}", chat)
+    } else {
+        format!("settings:{}", chat)
+    }
+}
+
+fn format_ago(secs: u64) -> String {
+    let (value, unit) = if secs >= 86400 {
+        (secs / 86400, "day")
+    } else if secs >= 3600 {
+        (secs / 3600, "hour")
+    } else if secs >= 60 {
+        (secs / 60, "minute")
+    } else {
+        (secs, "second")
+    };
+    if value == 1 {
+        format!("1 {} ago", unit)
+    } else {
+        format!("{} {}s ago", value, unit)
+    }
+}
+
+/// Splits text into messages Telegram accepts, at line breaks when possible
+fn split_message(text: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut part_len = 0;
+    for line in text.lines() {
+        let len = line.chars().count() + 1;
+        if part_len + len > MAX_MESSAGE_LENGTH && !part.is_empty() {
+            parts.push(part.split_off(0));
+            part_len = 0;
+        }
+        if len > MAX_MESSAGE_LENGTH {
+            let chars: Vec<char> = line.chars().collect();
+            for chunk in chars.chunks(MAX_MESSAGE_LENGTH) {
+                parts.push(chunk.iter().collect());
+            }
+            continue;
+        }
+        part.push_str(line);
+        part.push('\n');
+        part_len += len;
+    }
+    if !part.trim().is_empty() {
+        parts.push(part);
+    }
+    parts
+}
+
+/// Query limited by a scope from user_scope
+fn scoped_query(scope: &str, query: &str) -> String {
+    if query.trim().is_empty() {
+        scope.to_string()
+    } else {
+        format!("{} ({})", scope, query)
+    }
+}
+
+/// Escapes the characters Telegram Markdown treats as markup
+fn escape_markdown(text: &str) -> String {
+    let mut s = text.to_string();
     let escaped_chars = vec!['_', '*', '`', '['];
     for c in escaped_chars {
         s = s.replace(c, format!("\\{}", c).as_str())
     }
-    Ok(Value::String(s))
+    s
+}
+
+fn markdown_escape(value: &Value, _: &HashMap<String, Value>) -> tera::Result<Value> {
+    let s = try_get_value!("escape_html", "value", String, value);
+    Ok(Value::String(escape_markdown(&s)))
 }
 
 impl Bot {
@@ -121,11 +640,20 @@ impl Bot {
             api: opts.telegram_api(),
             yt: opts.youtrack_api()?,
             templates,
-            backlog_query: byte_serialize(opts.youtrack_backlog.as_bytes()).collect(),
+            backlog_query: opts.youtrack_backlog.clone(),
             yt_oauth: opts.oauth_client(),
             csrf_tokens: HashMap::new(),
             yt_tokens: TtlCache::new(100),
+            telegram_token: opts.telegram_token.clone(),
+            telegram_client: opts.telegram_client(),
             redis: redis::Client::open(opts.redis_url)?,
+            wizard_timeout: opts.wizard_timeout,
+            state_ttl: opts.state_ttl,
+            issue_templates: match &opts.issue_templates {
+                Some(path) => IssueTemplate::load(path)?,
+                None => Vec::new(),
+            },
+            username: None,
         })
     }
 
@@ -142,14 +670,22 @@ impl Bot {
         message: &Message,
         b: &BacklogParams,
     ) -> Result<UserStateMessages> {
-        self.fetch_issues(message.from.id, message, b).await
+        self.fetch_issues(message.from.id, message, b).await?;
+        Ok(UserStateMessages::Noop(Noop {}))
     }
 
-    async fn _fetch_issues(&self, yt: &YouTrack, top: i32, skip: i32) -> Result<Issues> {
+    async fn _fetch_issues(
+        &self,
+        yt: &YouTrack,
+        query: &str,
+        top: i32,
+        skip: i32,
+    ) -> Result<Issues> {
+        let query: String = byte_serialize(query.as_bytes()).collect();
         let issues = yt
             .get()
             .issues()
-            .query(self.backlog_query.as_str())
+            .query(query.as_str())
             .top(top.to_string().as_str())
             .skip(skip.to_string().as_str())
             .fields("idReadable,summary,votes,voters(hasVote)")
@@ -175,27 +711,26 @@ impl Bot {
         Project::list(&self.yt).await
     }
 
-    async fn get_project(&self, name: String) -> Result<Project> {
-        let projects = self.get_projects().await?;
-        let name = Some(name);
-        match projects.binary_search_by_key(&name, |p| p.name.clone()) {
-            Ok(r) => Ok(projects.get(r).unwrap().clone()),
-            Err(_) => bail!("No such project"),
-        }
-    }
-
     pub async fn fetch_issues(
         &self,
         user: UserId,
         msg: &Message,
         params: &BacklogParams,
-    ) -> Result<UserStateMessages> {
+    ) -> Result<()> {
+        let config = self.get_chat_config(i64::from(msg.chat.id()))?;
+        let query = config.backlog_query.as_ref().unwrap_or(&self.backlog_query);
         match self.get_youtrack(user).await {
             Some(yt) => {
-                match self._fetch_issues(yt, params.top, params.skip).await {
+                match self._fetch_issues(yt, query, params.top, params.skip).await {
                     Ok(issues) => {
                         debug!("{}", issues.len());
-                        let kb = backlog_keyboard(&issues, &params);
+                        // Only bot messages can be in selection mode
+                        let selection = if msg.from.is_bot {
+                            self.get_backlog_selection(msg)?
+                        } else {
+                            None
+                        };
+                        let kb = backlog_keyboard(&issues, &params, selection.as_ref());
                         let mut txt_msg: String = "No issues to display".to_string();
                         if issues.len() > 0 {
                             let mut context = Context::new();
@@ -204,6 +739,14 @@ impl Bot {
                             context.insert("youtrack_url", &self.yt.get_uri());
                             txt_msg = self.templates.render("issues_list.md", &context).unwrap();
                         }
+                        if let Some(selection) = &selection {
+                            let selected = if selection.issues.is_empty() {
+                                "tap issues to select them".to_string()
+                            } else {
+                                escape_markdown(&selection.issues.join(", "))
+                            };
+                            txt_msg.push_str(&format!("\n*Selected*: {}", selected));
+                        }
 
                         // TODO: check whether original message is from our bot
                         if msg.from.is_bot {
@@ -223,19 +766,13 @@ impl Bot {
                                 )
                                 .await?;
                         };
-                        if params.skip == 0 {
-                            Ok(UserStateMessages::StartBacklog(StartBacklog(
-                                params.clone(),
-                            )))
-                        } else {
-                            Ok(UserStateMessages::BacklogPage(BacklogPage(params.clone())))
-                        }
+                        Ok(())
                     }
                     Err(e) => {
                         warn!("Error occured: {}", e);
                         self.api
                             .spawn(msg.text_reply(format!("Error occured: {}", e)));
-                        Ok(UserStateMessages::Noop(Noop {}))
+                        Ok(())
                     }
                 }
             }
@@ -244,7 +781,7 @@ impl Bot {
                 self.api.spawn(msg.text_reply(format!(
                     "No valid access token founds, use /login command to login in youtrack"
                 )));
-                Ok(UserStateMessages::Noop(Noop {}))
+                Ok(())
             }
         }
     }
@@ -252,7 +789,7 @@ impl Bot {
     async fn handle_start(&self, msg: &Message) -> Result<UserStateMessages> {
         let mut context = Context::new();
         context.insert("name", &msg.from.first_name);
-        let txt_msg = self.templates.render("start.md", &context).unwrap();
+        let txt_msg = self.render_localized(i64::from(msg.chat.id()), "start.md", &context)?;
         self.api
             .send(msg.text_reply(txt_msg).parse_mode(ParseMode::Markdown))
             .await?;
@@ -283,16 +820,268 @@ impl Bot {
     }
 
     async fn handle_new_issue(&self, msg: &Message) -> Result<UserStateMessages> {
-        let kb = reply_markup!(force_reply);
-        self.api
-            .send(
-                msg.text_reply("Creating new issue. Please, enter issue summary.")
+        let templates = self.get_issue_templates(msg.chat.id())?;
+        if templates.is_empty() {
+            let kb = reply_markup!(force_reply);
+            self.api
+                .send(
+                    msg.text_reply("Creating new issue. Please, enter issue summary.")
+                        .reply_markup(kb),
+                )
+                .await?;
+        } else {
+            let kb = picker_keyboard(&templates, PickerStep::Template, 0, &[]);
+            self.api
+                .send(
+                    msg.text_reply(
+                        "Creating new issue. Pick a template or enter issue summary for a blank issue.",
+                    )
                     .reply_markup(kb),
-            )
-            .await?;
+                )
+                .await?;
+        }
         Ok(UserStateMessages::CreateNewIssue(CreateNewIssue {}))
     }
 
+    /// Chat templates stored in redis followed by the global ones
+    fn get_issue_templates(&self, chat: ChatId) -> Result<IssueTemplates> {
+        let mut con = self.redis.get_connection()?;
+        let key = format!("issue_templates:{}", chat);
+        let mut templates: IssueTemplates = match con.get::<_, Option<String>>(key)? {
+            Some(json) => serde_json::from_str(&json)?,
+            None => Vec::new(),
+        };
+        templates.extend(self.issue_templates.iter().cloned());
+        Ok(templates)
+    }
+
+    /// Project of the issue template, or the chat default one
+    async fn template_project(
+        &self,
+        template: &Option<IssueTemplate>,
+        config: &ChatConfig,
+    ) -> Result<Option<Project>> {
+        let name = template
+            .as_ref()
+            .and_then(|t| t.project.as_ref())
+            .or_else(|| config.project.as_ref());
+        if let Some(name) = name {
+            let projects = self.get_projects().await?;
+            if let Lookup::Found(index) = fuzzy::lookup(name, &projects) {
+                return Ok(projects.into_iter().nth(index));
+            }
+            warn!(
+                "Project {} from issue template or chat config not found",
+                name
+            );
+        }
+        Ok(None)
+    }
+
+    /// Template or chat default value of the project's enum field as (field id, value)
+    async fn template_field_value(
+        &self,
+        template: &Option<IssueTemplate>,
+        config: &ChatConfig,
+        project: &Project,
+        field_name: &str,
+    ) -> Result<Option<(String, String)>> {
+        let value = template
+            .as_ref()
+            .and_then(|t| t.field(field_name))
+            .or_else(|| config.fields.get(field_name));
+        if let Some(value) = value {
+            if let Some(field) = project.get_project_custom_field(field_name) {
+                let bundle: Bundle = project.get_bundle(&self.yt, field_name).await?;
+                let values = bundle.values.unwrap_or_default();
+                if values.iter().any(|v| &v.name == value) {
+                    return Ok(Some((field.id.clone(), value.clone())));
+                }
+            }
+            warn!("{} {} from issue template not found", field_name, value);
+        }
+        Ok(None)
+    }
+
+    /// Fills values missing so far from the issue template and asks for the first
+    /// value the template doesn't provide.
+    async fn continue_draft(
+        &self,
+        msg: &Message,
+        template: Option<IssueTemplate>,
+        summary: String,
+        project: Option<Project>,
+        stream: Option<IssueStream>,
+        issue_type: Option<IssueType>,
+    ) -> Result<UserStateMessages> {
+        let config = self.get_chat_config(i64::from(msg.chat.id()))?;
+        let project = match project {
+            Some(project) => Some(project),
+            None => self.template_project(&template, &config).await?,
+        };
+        let project = match project {
+            Some(project) => project,
+            None => {
+                let projects = self.get_projects().await?;
+                self.ask_pick(
+                    msg,
+                    "Got it. Now select project for the issue.",
+                    PickerStep::Project,
+                    &projects,
+                    &[],
+                );
+                return Ok(UserStateMessages::IssueSummary(IssueSummary(
+                    template, summary,
+                )));
+            }
+        };
+
+        let stream = match stream {
+            Some(stream) => Some(stream),
+            None => self
+                .template_field_value(&template, &config, &project, "Stream")
+                .await?
+                .map(|(id, value)| IssueStream(id, value)),
+        };
+        let stream = match stream {
+            Some(stream) => stream,
+            None => {
+                let streams = project.streams(&self.yt).await?;
+                self.ask_pick(
+                    msg,
+                    "Got it. Now select stream for the issue.",
+                    PickerStep::Stream,
+                    &streams.values.unwrap_or_default(),
+                    &[],
+                );
+                return Ok(UserStateMessages::IssueSummaryProject(IssueSummaryProject(
+                    template, summary, project,
+                )));
+            }
+        };
+
+        let issue_type = match issue_type {
+            Some(issue_type) => Some(issue_type),
+            None => self
+                .template_field_value(&template, &config, &project, "Type")
+                .await?
+                .map(|(id, value)| IssueType(id, value)),
+        };
+        let issue_type = match issue_type {
+            Some(issue_type) => issue_type,
+            None => {
+                let types = project.types(&self.yt).await?;
+                self.ask_pick(
+                    msg,
+                    "Got it. Now select issue type.",
+                    PickerStep::Type,
+                    &types.values.unwrap_or_default(),
+                    &[],
+                );
+                return Ok(UserStateMessages::IssueSummaryProjectStream(
+                    IssueSummaryProjectStream(template, summary, project, stream),
+                ));
+            }
+        };
+
+        if project.get_project_custom_field("Assignee").is_none() {
+            self.ask_tags(msg).await?;
+            return Ok(UserStateMessages::IssueSummaryProjectStreamTypeAssignee(
+                IssueSummaryProjectStreamTypeAssignee(
+                    template, summary, project, stream, issue_type, None,
+                ),
+            ));
+        }
+        let users = project.assignees(&self.yt).await?;
+        self.ask_pick(
+            msg,
+            "Got it. Now select assignee.",
+            PickerStep::Assignee,
+            &users,
+            &assignee_extra_buttons(),
+        );
+        Ok(UserStateMessages::IssueSummaryProjectStreamType(
+            IssueSummaryProjectStreamType(template, summary, project, stream, issue_type),
+        ))
+    }
+
+    async fn ask_tags(&self, msg: &Message) -> Result<()> {
+        let tags = IssueTag::list(&self.yt).await?;
+        let kb = issue_tags_keyboard(&tags, &Vec::new());
+        self.api.spawn(
+            msg.text_reply("Got it. Now select tags and press done.")
+                .reply_markup(kb),
+        );
+        Ok(())
+    }
+
+    fn ask_pick<T: Named>(
+        &self,
+        msg: &Message,
+        text: &str,
+        step: PickerStep,
+        values: &[T],
+        extra: &[InlineKeyboardButton],
+    ) {
+        let kb = picker_keyboard(values, step, 0, extra);
+        self.api.spawn(
+            msg.text_reply(format!("{} Use the buttons or type the name.", text))
+                .reply_markup(kb),
+        );
+    }
+
+    /// Handles input of a picker step. Typed text is fuzzy matched against values,
+    /// buttons select a value or turn the page. Returns index of the selected value.
+    /// Buttons left from other steps of the wizard are ignored.
+    async fn pick<T: Named>(
+        &self,
+        cmd: &BotCommand,
+        step: PickerStep,
+        values: &[T],
+        extra: &[InlineKeyboardButton],
+    ) -> Result<Option<usize>> {
+        match cmd {
+            BotCommand::Text(msg) => {
+                let text = cmd.get_message_text().unwrap_or_default();
+                match fuzzy::lookup(&text, values) {
+                    Lookup::Found(index) => Ok(Some(index)),
+                    Lookup::Ambiguous(found) => {
+                        let kb = suggestions_keyboard(values, step, &found);
+                        self.api.spawn(
+                            msg.text_reply("Did you mean one of these?")
+                                .reply_markup(kb),
+                        );
+                        Ok(None)
+                    }
+                    Lookup::NotFound => {
+                        let kb = picker_keyboard(values, step, 0, extra);
+                        self.api.spawn(
+                            msg.text_reply(format!(
+                                "Nothing matches \"{}\". Try again or pick from the list.",
+                                text
+                            ))
+                            .reply_markup(kb),
+                        );
+                        Ok(None)
+                    }
+                }
+            }
+            BotCommand::PickerSelect(cb, p) if p.step == step && p.index < values.len() => {
+                let msg = cb.message.clone().unwrap();
+                self.api
+                    .spawn(msg.edit_reply_markup(Some(reply_markup!(inline_keyboard, []))));
+                Ok(Some(p.index))
+            }
+            BotCommand::PickerPage(cb, p) if p.step == step => {
+                let msg = cb.message.clone().unwrap();
+                let kb = picker_keyboard(values, step, p.index, extra);
+                self.api.send(msg.edit_reply_markup(Some(kb))).await?;
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
     pub async fn on_auth(&mut self, params: super::yt_oauth::AuthRequest) {
         match self.csrf_tokens.get(&params.state) {
             Some(user_id) => {
@@ -300,7 +1089,12 @@ impl Bot {
                 let mut yt = self.yt.clone();
                 yt.set_token(params.access_token.clone());
 
-                let me = yt.get().users().me().fields("fullName").execute::<Value>();
+                let me = yt
+                    .get()
+                    .users()
+                    .me()
+                    .fields("login,fullName")
+                    .execute::<Value>();
 
                 match me.await {
                     Ok((_, _, v)) => {
@@ -308,6 +1102,11 @@ impl Bot {
 
                         self.yt_tokens
                             .insert(user_id.clone(), yt, params.expires_in_duration());
+                        if let Some(login) = me["login"].as_str() {
+                            if let Err(e) = self.link_youtrack_user(*user_id, login) {
+                                warn!("Unable to link {} to {}: {}", user_id, login, e);
+                            }
+                        }
                         self.api
                             .spawn(user_id.text(format!("Hello, {}!", me["fullName"])));
                     }
@@ -320,14 +1119,2809 @@ impl Bot {
         };
     }
 
-    async fn vote_for_issue(&self, yt: &YouTrack, has_vote: bool, id: String) -> Result<bool> {
-        let json_has_vote = json!({"hasVote": !has_vote});
-        let i = yt.post(json_has_vote).issues();
-        let i = i.id(id.as_str());
-        let i = i.voters().execute::<Value>().await?;
+    /// Remembers which telegram user the YouTrack login belongs to, for notifications
+    fn link_youtrack_user(&self, uid: UserId, login: &str) -> Result<()> {
+        let mut con = self.redis.get_connection()?;
+        let _: () = con.set(format!("yt_login:{}", login), i64::from(uid))?;
+        let _: () = con.set(format!("yt_user:{}", uid), login)?;
+        let _: () = con.sadd("yt_logins", login)?;
+        Ok(())
+    }
 
-        let (headers, status, json) = i;
-        debug!("{:#?}", headers);
+    /// Remembers the issue the bot message refers to, so replies to it become comments
+    fn remember_issue_message(&self, sent: &MessageOrChannelPost, issue_id: &str) -> Result<()> {
+        if let MessageOrChannelPost::Message(sent) = sent {
+            let mut con = self.redis.get_connection()?;
+            let key = format!("issue_message:{}:{}", sent.chat.id(), sent.id);
+            let _: () = con.set_ex(key, issue_id, ISSUE_MESSAGE_TTL)?;
+        }
+        Ok(())
+    }
+
+    /// Remembers whose comment the bot message quotes, so replies mention them
+    fn remember_reply_to(&self, sent: &MessageOrChannelPost, login: &str) -> Result<()> {
+        if let MessageOrChannelPost::Message(sent) = sent {
+            let mut con = self.redis.get_connection()?;
+            let key = format!("reply_to:{}:{}", sent.chat.id(), sent.id);
+            let _: () = con.set_ex(key, login, ISSUE_MESSAGE_TTL)?;
+        }
+        Ok(())
+    }
+
+    fn get_replied_author(&self, msg: &Message) -> Result<Option<String>> {
+        match msg.reply_to_message.as_ref().map(|r| r.as_ref()) {
+            Some(MessageOrChannelPost::Message(reply)) => {
+                let mut con = self.redis.get_connection()?;
+                let key = format!("reply_to:{}:{}", reply.chat.id(), reply.id);
+                Ok(con.get(key)?)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn get_replied_issue(&self, msg: &Message) -> Result<Option<String>> {
+        match msg.reply_to_message.as_ref().map(|r| r.as_ref()) {
+            Some(MessageOrChannelPost::Message(reply)) => {
+                let mut con = self.redis.get_connection()?;
+                let key = format!("issue_message:{}:{}", reply.chat.id(), reply.id);
+                Ok(con.get(key)?)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    async fn handle_comment(
+        &self,
+        msg: &Message,
+        params: &Option<CommentParams>,
+    ) -> Result<UserStateMessages> {
+        match params {
+            Some(params) => self.add_comment(msg, &params.issue, &params.text).await?,
+            None => self
+                .api
+                .spawn(msg.text_reply("Usage: /comment TP-123 comment text")),
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    /// Adds message to the issue comments if it replies to a message about the issue
+    async fn handle_reply(&self, msg: &Message) -> Result<UserStateMessages> {
+        if let Some(issue_id) = self.get_replied_issue(msg)? {
+            match &msg.kind {
+                MessageKind::Text { data, .. } => {
+                    let text = match self.get_replied_author(msg)? {
+                        Some(login) => format!("@{} {}", login, data),
+                        None => data.clone(),
+                    };
+                    self.add_comment(msg, &issue_id, &text).await?
+                }
+                MessageKind::Document { data, caption } => {
+                    let filename = data
+                        .file_name
+                        .clone()
+                        .unwrap_or_else(|| format!("file_{}", msg.id));
+                    self.add_attachment(msg, &issue_id, GetFile::new(data), filename, caption)
+                        .await?
+                }
+                MessageKind::Photo { data, caption, .. } => {
+                    // Sizes go from the smallest to the largest one
+                    if let Some(photo) = data.last() {
+                        let filename = format!("photo_{}.jpg", msg.id);
+                        self.add_attachment(msg, &issue_id, GetFile::new(photo), filename, caption)
+                            .await?
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    async fn add_comment(&self, msg: &Message, issue_id: &str, text: &str) -> Result<()> {
+        match self.get_youtrack(msg.from.id).await {
+            Some(yt) => match IssueComment::create(yt, issue_id, text).await {
+                Ok(_) => {
+                    let sent = self
+                        .api
+                        .send(msg.text_reply(format!("Comment added to {}", issue_id)))
+                        .await?;
+                    self.remember_issue_message(&sent, issue_id)?;
+                }
+                Err(e) => {
+                    warn!("Error occured: {}", e);
+                    self.api
+                        .spawn(msg.text_reply(format!("Error occured: {}", e)));
+                }
+            },
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "No valid access token founds, use /login command to login in youtrack",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_issue_command(
+        &self,
+        msg: &Message,
+        params: &Option<IssueCommandParams>,
+    ) -> Result<UserStateMessages> {
+        let params = match params {
+            Some(params) => params,
+            None => {
+                self.api.spawn(
+                    msg.text_reply("Usage: /cmd TP-123 command, e.g. /cmd TP-123 State Fixed"),
+                );
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        let assist = match self
+            .command_assist(msg.from.id, msg, &params.issue, &params.query)
+            .await?
+        {
+            Some(assist) => assist,
+            None => return Ok(UserStateMessages::Noop(Noop {})),
+        };
+        if assist.is_valid() {
+            self.apply_command(msg.from.id, msg, &params.issue, &params.query)
+                .await?;
+            Ok(UserStateMessages::Noop(Noop {}))
+        } else {
+            self.show_command(msg, &params.issue, &params.query, &assist, false)
+                .await?;
+            Ok(UserStateMessages::StartCommand(StartCommand(
+                params.issue.clone(),
+                params.query.clone(),
+                Vec::new(),
+            )))
+        }
+    }
+
+    /// Parses the command, errors are reported to the user
+    async fn command_assist(
+        &self,
+        user: UserId,
+        msg: &Message,
+        issue: &str,
+        query: &str,
+    ) -> Result<Option<CommandAssist>> {
+        let yt = match self.get_youtrack(user).await {
+            Some(yt) => yt,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "No valid access token founds, use /login command to login in youtrack",
+                ));
+                return Ok(None);
+            }
+        };
+        match CommandAssist::fetch(yt, issue, query).await {
+            Ok(assist) => Ok(Some(assist)),
+            Err(e) => {
+                warn!("Error occured: {}", e);
+                self.api
+                    .spawn(msg.text_reply(format!("Error occured: {}", e)));
+                Ok(None)
+            }
+        }
+    }
+
+    /// Shows parsed command with completions, `issue` is what it is applied to
+    async fn show_command(
+        &self,
+        msg: &Message,
+        issue: &str,
+        query: &str,
+        assist: &CommandAssist,
+        edit: bool,
+    ) -> Result<()> {
+        let mut context = Context::new();
+        context.insert("issue", issue);
+        context.insert("query", query);
+        context.insert("commands", &assist.commands);
+        let txt_msg = self.templates.render("command.md", &context).unwrap();
+        let kb = command_keyboard(assist);
+        if edit {
+            self.api
+                .send(
+                    msg.edit_text(txt_msg)
+                        .reply_markup(kb)
+                        .parse_mode(ParseMode::Markdown),
+                )
+                .await?;
+        } else {
+            let sent = self
+                .api
+                .send(
+                    msg.text_reply(txt_msg)
+                        .reply_markup(kb)
+                        .parse_mode(ParseMode::Markdown),
+                )
+                .await?;
+            if let MessageOrChannelPost::Message(sent) = sent {
+                let mut con = self.redis.get_connection()?;
+                let key = format!("command_message:{}:{}", sent.chat.id(), sent.id);
+                let _: () = con.set_ex(key, issue, self.state_ttl)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the update is composer input: its buttons, /cancel or a reply to its message
+    fn is_command_reply(&self, cmd: &BotCommand) -> Result<bool> {
+        let msg = match cmd {
+            BotCommand::Text(msg) => msg,
+            BotCommand::CommandSuggestion(_, _)
+            | BotCommand::CommandApply(_)
+            | BotCommand::CommandStop(_)
+            | BotCommand::Cancel(_) => return Ok(true),
+            _ => return Ok(false),
+        };
+        match msg.reply_to_message.as_ref().map(|r| r.as_ref()) {
+            Some(MessageOrChannelPost::Message(reply)) => {
+                let mut con = self.redis.get_connection()?;
+                let key = format!("command_message:{}:{}", reply.chat.id(), reply.id);
+                Ok(con.exists(key)?)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Applies the command to the issue, returns whether it succeeded
+    async fn apply_command(
+        &self,
+        user: UserId,
+        msg: &Message,
+        issue: &str,
+        query: &str,
+    ) -> Result<bool> {
+        let yt = match self.get_youtrack(user).await {
+            Some(yt) => yt,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "No valid access token founds, use /login command to login in youtrack",
+                ));
+                return Ok(false);
+            }
+        };
+        match CommandAssist::apply(yt, issue, query).await {
+            Ok(_) => {
+                let sent = self
+                    .api
+                    .send(msg.text_reply(format!("Command applied to {}: {}", issue, query)))
+                    .await?;
+                self.remember_issue_message(&sent, issue)?;
+                Ok(true)
+            }
+            Err(e) => {
+                warn!("Error occured: {}", e);
+                self.api
+                    .spawn(msg.text_reply(format!("Error occured: {}", e)));
+                Ok(false)
+            }
+        }
+    }
+
+    /// Applies the command to the issues selected in the backlog at once
+    async fn apply_bulk_command(
+        &self,
+        user: UserId,
+        msg: &Message,
+        issues: &[String],
+        query: &str,
+    ) -> Result<bool> {
+        let yt = match self.get_youtrack(user).await {
+            Some(yt) => yt,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "No valid access token founds, use /login command to login in youtrack",
+                ));
+                return Ok(false);
+            }
+        };
+        let ids: Vec<&str> = issues.iter().map(|i| i.as_str()).collect();
+        match CommandAssist::apply_to_all(yt, &ids, query).await {
+            Ok(_) => {
+                self.api.spawn(msg.text_reply(format!(
+                    "Command applied to {}: {}",
+                    issues.join(", "),
+                    query
+                )));
+                Ok(true)
+            }
+            Err(e) => {
+                warn!("Error occured: {}", e);
+                self.api
+                    .spawn(msg.text_reply(format!("Error occured: {}", e)));
+                Ok(false)
+            }
+        }
+    }
+
+    async fn handle_issue_card(
+        &self,
+        msg: &Message,
+        issue_id: &Option<String>,
+    ) -> Result<UserStateMessages> {
+        match (issue_id, self.get_youtrack(msg.from.id).await) {
+            (None, _) => self.api.spawn(msg.text_reply("Usage: /issue TP-123")),
+            (Some(issue_id), Some(yt)) => {
+                if let Err(e) = self.show_issue_card(yt, msg, issue_id).await {
+                    warn!("Error occured: {}", e);
+                    self.api
+                        .spawn(msg.text_reply(format!("Error occured: {}", e)));
+                }
+            }
+            (Some(_), None) => self.api.spawn(msg.text_reply(
+                "No valid access token founds, use /login command to login in youtrack",
+            )),
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    /// Sends the issue card, or updates it in place when given the card message
+    async fn show_issue_card(&self, yt: &YouTrack, msg: &Message, issue_id: &str) -> Result<()> {
+        let card = IssueCard::get(yt, issue_id).await?;
+        let states = card.next_states(yt).await?;
+        let kb = issue_card_keyboard(&card, &states);
+
+        let mut context = Context::new();
+        context.insert("issue", &card);
+        context.insert("fields", &card.field_values());
+        context.insert("youtrack_url", &self.yt.get_uri());
+        let txt_msg = self.templates.render("issue.md", &context).unwrap();
+
+        if msg.from.is_bot {
+            self.api
+                .send(
+                    msg.edit_text(txt_msg)
+                        .reply_markup(kb)
+                        .parse_mode(ParseMode::Markdown),
+                )
+                .await?;
+        } else {
+            let sent = self
+                .api
+                .send(
+                    msg.text_reply(txt_msg)
+                        .reply_markup(kb)
+                        .parse_mode(ParseMode::Markdown),
+                )
+                .await?;
+            self.remember_issue_message(&sent, &card.id_readable)?;
+        }
+        Ok(())
+    }
+
+    async fn handle_backlog_action(&self, cmd: BotCommand) -> Result<UserStateMessages> {
+        match &cmd {
+            BotCommand::BacklogStop(cb) => {
+                let msg = cb.message.clone().unwrap();
+                self.api
+                    .send(msg.edit_reply_markup(Some(reply_markup!(inline_keyboard, []))))
+                    .await?;
+            }
+            BotCommand::BacklogNext(cb, p) | BotCommand::BacklogPrev(cb, p) => {
+                let msg = cb.message.clone().unwrap();
+                self.fetch_issues(cb.from.id, &msg, p).await?;
+            }
+            BotCommand::BacklogVoteForIssue(cb, p) => {
+                let msg = cb.message.clone().unwrap();
+                let user = cb.from.id;
+                match self.get_youtrack(user).await {
+                    Some(yt) => match self.vote_for_issue(yt, p.has_vote, p.id.clone()).await {
+                        Ok(_) => {
+                            let page = BacklogParams::new_with_skip(BACKLOG_PAGE_SIZE, p.skip);
+                            self.fetch_issues(user, &msg, &page).await?;
+                        }
+                        Err(e) => {
+                            warn!("Error occured: {}", e);
+                            self.api
+                                .spawn(msg.text_reply(format!("Error occured: {}", e)));
+                        }
+                    },
+                    None => {
+                        warn!("No youtrack instance for user {}", user);
+                        self.api.spawn(msg.text_reply(format!(
+                            "No valid access token founds, use /login command to login in youtrack"
+                        )));
+                    }
+                }
+            }
+            BotCommand::BacklogSelect(cb, p) => {
+                let msg = cb.message.clone().unwrap();
+                self.save_backlog_selection(&msg, &BacklogSelection::default())?;
+                self.fetch_issues(cb.from.id, &msg, p).await?;
+            }
+            BotCommand::BacklogSelectDone(cb, p) => {
+                let msg = cb.message.clone().unwrap();
+                self.clear_backlog_selection(&msg)?;
+                self.fetch_issues(cb.from.id, &msg, p).await?;
+            }
+            BotCommand::SelectIssue(cb, p) => {
+                let msg = cb.message.clone().unwrap();
+                // Selection mode may have been left by someone else meanwhile
+                if let Some(mut selection) = self.get_backlog_selection(&msg)? {
+                    selection.toggle(&p.id);
+                    self.save_backlog_selection(&msg, &selection)?;
+                }
+                let page = BacklogParams::new_with_skip(BACKLOG_PAGE_SIZE, p.skip);
+                self.fetch_issues(cb.from.id, &msg, &page).await?;
+            }
+            BotCommand::BulkVote(cb, p) => {
+                let msg = cb.message.clone().unwrap();
+                let selected = self.selected_issues(&msg)?;
+                if selected.is_empty() {
+                    self.api.spawn(msg.text_reply("Select issues first"));
+                    return Ok(UserStateMessages::Noop(Noop {}));
+                }
+                let yt = match self.get_youtrack(cb.from.id).await {
+                    Some(yt) => yt,
+                    None => {
+                        self.api.spawn(msg.text_reply(
+                            "No valid access token founds, use /login command to login in youtrack",
+                        ));
+                        return Ok(UserStateMessages::Noop(Noop {}));
+                    }
+                };
+                let mut voted = 0;
+                for id in selected.iter() {
+                    match self.vote_for_issue(yt, false, id.clone()).await {
+                        Ok(_) => voted += 1,
+                        Err(e) => warn!("Unable to vote for {}: {}", id, e),
+                    }
+                }
+                self.clear_backlog_selection(&msg)?;
+                self.api.spawn(msg.text_reply(format!(
+                    "Voted for {} of {} selected issues",
+                    voted,
+                    selected.len()
+                )));
+                self.fetch_issues(cb.from.id, &msg, p).await?;
+            }
+            BotCommand::ApplyPriorityVotes(cb, p) => {
+                let msg = cb.message.clone().unwrap();
+                self.apply_priority_votes(&msg, cb.from.id, &p.poll).await?;
+            }
+            _ => {}
+        };
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    fn get_backlog_selection(&self, msg: &Message) -> Result<Option<BacklogSelection>> {
+        let mut con = self.redis.get_connection()?;
+        let key = backlog::selection_key(i64::from(msg.chat.id()), i64::from(msg.id));
+        Ok(con.get(key)?)
+    }
+
+    fn save_backlog_selection(&self, msg: &Message, selection: &BacklogSelection) -> Result<()> {
+        let mut con = self.redis.get_connection()?;
+        let key = backlog::selection_key(i64::from(msg.chat.id()), i64::from(msg.id));
+        let _: () = con.set_ex(key, selection, SELECTION_TTL)?;
+        Ok(())
+    }
+
+    fn clear_backlog_selection(&self, msg: &Message) -> Result<()> {
+        let mut con = self.redis.get_connection()?;
+        let key = backlog::selection_key(i64::from(msg.chat.id()), i64::from(msg.id));
+        let _: () = con.del(key)?;
+        Ok(())
+    }
+
+    fn selected_issues(&self, msg: &Message) -> Result<Vec<String>> {
+        Ok(self
+            .get_backlog_selection(msg)?
+            .map(|selection| selection.issues)
+            .unwrap_or_default())
+    }
+
+    /// Starts composing a command for the issues selected in the backlog, the
+    /// selection is handed over to the command and the backlog leaves selection mode
+    async fn start_bulk_command(
+        &self,
+        cb: &CallbackQuery,
+        params: &BulkCommandParams,
+    ) -> Result<UserStateMessages> {
+        let msg = cb.message.clone().unwrap();
+        let selected = self.selected_issues(&msg)?;
+        let issue = match selected.first() {
+            Some(issue) => issue.clone(),
+            None => {
+                self.api.spawn(msg.text_reply("Select issues first"));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        let query = params.action.prefix().to_string();
+        // Completions come from the first issue, they only differ between projects
+        let assist = match self
+            .command_assist(cb.from.id, &msg, &issue, &query)
+            .await?
+        {
+            Some(assist) => assist,
+            None => return Ok(UserStateMessages::Noop(Noop {})),
+        };
+        self.clear_backlog_selection(&msg)?;
+        let page = BacklogParams::new_with_skip(BACKLOG_PAGE_SIZE, params.skip);
+        self.fetch_issues(cb.from.id, &msg, &page).await?;
+        self.show_command(&msg, &selected.join(", "), &query, &assist, false)
+            .await?;
+        Ok(UserStateMessages::StartCommand(StartCommand(
+            issue, query, selected,
+        )))
+    }
+
+    async fn handle_issue_card_action(&self, cmd: BotCommand) -> Result<UserStateMessages> {
+        match &cmd {
+            BotCommand::ShowHistory(cb, p) => {
+                let msg = cb.message.clone().unwrap();
+                let params = HistoryParams::new(p.id.clone());
+                self.show_history(cb.from.id, &msg, &params, false).await?;
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+            BotCommand::HistoryPage(cb, p) => {
+                let msg = cb.message.clone().unwrap();
+                self.show_history(cb.from.id, &msg, p, true).await?;
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+            BotCommand::AddToSprint(cb, p) => {
+                self.plan_issue(cb, &p.id, false).await?;
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+            BotCommand::MoveToNextSprint(cb, p) => {
+                self.plan_issue(cb, &p.id, true).await?;
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+            _ => {}
+        }
+        let (cb, issue_id) = match &cmd {
+            BotCommand::SetIssueState(cb, p) => (cb, &p.issue),
+            BotCommand::AssignIssueToMe(cb, p) => (cb, &p.id),
+            _ => return Ok(UserStateMessages::Noop(Noop {})),
+        };
+        let msg = cb.message.clone().unwrap();
+        let yt = match self.get_youtrack(cb.from.id).await {
+            Some(yt) => yt,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "No valid access token founds, use /login command to login in youtrack",
+                ));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        let card = IssueCard::get(yt, issue_id).await?;
+        let update = match &cmd {
+            BotCommand::SetIssueState(_, p) => match card.field("State") {
+                Some(field) => Some(IssueDraftCustomField::state(
+                    field.id.clone(),
+                    field.name.clone(),
+                    p.state.clone(),
+                )),
+                None => None,
+            },
+            _ => match card.field("Assignee") {
+                Some(field) => {
+                    let me = YouTrackUser::me(yt).await?;
+                    Some(IssueDraftCustomField::user(
+                        field.id.clone(),
+                        field.name.clone(),
+                        me.login,
+                    ))
+                }
+                None => None,
+            },
+        };
+        let res = match update {
+            Some(field) => IssueCard::update_field(yt, issue_id, field).await,
+            None => Err("Issue has no such field".into()),
+        };
+        match res {
+            Ok(_) => self.show_issue_card(yt, &msg, issue_id).await?,
+            Err(e) => {
+                warn!("Error occured: {}", e);
+                self.api
+                    .spawn(msg.text_reply(format!("Error occured: {}", e)));
+            }
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    /// Puts the issue into the current or the next sprint of the chat board
+    async fn plan_issue(&self, cb: &CallbackQuery, issue_id: &str, next: bool) -> Result<()> {
+        let msg = cb.message.clone().unwrap();
+        let yt = match self.get_youtrack(cb.from.id).await {
+            Some(yt) => yt,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "No valid access token founds, use /login command to login in youtrack",
+                ));
+                return Ok(());
+            }
+        };
+        let agile = match self.find_board(yt, &msg, &None, false).await? {
+            Some(agile_id) => Agile::get(yt, &agile_id).await?,
+            None => return Ok(()),
+        };
+        let sprints = Agile::sprints(yt, &agile.id).await?;
+        let current = agile
+            .current_sprint
+            .as_ref()
+            .and_then(|c| sprints.iter().find(|s| s.id == c.id));
+        let target = if next {
+            agile.next_sprint(&sprints)
+        } else {
+            current
+        };
+        let target = match target {
+            Some(target) => target,
+            None => {
+                self.api.spawn(msg.text_reply(format!(
+                    "Board {} has no {} sprint",
+                    agile.name,
+                    if next { "next" } else { "current" }
+                )));
+                return Ok(());
+            }
+        };
+
+        let card = IssueCard::get(yt, issue_id).await?;
+        let res = async {
+            if let (true, Some(current)) = (next, current) {
+                current.remove_issue(yt, &agile.id, &card.id).await?;
+            }
+            target.add_issue(yt, &agile.id, &card.id).await
+        };
+        match res.await {
+            Ok(_) => self
+                .api
+                .spawn(msg.text_reply(format!("{} is in {} now", card.id_readable, target.name))),
+            Err(e) => {
+                warn!("Error occured: {}", e);
+                self.api
+                    .spawn(msg.text_reply(format!("Error occured: {}", e)));
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_spent(
+        &self,
+        msg: &Message,
+        params: &Option<SpentParams>,
+    ) -> Result<UserStateMessages> {
+        match params {
+            Some(params) => {
+                let res = self
+                    .log_work(
+                        msg,
+                        &params.issue,
+                        params.minutes,
+                        params.comment.clone(),
+                        &params.work_type,
+                    )
+                    .await;
+                if let Err(e) = res {
+                    warn!("Error occured: {}", e);
+                    self.api
+                        .spawn(msg.text_reply(format!("Error occured: {}", e)));
+                }
+            }
+            None => self
+                .api
+                .spawn(msg.text_reply("Usage: /spent TP-123 1h30m optional comment [work type]")),
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    /// Posts work item to the issue, returns whether it was logged
+    async fn log_work(
+        &self,
+        msg: &Message,
+        issue_id: &str,
+        minutes: u64,
+        comment: Option<String>,
+        work_type: &Option<String>,
+    ) -> Result<bool> {
+        let yt = match self.get_youtrack(msg.from.id).await {
+            Some(yt) => yt,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "No valid access token founds, use /login command to login in youtrack",
+                ));
+                return Ok(false);
+            }
+        };
+        let work_type = match work_type {
+            Some(name) => {
+                let project = match IssueCard::get(yt, issue_id).await?.project {
+                    Some(project) => project,
+                    None => bail!("Issue has no project"),
+                };
+                let types = WorkItemType::list(yt, &project.id).await?;
+                match fuzzy::lookup(name, &types) {
+                    Lookup::Found(index) => Some(types[index].clone()),
+                    _ => {
+                        let names: Vec<String> = types.iter().map(|t| t.title()).collect();
+                        self.api.spawn(msg.text_reply(format!(
+                            "Unknown work type {}, available ones: {}",
+                            name,
+                            names.join(", ")
+                        )));
+                        return Ok(false);
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let mut item = WorkItemDraft::new(minutes, now_timestamp() * 1000);
+        item.text(comment).work_type(work_type);
+        item.create(yt, issue_id).await?;
+
+        let sent = self
+            .api
+            .send(msg.text_reply(format!(
+                "{} logged to {}",
+                format_minutes(minutes),
+                issue_id
+            )))
+            .await?;
+        self.remember_issue_message(&sent, issue_id)?;
+        Ok(true)
+    }
+
+    async fn handle_timer(&self, msg: &Message, arg: &Option<String>) -> Result<UserStateMessages> {
+        let mut con = self.redis.get_connection()?;
+        let key = format!("timer:{}", msg.from.id);
+        let running: Option<RunningTimer> = con.get(&key)?;
+        match (arg.as_ref().map(|a| a.as_str()), running) {
+            (Some("stop"), Some(timer)) => {
+                let elapsed = now_timestamp().saturating_sub(timer.started_at);
+                // Started minutes count, so short timers are not lost
+                let minutes = (elapsed + 59) / 60;
+                match self.log_work(msg, &timer.issue, minutes, None, &None).await {
+                    Ok(true) => {
+                        let _: () = con.del(&key)?;
+                    }
+                    Ok(false) => {}
+                    // The timer is dropped anyway, so a broken issue doesn't block new ones
+                    Err(e) => {
+                        warn!("Error occured: {}", e);
+                        let _: () = con.del(&key)?;
+                        self.api.spawn(msg.text_reply(format!(
+                            "Error occured: {}\nTimer discarded, log {} minutes with /spent {} {}m",
+                            e, minutes, timer.issue, minutes
+                        )));
+                    }
+                }
+            }
+            (Some("stop"), None) | (None, None) => self
+                .api
+                .spawn(msg.text_reply("No timer is running, use /timer TP-123 to start one")),
+            (None, Some(timer)) => self.api.spawn(msg.text_reply(format!(
+                "Timer for {} was started {}",
+                timer.issue,
+                format_ago(now_timestamp().saturating_sub(timer.started_at))
+            ))),
+            (Some(_), Some(timer)) => self.api.spawn(msg.text_reply(format!(
+                "Timer for {} is already running, use /timer stop to log it first",
+                timer.issue
+            ))),
+            (Some(issue), None) => {
+                let yt = match self.get_youtrack(msg.from.id).await {
+                    Some(yt) => yt,
+                    None => {
+                        self.api.spawn(msg.text_reply(
+                            "No valid access token founds, use /login command to login in youtrack",
+                        ));
+                        return Ok(UserStateMessages::Noop(Noop {}));
+                    }
+                };
+                let issue = match IssueCard::get(yt, issue).await {
+                    Ok(card) => card.id_readable,
+                    Err(e) => {
+                        self.api.spawn(
+                            msg.text_reply(format!("Unable to start timer for {}: {}", issue, e)),
+                        );
+                        return Ok(UserStateMessages::Noop(Noop {}));
+                    }
+                };
+                let timer = RunningTimer {
+                    issue: issue.clone(),
+                    started_at: now_timestamp(),
+                };
+                let _: () = con.set(&key, timer)?;
+                self.api.spawn(msg.text_reply(format!(
+                    "Timer for {} started, use /timer stop to log the time",
+                    issue
+                )));
+            }
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    async fn handle_timesheet(
+        &self,
+        msg: &Message,
+        params: &Option<TimesheetParams>,
+    ) -> Result<UserStateMessages> {
+        let params = match params {
+            Some(params) => params,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "Usage: /timesheet [week|month] [@login|@all] [csv], login is a YouTrack login",
+                ));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        let yt = match self.get_youtrack(msg.from.id).await {
+            Some(yt) => yt,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "No valid access token founds, use /login command to login in youtrack",
+                ));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+
+        let (from, to) =
+            timesheet::period_range(&params.period, chrono::Local::today().naive_local());
+        let (author, label) = match &params.author {
+            TimesheetAuthor::Me => (Some("me"), None),
+            TimesheetAuthor::User(login) => (Some(login.as_str()), Some(login.as_str())),
+            TimesheetAuthor::Team => (None, Some("everyone")),
+        };
+        let from_date = from.format("%Y-%m-%d").to_string();
+        let to_date = to.format("%Y-%m-%d").to_string();
+        let items = match WorkItem::list(yt, author, &from_date, &to_date).await {
+            Ok(items) => items,
+            Err(e) => {
+                warn!("Error occured: {}", e);
+                self.api
+                    .spawn(msg.text_reply(format!("Error occured: {}", e)));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        if items.is_empty() {
+            self.api
+                .spawn(msg.text_reply(format!("No work logged from {} to {}", from_date, to_date)));
+            return Ok(UserStateMessages::Noop(Noop {}));
+        }
+
+        let mut context = Context::new();
+        context.insert("timesheet", &Timesheet::new(from, to, &items));
+        context.insert("author", &label);
+        context.insert("youtrack_url", &self.yt.get_uri());
+        let txt_msg = self.templates.render("timesheet.md", &context).unwrap();
+
+        // The report may be long, the file goes first so a failed message doesn't lose it
+        if params.csv {
+            let csv = timesheet::to_csv(&items);
+            let file = InputFileUpload::with_data(
+                csv.into_bytes(),
+                format!("timesheet_{}_{}.csv", from_date, to_date),
+            );
+            self.api
+                .send(SendDocument::new(msg.chat.id(), file))
+                .await?;
+        }
+        for part in split_message(&txt_msg) {
+            self.api
+                .send(msg.text_reply(part).parse_mode(ParseMode::Markdown))
+                .await?;
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    async fn handle_history(
+        &self,
+        msg: &Message,
+        issue_id: &Option<String>,
+    ) -> Result<UserStateMessages> {
+        match issue_id {
+            Some(issue_id) => {
+                let params = HistoryParams::new(issue_id.clone());
+                self.show_history(msg.from.id, msg, &params, false).await?
+            }
+            None => self.api.spawn(msg.text_reply("Usage: /history TP-123")),
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    /// Shows a page of issue activities, either as a new message or in place of the given one
+    async fn show_history(
+        &self,
+        user: UserId,
+        msg: &Message,
+        params: &HistoryParams,
+        edit: bool,
+    ) -> Result<()> {
+        let yt = match self.get_youtrack(user).await {
+            Some(yt) => yt,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "No valid access token founds, use /login command to login in youtrack",
+                ));
+                return Ok(());
+            }
+        };
+        // Pages are cursor based, cursors are too long for callback data
+        let mut con = self.redis.get_connection()?;
+        let cursor: Option<String> = if edit && params.page > 0 {
+            let key = format!("history:{}:{}", msg.chat.id(), msg.id);
+            match con.hget(&key, params.page)? {
+                Some(cursor) => Some(cursor),
+                None => {
+                    self.api.spawn(msg.text_reply(format!(
+                        "This history page has expired, use /history {} again",
+                        params.issue
+                    )));
+                    return Ok(());
+                }
+            }
+        } else {
+            None
+        };
+        let page = match ActivityPage::fetch(yt, &params.issue, cursor.as_deref()).await {
+            Ok(page) => page,
+            Err(e) => {
+                warn!("Error occured: {}", e);
+                self.api
+                    .spawn(msg.text_reply(format!("Error occured: {}", e)));
+                return Ok(());
+            }
+        };
+
+        let activities: Vec<(String, String, String)> = page
+            .activities
+            .iter()
+            .map(|a| {
+                (
+                    chrono::Local
+                        .timestamp_millis(a.timestamp)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string(),
+                    a.author.display_name(),
+                    a.describe(),
+                )
+            })
+            .collect();
+        let mut context = Context::new();
+        context.insert("issue", &params.issue);
+        context.insert("activities", &activities);
+        let txt_msg = self.templates.render("history.md", &context).unwrap();
+
+        let mut kb = InlineKeyboardMarkup::new();
+        let mut nav = Vec::new();
+        if let Some(prev) = params.prev() {
+            nav.push(CallbackParams::HistoryPrev(prev).into());
+        }
+        if page.has_before {
+            nav.push(CallbackParams::HistoryNext(params.next()).into());
+        }
+        if !nav.is_empty() {
+            kb.add_row(nav);
+        }
+
+        let history_msg = if edit {
+            self.api
+                .send(
+                    msg.edit_text(txt_msg)
+                        .reply_markup(kb)
+                        .parse_mode(ParseMode::Markdown),
+                )
+                .await?;
+            msg.id
+        } else {
+            let sent = self
+                .api
+                .send(
+                    msg.text_reply(txt_msg)
+                        .reply_markup(kb)
+                        .parse_mode(ParseMode::Markdown),
+                )
+                .await?;
+            match sent {
+                MessageOrChannelPost::Message(sent) => sent.id,
+                MessageOrChannelPost::ChannelPost(_) => return Ok(()),
+            }
+        };
+        if let (true, Some(cursor)) = (page.has_before, &page.before_cursor) {
+            let key = format!("history:{}:{}", msg.chat.id(), history_msg);
+            let _: () = con.hset(&key, params.page + 1, cursor)?;
+            let _: () = con.expire(&key, ISSUE_MESSAGE_TTL)?;
+        }
+        Ok(())
+    }
+
+    async fn handle_watch(
+        &self,
+        msg: &Message,
+        query: &Option<String>,
+    ) -> Result<UserStateMessages> {
+        let mut con = self.redis.get_connection()?;
+        let chat = i64::from(msg.chat.id());
+        let key = format!("watches:{}", chat);
+        match query {
+            Some(query) => {
+                let yt = match self.get_youtrack(msg.from.id).await {
+                    Some(yt) => yt,
+                    None => {
+                        self.api.spawn(msg.text_reply(
+                            "No valid access token founds, use /login command to login in youtrack",
+                        ));
+                        return Ok(UserStateMessages::Noop(Noop {}));
+                    }
+                };
+                let scope = match self.user_scope(yt).await? {
+                    Some(scope) => scope,
+                    None => {
+                        self.api
+                            .spawn(msg.text_reply("You don't have access to any project"));
+                        return Ok(UserStateMessages::Noop(Noop {}));
+                    }
+                };
+                let watch = Watch {
+                    query: query.clone(),
+                    since: now_timestamp() as i64 * 1000,
+                    cursor: None,
+                    scope: Some(scope),
+                };
+                let _: () = con.hset(&key, query, watch)?;
+                let _: () = con.sadd("watch_chats", chat)?;
+                self.api.spawn(
+                    msg.text_reply(format!("Watching {}, changes will be posted here", query)),
+                );
+            }
+            None => {
+                let watches: HashMap<String, Watch> = con.hgetall(&key)?;
+                if watches.is_empty() {
+                    self.api
+                        .spawn(msg.text_reply("Nothing is watched, usage: /watch <query>"));
+                } else {
+                    let mut queries: Vec<&String> = watches.keys().collect();
+                    queries.sort();
+                    let queries: Vec<String> = queries.iter().map(|q| format!("• {}", q)).collect();
+                    self.api.spawn(msg.text_reply(format!(
+                        "Watched queries:\n{}\n\nUse /unwatch <query> to stop watching",
+                        queries.join("\n")
+                    )));
+                }
+            }
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    async fn handle_unwatch(
+        &self,
+        msg: &Message,
+        query: &Option<String>,
+    ) -> Result<UserStateMessages> {
+        let mut con = self.redis.get_connection()?;
+        let key = format!("watches:{}", i64::from(msg.chat.id()));
+        match query {
+            Some(query) => {
+                let removed: i32 = con.hdel(&key, query)?;
+                if removed > 0 {
+                    self.api
+                        .spawn(msg.text_reply(format!("Stopped watching {}", query)));
+                } else {
+                    self.api
+                        .spawn(msg.text_reply(format!("{} is not watched, see /watch", query)));
+                }
+            }
+            None => self.api.spawn(msg.text_reply("Usage: /unwatch <query>")),
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    /// Background jobs run with the service account, this query limits them to
+    /// projects and issues the user can see
+    async fn user_scope(&self, yt: &YouTrack) -> Result<Option<String>> {
+        let me = YouTrackUser::me(yt).await?;
+        let projects: Vec<String> = Project::list(yt)
+            .await?
+            .iter()
+            .filter_map(|p| p.short_name.clone())
+            .map(|name| format!("{{{}}}", name))
+            .collect();
+        if projects.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(format!(
+            "project: {} visible to: {{{}}}",
+            projects.join(", "),
+            me.login
+        )))
+    }
+
+    /// Runs background jobs, called periodically from the main loop.
+    /// Watches are polled separately, see watch_jobs
+    pub async fn on_tick(&mut self) -> Result<()> {
+        if let Err(e) = self.poll_personal().await {
+            warn!("Unable to poll personal notifications: {}", e);
+        }
+        self.run_due_jobs().await
+    }
+
+    async fn run_due_jobs(&self) -> Result<()> {
+        let mut con = self.redis.get_connection()?;
+        for job in scheduler::due(&mut con, Utc::now().timestamp())? {
+            // Jobs reschedule themselves, so a failing one doesn't repeat every tick
+            scheduler::cancel(&mut con, &job)?;
+            let parts: Vec<&str> = job.split(':').collect();
+            let res = match parts.as_slice() {
+                ["digest", chat, id] => match (chat.parse(), id.parse()) {
+                    (Ok(chat), Ok(id)) => self.run_digest(chat, id).await,
+                    _ => Err(format!("Invalid job {}", job).into()),
+                },
+                ["flush", chat] => match chat.parse() {
+                    Ok(chat) => self.flush_notifications(chat).await,
+                    _ => Err(format!("Invalid job {}", job).into()),
+                },
+                ["standup", chat] => match chat.parse() {
+                    Ok(chat) => self.run_standup(chat).await,
+                    _ => Err(format!("Invalid job {}", job).into()),
+                },
+                ["standup_summary", chat] => match chat.parse() {
+                    Ok(chat) => self.post_standup_summary(chat).await,
+                    _ => Err(format!("Invalid job {}", job).into()),
+                },
+                _ => Err(format!("Unknown job {}", job).into()),
+            };
+            if let Err(e) = res {
+                warn!("Job {} failed: {}", job, e);
+            }
+        }
+        Ok(())
+    }
+
+    fn chat_timezone(&self, chat: i64) -> Result<Tz> {
+        let mut con = self.redis.get_connection()?;
+        let tz: Option<String> = con.get(format!("timezone:{}", chat))?;
+        Ok(tz.and_then(|tz| tz.parse().ok()).unwrap_or(Tz::UTC))
+    }
+
+    async fn handle_timezone(
+        &self,
+        msg: &Message,
+        tz: &Option<String>,
+    ) -> Result<UserStateMessages> {
+        let chat = i64::from(msg.chat.id());
+        match tz {
+            Some(name) => match name.parse::<Tz>() {
+                Ok(tz) => {
+                    let mut con = self.redis.get_connection()?;
+                    let _: () = con.set(format!("timezone:{}", chat), tz.name())?;
+                    // Digests and the stand-up keep their local time
+                    let digests: HashMap<u32, Digest> = con.hgetall(format!("digests:{}", chat))?;
+                    for digest in digests.values() {
+                        let next = digest.next_run(tz, Utc::now());
+                        scheduler::schedule(&mut con, &digest.job(), next.timestamp())?;
+                    }
+                    let standup: Option<Standup> = con.get(format!("standup:{}", chat))?;
+                    if let Some(standup) = standup {
+                        let next = standup.next_run(tz, Utc::now());
+                        scheduler::schedule(&mut con, &standup.job(), next.timestamp())?;
+                    }
+                    self.api
+                        .spawn(msg.text_reply(format!("Time zone is set to {}", tz.name())));
+                }
+                Err(_) => self.api.spawn(msg.text_reply(format!(
+                    "Unknown time zone {}, use names like Europe/Moscow",
+                    name
+                ))),
+            },
+            None => {
+                let tz = self.chat_timezone(chat)?;
+                self.api.spawn(msg.text_reply(format!(
+                    "Time zone is {}, use /timezone Europe/Moscow to change it",
+                    tz.name()
+                )));
+            }
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    async fn handle_digest(
+        &self,
+        msg: &Message,
+        params: &Option<DigestParams>,
+    ) -> Result<UserStateMessages> {
+        let mut con = self.redis.get_connection()?;
+        let chat = i64::from(msg.chat.id());
+        let key = format!("digests:{}", chat);
+        let tz = self.chat_timezone(chat)?;
+        match params {
+            Some(DigestParams::Add(period, time, query)) => {
+                let yt = match self.get_youtrack(msg.from.id).await {
+                    Some(yt) => yt,
+                    None => {
+                        self.api.spawn(msg.text_reply(
+                            "No valid access token founds, use /login command to login in youtrack",
+                        ));
+                        return Ok(UserStateMessages::Noop(Noop {}));
+                    }
+                };
+                let scope = match self.user_scope(yt).await? {
+                    Some(scope) => scope,
+                    None => {
+                        self.api
+                            .spawn(msg.text_reply("You don't have access to any project"));
+                        return Ok(UserStateMessages::Noop(Noop {}));
+                    }
+                };
+                let id: u32 = con.incr(format!("digest_id:{}", chat), 1)?;
+                let digest = Digest {
+                    id,
+                    chat,
+                    period: period.clone(),
+                    time: *time,
+                    query: query.clone(),
+                    scope: Some(scope),
+                    last_run: None,
+                };
+                let next = digest.next_run(tz, Utc::now());
+                let _: () = con.hset(&key, id, &digest)?;
+                scheduler::schedule(&mut con, &digest.job(), next.timestamp())?;
+                self.api.spawn(msg.text_reply(format!(
+                    "Digest {} scheduled, the first one comes at {}",
+                    id,
+                    next.with_timezone(&tz).format("%Y-%m-%d %H:%M %Z")
+                )));
+            }
+            Some(DigestParams::Remove(id)) => {
+                let digest: Option<Digest> = con.hget(&key, *id)?;
+                match digest {
+                    Some(digest) => {
+                        let _: () = con.hdel(&key, *id)?;
+                        scheduler::cancel(&mut con, &digest.job())?;
+                        self.api
+                            .spawn(msg.text_reply(format!("Digest {} removed", id)));
+                    }
+                    None => self
+                        .api
+                        .spawn(msg.text_reply(format!("No digest {}, see /digest", id))),
+                }
+            }
+            Some(DigestParams::List) => {
+                let digests: HashMap<u32, Digest> = con.hgetall(&key)?;
+                if digests.is_empty() {
+                    self.api.spawn(msg.text_reply(
+                        "No digests yet, usage: /digest daily 09:30 <query> or /digest weekly mon 09:30 <query>",
+                    ));
+                } else {
+                    let mut digests: Vec<&Digest> = digests.values().collect();
+                    digests.sort_by_key(|d| d.id);
+                    let lines: Vec<String> = digests
+                        .iter()
+                        .map(|d| format!("{}. {}", d.id, d.describe()))
+                        .collect();
+                    self.api.spawn(msg.text_reply(format!(
+                        "Digests ({}):\n{}\n\nUse /digest stop <number> to remove one",
+                        tz.name(),
+                        lines.join("\n")
+                    )));
+                }
+            }
+            None => self.api.spawn(msg.text_reply(
+                "Usage: /digest daily 09:30 <query>, /digest weekly mon 09:30 <query> or /digest stop <number>",
+            )),
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    async fn run_digest(&self, chat: i64, id: u32) -> Result<()> {
+        let mut con = self.redis.get_connection()?;
+        let digest: Option<Digest> = con.hget(format!("digests:{}", chat), id)?;
+        let digest = match digest {
+            Some(digest) => digest,
+            None => return Ok(()),
+        };
+        let tz = self.chat_timezone(chat)?;
+        let now = Utc::now();
+        let settings = self.get_settings(chat)?;
+        if settings.is_quiet(tz, now) {
+            // Postponed digest still covers its whole period
+            if let Some(at) = settings.delivery_time(tz, now) {
+                return scheduler::schedule(&mut con, &digest.job(), at.timestamp());
+            }
+        }
+        scheduler::schedule(
+            &mut con,
+            &digest.job(),
+            digest.next_run(tz, now).timestamp(),
+        )?;
+        if !settings.is_enabled(NotificationCategory::Digests)
+            || !self.get_chat_config(chat)?.is_enabled(Feature::Watches)
+        {
+            return Ok(());
+        }
+
+        // Digests added before scopes were stored could show anything
+        let scope = match &digest.scope {
+            Some(scope) => scope,
+            None => {
+                warn!("Skipping unscoped digest {} of chat {}", id, chat);
+                return Ok(());
+            }
+        };
+        // Conditions are added to the query, its own sort clause would break them
+        let query = scoped_query(scope, digests::without_sort(&digest.query));
+        let (from, to) = digest.window(now);
+        let (from_date, to_date) = digests::query_dates(from, to);
+        let in_window = |time: Option<i64>| time.map_or(false, |t| from <= t && t < to);
+        let stale_since = (now - chrono::Duration::days(STALE_DAYS))
+            .format("%Y-%m-%d")
+            .to_string();
+
+        // Query dates are whole days, exact times are checked here
+        let created: Vec<DigestIssue> = search_issues(
+            &self.yt,
+            &format!("{} created: {} .. {}", query, from_date, to_date),
+            50,
+            "idReadable,summary,created,resolved",
+        )
+        .await?;
+        let created: Vec<DigestIssue> = created
+            .into_iter()
+            .filter(|i| in_window(i.created))
+            .take(10)
+            .collect();
+        let resolved: Vec<DigestIssue> = search_issues(
+            &self.yt,
+            &format!("{} resolved date: {} .. {}", query, from_date, to_date),
+            50,
+            "idReadable,summary,created,resolved",
+        )
+        .await?;
+        let resolved: Vec<DigestIssue> = resolved
+            .into_iter()
+            .filter(|i| in_window(i.resolved))
+            .take(10)
+            .collect();
+        let top_voted: Issues = search_issues(
+            &self.yt,
+            &format!("{} #Unresolved sort by: votes desc", query),
+            5,
+            "idReadable,summary,votes,voters(hasVote)",
+        )
+        .await?;
+        let stale: Vec<IssueHeader> = search_issues(
+            &self.yt,
+            &format!(
+                "{} #Unresolved updated: * .. {} sort by: updated asc",
+                query, stale_since
+            ),
+            5,
+            "idReadable,summary",
+        )
+        .await?;
+
+        let date = |ms: i64| tz.timestamp_millis(ms).format("%Y-%m-%d %H:%M").to_string();
+        let mut context = Context::new();
+        context.insert("query", &digest.query);
+        context.insert("from", &date(from));
+        context.insert("to", &date(to));
+        context.insert("created", &created);
+        context.insert("resolved", &resolved);
+        context.insert("top_voted", &top_voted);
+        context.insert("stale", &stale);
+        context.insert("stale_days", &STALE_DAYS);
+        context.insert("youtrack_url", &self.yt.get_uri());
+        let txt_msg = self.templates.render("digest.md", &context).unwrap();
+        self.api
+            .send(
+                ChatId::new(chat)
+                    .text(txt_msg)
+                    .parse_mode(ParseMode::Markdown)
+                    .disable_preview(),
+            )
+            .await?;
+        let digest = Digest {
+            last_run: Some(to),
+            ..digest
+        };
+        let _: () = con.hset(format!("digests:{}", chat), id, &digest)?;
+        Ok(())
+    }
+
+    /// Chats and queries to poll, each one is polled with poll_watch_job
+    /// so the main loop can handle updates in between
+    pub fn watch_jobs(&self) -> Result<Vec<(i64, String)>> {
+        let mut con = self.redis.get_connection()?;
+        let chats: Vec<i64> = con.smembers("watch_chats")?;
+        let mut jobs = Vec::new();
+        for chat in chats {
+            let key = format!("watches:{}", chat);
+            let queries: Vec<String> = con.hkeys(&key)?;
+            if queries.is_empty() {
+                let _: () = con.srem("watch_chats", chat)?;
+                continue;
+            }
+            if !self.get_chat_config(chat)?.is_enabled(Feature::Watches) {
+                continue;
+            }
+            jobs.extend(queries.into_iter().map(|query| (chat, query)));
+        }
+        Ok(jobs)
+    }
+
+    pub async fn poll_watch_job(&self, chat: i64, query: &str) -> Result<()> {
+        let mut con = self.redis.get_connection()?;
+        let key = format!("watches:{}", chat);
+        // Removed since the job was queued
+        let watch: Watch = match con.hget(&key, query)? {
+            Some(watch) => watch,
+            None => return Ok(()),
+        };
+        let watch = self.poll_watch(ChatId::new(chat), &watch).await?;
+        // Moving the cursor must not bring back a watch removed meanwhile
+        if con.hexists(&key, query)? {
+            let _: () = con.hset(&key, query, watch)?;
+        }
+        Ok(())
+    }
+
+    /// Posts new activities of the watch, returns the watch moved past them
+    async fn poll_watch(&self, chat: ChatId, watch: &Watch) -> Result<Watch> {
+        // Watches added before scopes were stored could show anything
+        let scope = match &watch.scope {
+            Some(scope) => scope,
+            None => {
+                warn!("Skipping unscoped watch {} of chat {}", watch.query, chat);
+                return Ok(watch.clone());
+            }
+        };
+        // Polling can't rely on user tokens, those expire in an hour
+        let query = scoped_query(scope, &watch.query);
+        let page =
+            ActivityPage::watch(&self.yt, &query, watch.cursor.as_deref(), watch.since).await?;
+        for activity in page.activities.iter().filter(|a| a.is_notable()) {
+            if let Err(e) = self.notify(chat, activity).await {
+                warn!("Unable to notify chat {}: {}", chat, e);
+            }
+        }
+        Ok(Watch {
+            cursor: page.after_cursor.or_else(|| watch.cursor.clone()),
+            ..watch.clone()
+        })
+    }
+
+    async fn notify(&self, chat: ChatId, activity: &Activity) -> Result<()> {
+        if let Some(issue) = activity.issue() {
+            let notification = PendingNotification {
+                issue,
+                author: activity.author.display_name(),
+                text: activity.describe(),
+                reply_to: None,
+            };
+            self.send_notification(chat, Some(NotificationCategory::Watches), notification)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Issues personal notifications can come from: linked users are mentioned in them,
+    /// assigned to them or reported them
+    fn personal_query(&self) -> Result<Option<String>> {
+        let mut con = self.redis.get_connection()?;
+        // Logins linked before the set was kept are only known by their keys
+        let known: bool = con.exists("yt_logins")?;
+        if !known {
+            let keys: Vec<String> = con.scan_match("yt_login:*")?.collect();
+            for key in keys {
+                let _: () = con.sadd("yt_logins", &key["yt_login:".len()..])?;
+            }
+        }
+        let mut logins: Vec<String> = con.smembers("yt_logins")?;
+        if logins.is_empty() {
+            return Ok(None);
+        }
+        logins.sort();
+        let users: Vec<String> = logins.iter().map(|l| format!("{{{}}}", l)).collect();
+        let users = users.join(", ");
+        Ok(Some(format!(
+            "mentions: {users} or Assignee: {users} or reporter: {users}",
+            users = users
+        )))
+    }
+
+    /// Tells linked users about mentions, assignments and changes of issues they reported
+    async fn poll_personal(&self) -> Result<()> {
+        let query = match self.personal_query()? {
+            Some(query) => query,
+            None => return Ok(()),
+        };
+        let mut con = self.redis.get_connection()?;
+        let stored: Option<Watch> = con.get("personal_watch")?;
+        let watch = stored.unwrap_or_else(|| Watch {
+            query: String::new(),
+            since: now_timestamp() as i64 * 1000,
+            cursor: None,
+            scope: None,
+        });
+        let watch = Watch { query, ..watch };
+        let page =
+            ActivityPage::watch(&self.yt, &watch.query, watch.cursor.as_deref(), watch.since)
+                .await?;
+        for activity in page.activities.iter() {
+            if let Err(e) = self.notify_personal(activity).await {
+                warn!("Unable to send personal notification: {}", e);
+            }
+        }
+        let watch = Watch {
+            cursor: page.after_cursor.or_else(|| watch.cursor.clone()),
+            ..watch
+        };
+        let _: () = con.set("personal_watch", watch)?;
+        Ok(())
+    }
+
+    async fn notify_personal(&self, activity: &Activity) -> Result<()> {
+        let issue = match activity.issue() {
+            Some(issue) => issue,
+            None => return Ok(()),
+        };
+        let (category, recipients, text) =
+            match (activity.category.id.as_str(), activity.field_name()) {
+                ("CommentsCategory", _) if activity.is_added() => (
+                    NotificationCategory::Mentions,
+                    activity.mentions(),
+                    format!(
+                        "mentioned you: {}",
+                        activity.added_text().unwrap_or_default()
+                    ),
+                ),
+                ("CustomFieldCategory", Some("Assignee")) => (
+                    NotificationCategory::Assignments,
+                    activity.added_logins(),
+                    "assigned the issue to you".to_string(),
+                ),
+                ("CustomFieldCategory", Some("State")) => match &issue.reporter {
+                    Some(reporter) => (
+                        NotificationCategory::Reported,
+                        vec![reporter.login.clone()],
+                        activity.describe(),
+                    ),
+                    None => return Ok(()),
+                },
+                _ => return Ok(()),
+            };
+
+        let mut con = self.redis.get_connection()?;
+        for login in recipients {
+            // Nobody needs to hear about their own actions
+            if login == activity.author.login {
+                continue;
+            }
+            let uid: Option<i64> = con.get(format!("yt_login:{}", login))?;
+            if let Some(uid) = uid {
+                let notification = PendingNotification {
+                    issue: issue.clone(),
+                    author: activity.author.display_name(),
+                    text: text.clone(),
+                    reply_to: if category == NotificationCategory::Mentions {
+                        Some(activity.author.login.clone())
+                    } else {
+                        None
+                    },
+                };
+                self.send_notification(ChatId::new(uid), Some(category), notification)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Routes notification pushed by a YouTrack workflow to its users and chats
+    pub async fn on_hook(&self, hook: HookPayload) -> Result<()> {
+        let mut con = self.redis.get_connection()?;
+        let mut chats: Vec<i64> = hook.chats.clone();
+        for login in &hook.users {
+            let linked: Option<i64> = con.get(format!("yt_login:{}", login))?;
+            match linked {
+                Some(uid) => chats.push(uid),
+                None => debug!("No telegram user linked to {}", login),
+            }
+        }
+        chats.sort();
+        chats.dedup();
+
+        let issue = IssueHeader {
+            id_readable: hook.issue.clone(),
+            summary: hook.summary.clone().unwrap_or_default(),
+            reporter: None,
+        };
+        let notification = PendingNotification {
+            issue,
+            author: hook.author.clone().unwrap_or_default(),
+            text: hook.describe(),
+            reply_to: None,
+        };
+        for chat in chats {
+            let chat = ChatId::new(chat);
+            // Workflows decide themselves whom to notify, so there is no category to mute
+            if let Err(e) = self
+                .send_notification(chat, None, notification.clone())
+                .await
+            {
+                warn!("Unable to notify chat {}: {}", chat, e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_board(
+        &self,
+        msg: &Message,
+        name: &Option<String>,
+    ) -> Result<UserStateMessages> {
+        let yt = match self.get_youtrack(msg.from.id).await {
+            Some(yt) => yt,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "No valid access token founds, use /login command to login in youtrack",
+                ));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        if let Some(agile_id) = self.find_board(yt, msg, name, true).await? {
+            self.show_board(yt, msg, &agile_id, false).await?;
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    /// Board picked by name, pinned to the chat or the only one there is
+    async fn find_board(
+        &self,
+        yt: &YouTrack,
+        msg: &Message,
+        name: &Option<String>,
+        command: bool,
+    ) -> Result<Option<String>> {
+        let mut con = self.redis.get_connection()?;
+        let pinned: Option<String> = con.get(format!("board:{}", msg.chat.id()))?;
+        Ok(Some(match (name, pinned) {
+            (None, Some(pinned)) => pinned,
+            (name, _) => {
+                let agiles = Agile::list(yt).await?;
+                let found = match name {
+                    Some(name) => fuzzy::lookup(name, &agiles),
+                    None if agiles.len() == 1 => fuzzy::Lookup::Found(0),
+                    None => fuzzy::Lookup::Ambiguous((0..agiles.len()).collect()),
+                };
+                match found {
+                    fuzzy::Lookup::Found(i) => agiles[i].id.clone(),
+                    fuzzy::Lookup::Ambiguous(found) => {
+                        let names: Vec<String> =
+                            found.iter().map(|i| agiles[*i].name.clone()).collect();
+                        // Buttons have no arguments, the board has to be pinned for them
+                        let hint = if command {
+                            "Add its name to the command or pin one with /board"
+                        } else {
+                            "Pin one with /board first"
+                        };
+                        self.api.spawn(msg.text_reply(format!(
+                            "Which board? {}\n{}",
+                            names.join(", "),
+                            hint
+                        )));
+                        return Ok(None);
+                    }
+                    fuzzy::Lookup::NotFound => {
+                        self.api.spawn(msg.text_reply("No such board"));
+                        return Ok(None);
+                    }
+                }
+            }
+        }))
+    }
+
+    /// Current sprint of the board with issue counts per column
+    async fn show_board(
+        &self,
+        yt: &YouTrack,
+        msg: &Message,
+        agile_id: &str,
+        edit: bool,
+    ) -> Result<()> {
+        let agile = Agile::get(yt, agile_id).await?;
+        let sprint = match &agile.current_sprint {
+            Some(sprint) => sprint,
+            None => {
+                self.api
+                    .spawn(msg.text_reply(format!("Board {} has no current sprint", agile.name)));
+                return Ok(());
+            }
+        };
+        let counts: Vec<usize> = (0..agile.columns().len())
+            .map(|c| agile.column_issues(c).len())
+            .collect();
+        let columns: Vec<(String, usize)> = agile
+            .columns()
+            .iter()
+            .zip(&counts)
+            .map(|(c, count)| (c.presentation.clone(), *count))
+            .collect();
+        let tz = self.chat_timezone(i64::from(msg.chat.id()))?;
+        let date =
+            |ms: Option<i64>| ms.map(|ms| tz.timestamp_millis(ms).format("%Y-%m-%d").to_string());
+
+        let mut context = Context::new();
+        context.insert("board", &agile.name);
+        context.insert("sprint", sprint);
+        context.insert("start", &date(sprint.start));
+        context.insert("finish", &date(sprint.finish));
+        context.insert("columns", &columns);
+        context.insert(
+            "other",
+            &(sprint.issues.len() - counts.iter().sum::<usize>()),
+        );
+        let txt_msg = self.templates.render("board.md", &context).unwrap();
+        let kb = board_keyboard(&agile, &counts);
+        if edit {
+            self.api
+                .send(
+                    msg.edit_text(txt_msg)
+                        .reply_markup(kb)
+                        .parse_mode(ParseMode::Markdown),
+                )
+                .await?;
+        } else {
+            self.api
+                .send(
+                    msg.text_reply(txt_msg)
+                        .reply_markup(kb)
+                        .parse_mode(ParseMode::Markdown),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn show_board_column(
+        &self,
+        yt: &YouTrack,
+        msg: &Message,
+        params: &BoardColumnParams,
+    ) -> Result<()> {
+        let agile = Agile::get(yt, &params.agile).await?;
+        let column = match agile.columns().get(params.column) {
+            Some(column) => column,
+            None => return self.show_board(yt, msg, &params.agile, true).await,
+        };
+        let issues = agile.column_issues(params.column);
+        let page: Vec<&SprintIssue> = issues
+            .iter()
+            .skip(params.skip)
+            .take(BOARD_PAGE_SIZE)
+            .cloned()
+            .collect();
+
+        let mut context = Context::new();
+        context.insert("issues", &page);
+        context.insert("skip", &params.skip);
+        context.insert("youtrack_url", &self.yt.get_uri());
+        let txt_msg = format!(
+            "*{}: {}* ({})\n{}",
+            agile.name,
+            column.presentation,
+            issues.len(),
+            self.templates.render("issues_list.md", &context).unwrap()
+        );
+
+        let mut kb = InlineKeyboardMarkup::new();
+        let mut nav = Vec::new();
+        if params.skip > 0 {
+            nav.push(
+                CallbackParams::BoardColumn(BoardColumnParams {
+                    skip: params.skip.saturating_sub(BOARD_PAGE_SIZE),
+                    ..params.clone()
+                })
+                .button("prev"),
+            );
+        }
+        if params.skip + BOARD_PAGE_SIZE < issues.len() {
+            nav.push(
+                CallbackParams::BoardColumn(BoardColumnParams {
+                    skip: params.skip + BOARD_PAGE_SIZE,
+                    ..params.clone()
+                })
+                .button("next"),
+            );
+        }
+        nav.push(
+            CallbackParams::BoardOverview(BoardParams {
+                agile: params.agile.clone(),
+            })
+            .into(),
+        );
+        kb.add_row(nav);
+        self.api
+            .send(
+                msg.edit_text(txt_msg)
+                    .reply_markup(kb)
+                    .parse_mode(ParseMode::Markdown)
+                    .disable_preview(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn handle_burndown(
+        &self,
+        msg: &Message,
+        name: &Option<String>,
+    ) -> Result<UserStateMessages> {
+        let yt = match self.get_youtrack(msg.from.id).await {
+            Some(yt) => yt,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "No valid access token founds, use /login command to login in youtrack",
+                ));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        let agile = match self.find_board(yt, msg, name, true).await? {
+            Some(agile_id) => Agile::get(yt, &agile_id).await?,
+            None => return Ok(UserStateMessages::Noop(Noop {})),
+        };
+        let sprint = match &agile.current_sprint {
+            Some(sprint) => sprint,
+            None => {
+                self.api
+                    .spawn(msg.text_reply(format!("Board {} has no current sprint", agile.name)));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        let (start, finish) = match (sprint.start, sprint.finish) {
+            (Some(start), Some(finish)) => (start, finish),
+            _ => {
+                self.api.spawn(msg.text_reply(format!(
+                    "Sprint {} has no start or finish date",
+                    sprint.name
+                )));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+
+        // Every issue takes its own history requests, all handled under the bot lock
+        if sprint.issues.len() > BURNDOWN_MAX_ISSUES {
+            self.api.spawn(msg.text_reply(format!(
+                "Sprint {} has {} issues, burndown is drawn for up to {}",
+                sprint.name,
+                sprint.issues.len(),
+                BURNDOWN_MAX_ISSUES
+            )));
+            return Ok(UserStateMessages::Noop(Noop {}));
+        }
+        let field = agile.estimation_field_name();
+        let histories: Vec<Result<Vec<Activity>>> = stream::iter(&sprint.issues)
+            .map(|issue| ActivityPage::changes(yt, &issue.id_readable))
+            .buffered(BURNDOWN_REQUESTS)
+            .collect()
+            .await;
+        let mut timelines = Vec::new();
+        for (issue, activities) in sprint.issues.iter().zip(histories) {
+            timelines.push(IssueTimeline::new(issue, field, &activities?));
+        }
+        let tz = self.chat_timezone(i64::from(msg.chat.id()))?;
+        let burndown = Burndown::new(&timelines, tz, start, finish, Utc::now());
+        let remaining: Vec<f64> = burndown
+            .remaining
+            .iter()
+            .map(|v| chart_value(*v, field))
+            .collect();
+        let png = charts::burndown(burndown.days(), &remaining)?;
+        let caption = format!(
+            "{}: {}, {} — {}\nRemaining {} of {}, ideal is {}\nRed is actual, blue is ideal\n{}",
+            agile.name,
+            sprint.name,
+            burndown.start.format("%Y-%m-%d"),
+            burndown.finish.format("%Y-%m-%d"),
+            format_amount(*burndown.remaining.last().unwrap(), field),
+            format_amount(burndown.total(), field),
+            format_amount(burndown.ideal(), field),
+            grid_legend(&remaining, field, "days")
+        );
+        let photo = InputFileUpload::with_data(png, "burndown.png");
+        self.api
+            .send(SendPhoto::new(msg.chat.id(), photo).caption(caption))
+            .await?;
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    async fn handle_velocity(
+        &self,
+        msg: &Message,
+        name: &Option<String>,
+    ) -> Result<UserStateMessages> {
+        let yt = match self.get_youtrack(msg.from.id).await {
+            Some(yt) => yt,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "No valid access token founds, use /login command to login in youtrack",
+                ));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        let agile = match self.find_board(yt, msg, name, true).await? {
+            Some(agile_id) => Agile::get(yt, &agile_id).await?,
+            None => return Ok(UserStateMessages::Noop(Noop {})),
+        };
+        let now = Utc::now().timestamp_millis();
+        let mut sprints: Vec<Sprint> = Agile::sprints(yt, &agile.id)
+            .await?
+            .into_iter()
+            .filter(|s| s.finish.map_or(false, |f| f <= now))
+            .collect();
+        sprints.sort_by_key(|s| s.start);
+        let sprints = &sprints[sprints.len().saturating_sub(VELOCITY_SPRINTS)..];
+        if sprints.is_empty() {
+            self.api
+                .spawn(msg.text_reply(format!("Board {} has no finished sprints yet", agile.name)));
+            return Ok(UserStateMessages::Noop(Noop {}));
+        }
+
+        let field = agile.estimation_field_name();
+        let done: Vec<i64> = sprints
+            .iter()
+            .map(|s| {
+                s.issues
+                    .iter()
+                    .filter(|i| i.resolved.is_some())
+                    .map(|i| issue_weight(i, field))
+                    .sum()
+            })
+            .collect();
+        let values: Vec<f64> = done.iter().map(|v| chart_value(*v, field)).collect();
+        let png = charts::velocity(&values)?;
+        let lines: Vec<String> = sprints
+            .iter()
+            .zip(&done)
+            .map(|(s, v)| format!("{}: {}", s.name, format_amount(*v, field)))
+            .collect();
+        let average = done.iter().sum::<i64>() / done.len() as i64;
+        let caption = format!(
+            "{} velocity, done per sprint\n{}\nAverage is {}\n{}",
+            agile.name,
+            lines.join("\n"),
+            format_amount(average, field),
+            grid_legend(&values, field, "sprints")
+        );
+        let photo = InputFileUpload::with_data(png, "velocity.png");
+        self.api
+            .send(SendPhoto::new(msg.chat.id(), photo).caption(caption))
+            .await?;
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    async fn handle_board_action(&self, cmd: BotCommand) -> Result<UserStateMessages> {
+        let cb = match &cmd {
+            BotCommand::BoardOverview(cb, _)
+            | BotCommand::BoardColumn(cb, _)
+            | BotCommand::BoardPin(cb, _) => cb,
+            _ => return Ok(UserStateMessages::Noop(Noop {})),
+        };
+        let msg = cb.message.clone().unwrap();
+        let yt = match self.get_youtrack(cb.from.id).await {
+            Some(yt) => yt,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "No valid access token founds, use /login command to login in youtrack",
+                ));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        match &cmd {
+            BotCommand::BoardOverview(_, p) => self.show_board(yt, &msg, &p.agile, true).await?,
+            BotCommand::BoardColumn(_, p) => self.show_board_column(yt, &msg, p).await?,
+            BotCommand::BoardPin(_, p) => {
+                let mut con = self.redis.get_connection()?;
+                let _: () = con.set(format!("board:{}", msg.chat.id()), &p.agile)?;
+                self.api
+                    .spawn(msg.text_reply("Pinned, /board now shows this board in this chat"));
+            }
+            _ => {}
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    async fn handle_standup(
+        &self,
+        msg: &Message,
+        params: &Option<StandupParams>,
+    ) -> Result<UserStateMessages> {
+        let chat = i64::from(msg.chat.id());
+        let key = format!("standup:{}", chat);
+        let mut con = self.redis.get_connection()?;
+        let stored: Option<Standup> = con.get(&key)?;
+        let member = StandupMember {
+            id: i64::from(msg.from.id),
+            name: full_name(&msg.from),
+        };
+        let params = match params {
+            Some(StandupParams::Answer) => return self.answer_standup(msg),
+            Some(StandupParams::Schedule(time)) => {
+                let standup = match stored {
+                    Some(standup) => Standup {
+                        time: *time,
+                        ..standup
+                    },
+                    None => Standup {
+                        chat,
+                        time: *time,
+                        members: Vec::new(),
+                        comment_issues: false,
+                    },
+                };
+                let tz = self.chat_timezone(chat)?;
+                let _: () = con.set(&key, &standup)?;
+                scheduler::schedule(
+                    &mut con,
+                    &standup.job(),
+                    standup.next_run(tz, Utc::now()).timestamp(),
+                )?;
+                self.api.spawn(msg.text_reply(format!(
+                    "Stand-up is scheduled on weekdays at {} {}, join it with /standup join",
+                    time.format("%H:%M"),
+                    tz.name()
+                )));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+            Some(params) => params,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "Usage: /standup 10:00, /standup join, /standup leave, /standup comments on|off or /standup off",
+                ));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        let mut standup = match stored {
+            Some(standup) => standup,
+            None => {
+                self.api.spawn(
+                    msg.text_reply("No stand-up in this chat, schedule one with /standup 10:00"),
+                );
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        let reply = match params {
+            StandupParams::Off => {
+                let _: () = con.del(&key)?;
+                scheduler::cancel(&mut con, &standup.job())?;
+                scheduler::cancel(&mut con, &standup.summary_job())?;
+                self.api.spawn(msg.text_reply("Stand-up is turned off"));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+            StandupParams::Join if !standup.is_member(member.id) => {
+                standup.members.push(member);
+                "You are in. Questions come in a private chat, so make sure you have started one with me".to_string()
+            }
+            StandupParams::Leave => {
+                standup.members.retain(|m| m.id != member.id);
+                "You are out of the stand-up".to_string()
+            }
+            StandupParams::Comments(on) => {
+                standup.comment_issues = *on;
+                if *on {
+                    "Updates will be added as comments to the issues they mention".to_string()
+                } else {
+                    "Updates won't be added to issues".to_string()
+                }
+            }
+            _ => {
+                let names: Vec<String> = standup.members.iter().map(|m| m.name.clone()).collect();
+                format!(
+                    "Stand-up is on weekdays at {} {}\nMembers: {}\nComments on issues: {}",
+                    standup.time.format("%H:%M"),
+                    self.chat_timezone(chat)?.name(),
+                    if names.is_empty() {
+                        "nobody yet".to_string()
+                    } else {
+                        names.join(", ")
+                    },
+                    if standup.comment_issues { "on" } else { "off" }
+                )
+            }
+        };
+        let _: () = con.set(&key, &standup)?;
+        self.api.spawn(msg.text_reply(reply));
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    /// Starts stand-up questions for a member who was busy when the stand-up began
+    fn answer_standup(&self, msg: &Message) -> Result<UserStateMessages> {
+        // Answers are collected in the private chat the questions are asked in
+        match msg.chat {
+            MessageChat::Private(_) => {}
+            _ => {
+                self.api
+                    .spawn(msg.text_reply("Send /standup answer to me in a private chat"));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        }
+        let mut con = self.redis.get_connection()?;
+        let uid = i64::from(msg.from.id);
+        let chat: Option<i64> = con.get(format!("standup_user:{}", uid))?;
+        match chat {
+            Some(chat) => {
+                self.api.spawn(msg.from.text(standup_question(0)));
+                Ok(UserStateMessages::StartStandup(StartStandup(chat)))
+            }
+            None => {
+                self.api
+                    .spawn(msg.text_reply("No stand-up is waiting for your answers"));
+                Ok(UserStateMessages::Noop(Noop {}))
+            }
+        }
+    }
+
+    /// Asks team members the stand-up questions in their private chats
+    async fn run_standup(&self, chat: i64) -> Result<()> {
+        let mut con = self.redis.get_connection()?;
+        let standup: Option<Standup> = con.get(format!("standup:{}", chat))?;
+        let standup = match standup {
+            Some(standup) => standup,
+            None => return Ok(()),
+        };
+        let tz = self.chat_timezone(chat)?;
+        let now = Utc::now();
+        scheduler::schedule(
+            &mut con,
+            &standup.job(),
+            standup.next_run(tz, now).timestamp(),
+        )?;
+        if standup.members.is_empty() || !self.get_chat_config(chat)?.is_enabled(Feature::Standups)
+        {
+            return Ok(());
+        }
+
+        let pending_key = format!("standup_pending:{}", chat);
+        let _: () = con.del(format!("standup_answers:{}", chat))?;
+        let _: () = con.del(&pending_key)?;
+        scheduler::schedule(
+            &mut con,
+            &standup.summary_job(),
+            now.timestamp() + standups::ANSWER_WINDOW,
+        )?;
+        for member in &standup.members {
+            let _: () = con.sadd(&pending_key, member.id)?;
+            let _: () = con.set_ex(
+                format!("standup_user:{}", member.id),
+                chat,
+                standups::ANSWER_WINDOW as usize,
+            )?;
+            let uid = UserId::new(member.id);
+            // Questions are asked in the private chat, which has the user id
+            let private = ChatId::new(member.id);
+            let stored: Option<StoredUserState> = con.get(state_key(private, uid)).unwrap_or(None);
+            let idle = match stored.map(|s| s.state) {
+                None | Some(UserState::Idle(_)) => true,
+                _ => false,
+            };
+            // Don't break into a draft being composed
+            let text = if idle {
+                self.save_state(
+                    private,
+                    uid,
+                    UserState::AnsweringStandup(AnsweringStandup {
+                        chat,
+                        answers: Vec::new(),
+                    }),
+                )?;
+                format!("Stand-up time!\n{}", standup_question(0))
+            } else {
+                "Stand-up time! Finish or /cancel what you are doing and send /standup answer"
+                    .to_string()
+            };
+            if let Err(e) = self.api.send(uid.text(text)).await {
+                warn!("Unable to ask {} for stand-up: {}", member.name, e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_command_answering_standup(
+        &mut self,
+        state: &AnsweringStandup,
+        cmd: BotCommand,
+    ) -> Result<UserStateMessages> {
+        let res = match &cmd {
+            BotCommand::Text(msg) => {
+                let answer = cmd.get_message_text().unwrap_or_default();
+                let mut answers = state.answers.clone();
+                answers.push(answer.clone());
+                if answers.len() < standups::QUESTIONS.len() {
+                    self.api
+                        .spawn(msg.from.text(standup_question(answers.len())));
+                    UserStateMessages::StandupAnswer(StandupAnswer(answer))
+                } else {
+                    self.finish_standup(msg, state.chat, answers).await?;
+                    UserStateMessages::StandupDone(StandupDone {})
+                }
+            }
+            BotCommand::Cancel(msg) => {
+                self.api.spawn(
+                    msg.from
+                        .text("Stand-up answers discarded, use /standup answer to start over"),
+                );
+                UserStateMessages::Cancel(Cancel {})
+            }
+            _ => UserStateMessages::Noop(Noop {}),
+        };
+        Ok(res)
+    }
+
+    async fn finish_standup(&self, msg: &Message, chat: i64, answers: Vec<String>) -> Result<()> {
+        let mut con = self.redis.get_connection()?;
+        let uid = i64::from(msg.from.id);
+        let standup: Option<Standup> = con.get(format!("standup:{}", chat))?;
+        let standup = match standup {
+            Some(standup) if standup.is_member(uid) => standup,
+            _ => {
+                self.api
+                    .spawn(msg.from.text("This stand-up is over, thanks anyway"));
+                return Ok(());
+            }
+        };
+        let member = standup
+            .members
+            .iter()
+            .find(|m| m.id == uid)
+            .cloned()
+            .unwrap();
+        let yt = self.get_youtrack(msg.from.id).await;
+        // Members' tokens may expire before the summary, so issues are resolved now
+        let mut issues = Vec::new();
+        if let Some(yt) = yt {
+            for issue_id in standups::issue_ids(&answers.join("\n")) {
+                if let Ok(card) = IssueCard::get(yt, &issue_id).await {
+                    issues.push(IssueHeader {
+                        id_readable: card.id_readable,
+                        summary: card.summary,
+                        reporter: None,
+                    });
+                }
+            }
+        }
+        let report = StandupReport {
+            member,
+            answers,
+            issues,
+        };
+        let _: () = con.hset(format!("standup_answers:{}", chat), uid, &report)?;
+        let _: () = con.srem(format!("standup_pending:{}", chat), uid)?;
+        let _: () = con.del(format!("standup_user:{}", uid))?;
+
+        if standup.comment_issues {
+            let text: Vec<String> = standups::QUESTIONS
+                .iter()
+                .zip(&report.answers)
+                .map(|(q, a)| format!("*{}*\n{}", q, a))
+                .collect();
+            let text = format!("Stand-up update\n\n{}", text.join("\n\n"));
+            if let Some(yt) = yt {
+                for issue_id in standups::issue_ids(&report.answers.join("\n")) {
+                    if let Err(e) = IssueComment::create(yt, &issue_id, &text).await {
+                        warn!("Unable to comment {}: {}", issue_id, e);
+                    }
+                }
+            }
+        }
+        self.api
+            .spawn(msg.from.text("Thanks! Your update goes to the team chat"));
+
+        let pending: usize = con.scard(format!("standup_pending:{}", chat))?;
+        if pending == 0 {
+            self.post_standup_summary(chat).await?;
+        }
+        Ok(())
+    }
+
+    /// Posts collected stand-up updates to the team chat
+    async fn post_standup_summary(&self, chat: i64) -> Result<()> {
+        let mut con = self.redis.get_connection()?;
+        let standup: Option<Standup> = con.get(format!("standup:{}", chat))?;
+        let standup = match standup {
+            Some(standup) => standup,
+            None => return Ok(()),
+        };
+        scheduler::cancel(&mut con, &standup.summary_job())?;
+        let answers_key = format!("standup_answers:{}", chat);
+        let reports: HashMap<i64, StandupReport> = con.hgetall(&answers_key)?;
+        let _: () = con.del(&answers_key)?;
+        let _: () = con.del(format!("standup_pending:{}", chat))?;
+
+        let mut updates = Vec::new();
+        let mut missing = Vec::new();
+        for member in &standup.members {
+            let report = match reports.get(&member.id) {
+                Some(report) => report,
+                None => {
+                    missing.push(member.name.clone());
+                    continue;
+                }
+            };
+            let answers: Vec<(&str, &String)> = standups::QUESTIONS
+                .iter()
+                .cloned()
+                .zip(&report.answers)
+                .collect();
+            updates.push(json!({
+                "name": member.name,
+                "answers": answers,
+                "issues": report.issues,
+            }));
+        }
+        let tz = self.chat_timezone(chat)?;
+
+        let mut context = Context::new();
+        context.insert(
+            "date",
+            &Utc::now().with_timezone(&tz).format("%Y-%m-%d").to_string(),
+        );
+        context.insert("updates", &updates);
+        context.insert("missing", &missing);
+        context.insert("youtrack_url", &self.yt.get_uri());
+        let txt_msg = self.templates.render("standup.md", &context).unwrap();
+        self.api
+            .send(
+                ChatId::new(chat)
+                    .text(txt_msg)
+                    .parse_mode(ParseMode::Markdown)
+                    .disable_preview(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn handle_settings(
+        &self,
+        msg: &Message,
+        params: &Option<SettingsParams>,
+    ) -> Result<UserStateMessages> {
+        let chat = i64::from(msg.chat.id());
+        match params {
+            Some(SettingsParams::Menu) => {}
+            Some(SettingsParams::Quiet(_)) if !self.is_chat_admin(msg).await? => {
+                self.api.spawn(msg.text_reply(
+                    "Only chat admins can change its settings, \
+                     personal ones are in the private chat with me",
+                ));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+            Some(SettingsParams::Quiet(quiet)) => {
+                let mut settings = self.get_settings(chat)?;
+                settings.quiet_hours = quiet.clone();
+                self.save_settings(chat, &settings)?;
+            }
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "Usage: /settings, /settings quiet 22:00-08:00 or /settings quiet off",
+                ));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        }
+        self.show_settings(msg, false).await?;
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    async fn show_settings(&self, msg: &Message, edit: bool) -> Result<()> {
+        let chat = i64::from(msg.chat.id());
+        let settings = self.get_settings(chat)?;
+        let tz = self.chat_timezone(chat)?;
+        let text = format!(
+            "Notification settings. Quiet hours are in {} time zone, change it with /timezone, \
+             or set custom ones with /settings quiet 22:00-08:00",
+            tz.name()
+        );
+        let kb = settings_keyboard(&settings);
+        if edit {
+            self.api.send(msg.edit_text(text).reply_markup(kb)).await?;
+        } else {
+            self.api.send(msg.text_reply(text).reply_markup(kb)).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_settings_action(&self, cmd: BotCommand) -> Result<UserStateMessages> {
+        let cb = match &cmd {
+            BotCommand::SettingsToggle(cb, _)
+            | BotCommand::SettingsQuiet(cb)
+            | BotCommand::SettingsBatching(cb)
+            | BotCommand::SettingsDone(cb) => cb,
+            _ => return Ok(UserStateMessages::Noop(Noop {})),
+        };
+        let msg = cb.message.clone().unwrap();
+        let chat = i64::from(msg.chat.id());
+        if !self.is_admin_of(&msg.chat, cb.from.id).await? {
+            self.api.spawn(msg.text_reply(
+                "Only chat admins can change its settings, \
+                 personal ones are in the private chat with me",
+            ));
+            return Ok(UserStateMessages::Noop(Noop {}));
+        }
+        let mut settings = self.get_settings(chat)?;
+        match &cmd {
+            BotCommand::SettingsToggle(_, p) => settings.toggle(p.category),
+            BotCommand::SettingsQuiet(_) => settings.next_quiet_hours(),
+            BotCommand::SettingsBatching(_) => {
+                settings.batching = match settings.batching {
+                    Batching::Immediate => Batching::Hourly,
+                    Batching::Hourly => Batching::Immediate,
+                }
+            }
+            _ => {
+                self.api
+                    .send(msg.edit_reply_markup(Some(reply_markup!(inline_keyboard, []))))
+                    .await?;
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        }
+        self.save_settings(chat, &settings)?;
+        self.show_settings(&msg, true).await?;
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    /// Settings of a group chat, or of the user for private chats,
+    /// which get personal notifications
+    fn get_settings(&self, chat: i64) -> Result<NotificationSettings> {
+        let mut con = self.redis.get_connection()?;
+        let settings: Option<NotificationSettings> = con.get(settings_key(chat))?;
+        Ok(settings.unwrap_or_default())
+    }
+
+    fn save_settings(&self, chat: i64, settings: &NotificationSettings) -> Result<()> {
+        let mut con = self.redis.get_connection()?;
+        let _: () = con.set(settings_key(chat), settings)?;
+        Ok(())
+    }
+
+    /// Renders the template translated to the chat language, untranslated ones are used as is
+    fn render_localized(&self, chat: i64, name: &str, context: &Context) -> Result<String> {
+        let localized = self.get_chat_config(chat)?.language.template(name);
+        let name = if self.templates.get_template_names().any(|t| t == localized) {
+            localized.as_str()
+        } else {
+            name
+        };
+        Ok(self.templates.render(name, context).unwrap())
+    }
+
+    fn get_chat_config(&self, chat: i64) -> Result<ChatConfig> {
+        let mut con = self.redis.get_connection()?;
+        let config: Option<ChatConfig> = con.get(format!("config:{}", chat))?;
+        Ok(config.unwrap_or_default())
+    }
+
+    fn save_chat_config(&self, chat: i64, config: &ChatConfig) -> Result<()> {
+        let mut con = self.redis.get_connection()?;
+        let _: () = con.set(format!("config:{}", chat), config)?;
+        Ok(())
+    }
+
+    /// Anyone may configure a private chat
+    async fn is_chat_admin(&self, msg: &Message) -> Result<bool> {
+        self.is_admin_of(&msg.chat, msg.from.id).await
+    }
+
+    /// Whether the user administers the chat, e.g. the one pressing a button
+    async fn is_admin_of(&self, chat: &MessageChat, user: UserId) -> Result<bool> {
+        match chat {
+            MessageChat::Private(_) => Ok(true),
+            _ => {
+                let admins = self.api.send(GetChatAdministrators::new(chat.id())).await?;
+                Ok(admins.iter().any(|m| m.user.id == user))
+            }
+        }
+    }
+
+    async fn handle_config(
+        &self,
+        msg: &Message,
+        params: &Option<ConfigParams>,
+    ) -> Result<UserStateMessages> {
+        let chat = i64::from(msg.chat.id());
+        let params = match params {
+            Some(ConfigParams::Show) => {
+                self.show_config(msg).await?;
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+            Some(params) => params,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "Usage: /config, /config backlog <query>, /config project <name>, \
+                     /config field <name> <value>, /config language ru|en, \
+                     /config scale fibonacci|tshirt, \
+                     /config enable|disable backlog|issues|time|watches|boards|standups|estimation",
+                ));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        if !self.is_chat_admin(msg).await? {
+            self.api.spawn(
+                msg.text_reply("Only chat administrators can change the chat configuration"),
+            );
+            return Ok(UserStateMessages::Noop(Noop {}));
+        }
+
+        let mut config = self.get_chat_config(chat)?;
+        match params {
+            ConfigParams::Show => {}
+            ConfigParams::BacklogQuery(query) => {
+                if let Some(query) = query {
+                    if let Err(e) = search_issues::<Value>(&self.yt, query, 1, "id").await {
+                        self.api
+                            .spawn(msg.text_reply(format!("Invalid query {}: {}", query, e)));
+                        return Ok(UserStateMessages::Noop(Noop {}));
+                    }
+                }
+                config.backlog_query = query.clone();
+            }
+            ConfigParams::Project(name) => {
+                config.project = match name {
+                    Some(name) => {
+                        let projects = self.get_projects().await?;
+                        match fuzzy::lookup(name, &projects) {
+                            Lookup::Found(index) => Some(projects[index].title()),
+                            _ => {
+                                self.api
+                                    .spawn(msg.text_reply(format!("Project {} not found", name)));
+                                return Ok(UserStateMessages::Noop(Noop {}));
+                            }
+                        }
+                    }
+                    None => None,
+                }
+            }
+            ConfigParams::Field(name, value) => match value {
+                Some(value) => {
+                    if let Some(error) = self.check_field_default(&config, name, value).await? {
+                        self.api.spawn(msg.text_reply(error));
+                        return Ok(UserStateMessages::Noop(Noop {}));
+                    }
+                    config.fields.insert(name.clone(), value.clone());
+                }
+                None => {
+                    config.fields.remove(name);
+                }
+            },
+            ConfigParams::Feature(feature, enabled) => config.set_enabled(*feature, *enabled),
+            ConfigParams::Language(language) => config.language = *language,
+            ConfigParams::EstimationScale(scale) => config.estimation_scale = *scale,
+        }
+        self.save_chat_config(chat, &config)?;
+        self.show_config(msg).await?;
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    /// Checks the field default against the chat project, returns the reason it is rejected
+    async fn check_field_default(
+        &self,
+        config: &ChatConfig,
+        name: &str,
+        value: &str,
+    ) -> Result<Option<String>> {
+        let project = match &config.project {
+            Some(project) => project,
+            None => {
+                return Ok(Some(
+                    "Set the project with /config project <name> first".to_string(),
+                ))
+            }
+        };
+        let projects = self.get_projects().await?;
+        let project = match projects.iter().find(|p| &p.title() == project) {
+            Some(project) => project,
+            None => return Ok(Some(format!("Project {} not found", project))),
+        };
+        let field = match project.get_project_custom_field(name) {
+            Some(field) => field,
+            None => {
+                return Ok(Some(format!(
+                    "Project {} has no field {}",
+                    project.title(),
+                    name
+                )))
+            }
+        };
+        if IssueDraftCustomField::for_field(field, value).is_none() {
+            return Ok(Some(format!("{} can't be set to {}", name, value)));
+        }
+        if field.field.field_type.id.starts_with("enum") {
+            let bundle: Bundle = project.get_bundle(&self.yt, name).await?;
+            let values = bundle.values.unwrap_or_default();
+            // Multi-value fields take a comma separated list, as for_field splits them
+            let names: Vec<&str> = if field.field.field_type.id.ends_with("[*]") {
+                value.split(',').map(|v| v.trim()).collect()
+            } else {
+                vec![value]
+            };
+            let unknown = names
+                .into_iter()
+                .find(|v| !values.iter().any(|e| &e.name == v));
+            if let Some(unknown) = unknown {
+                return Ok(Some(format!("{} has no value {}", name, unknown)));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn show_config(&self, msg: &Message) -> Result<()> {
+        let config = self.get_chat_config(i64::from(msg.chat.id()))?;
+        let mut fields: Vec<(&String, &String)> = config.fields.iter().collect();
+        fields.sort();
+        let features: Vec<(&str, bool)> = Feature::all()
+            .into_iter()
+            .map(|f| (f.name(), config.is_enabled(f)))
+            .collect();
+        let mut context = Context::new();
+        context.insert(
+            "backlog_query",
+            config.backlog_query.as_ref().unwrap_or(&self.backlog_query),
+        );
+        context.insert("custom_backlog", &config.backlog_query.is_some());
+        context.insert("project", &config.project);
+        context.insert("fields", &fields);
+        context.insert("language", config.language.name());
+        context.insert("features", &features);
+        context.insert("scale", config.estimation_scale.name());
+        let text = self.render_localized(i64::from(msg.chat.id()), "config.md", &context)?;
+        self.api
+            .send(msg.text_reply(text).parse_mode(ParseMode::Markdown))
+            .await?;
+        Ok(())
+    }
+
+    /// Delivers the notification according to the chat settings: drops muted categories,
+    /// queues it during quiet hours or until the hourly summary
+    async fn send_notification(
+        &self,
+        chat: ChatId,
+        category: Option<NotificationCategory>,
+        notification: PendingNotification,
+    ) -> Result<()> {
+        let settings = self.get_settings(i64::from(chat))?;
+        if let Some(category) = category {
+            if !settings.is_enabled(category) {
+                return Ok(());
+            }
+        }
+        let tz = self.chat_timezone(i64::from(chat))?;
+        match settings.delivery_time(tz, Utc::now()) {
+            Some(at) => {
+                let mut con = self.redis.get_connection()?;
+                let _: () = con.rpush(format!("pending:{}", chat), notification)?;
+                scheduler::schedule(&mut con, &format!("flush:{}", chat), at.timestamp())
+            }
+            None => self.deliver_notification(chat, &notification).await,
+        }
+    }
+
+    /// Sends notifications queued for the chat, several ones as a single summary
+    async fn flush_notifications(&self, chat: i64) -> Result<()> {
+        let settings = self.get_settings(chat)?;
+        let tz = self.chat_timezone(chat)?;
+        let now = Utc::now();
+        let mut con = self.redis.get_connection()?;
+        // Quiet hours might have been changed since the notifications were queued
+        if settings.is_quiet(tz, now) {
+            if let Some(at) = settings.delivery_time(tz, now) {
+                return scheduler::schedule(&mut con, &format!("flush:{}", chat), at.timestamp());
+            }
+        }
+        let key = format!("pending:{}", chat);
+        let pending: Vec<PendingNotification> = con.lrange(&key, 0, -1)?;
+        let _: () = con.del(&key)?;
+        match pending.as_slice() {
+            [] => {}
+            [notification] => {
+                self.deliver_notification(ChatId::new(chat), notification)
+                    .await?
+            }
+            _ => {
+                let mut context = Context::new();
+                context.insert("notifications", &pending);
+                context.insert("youtrack_url", &self.yt.get_uri());
+                let txt_msg = self
+                    .templates
+                    .render("notification_summary.md", &context)
+                    .unwrap();
+                self.api
+                    .send(
+                        ChatId::new(chat)
+                            .text(txt_msg)
+                            .parse_mode(ParseMode::Markdown)
+                            .disable_preview(),
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends issue change to the chat with issue card buttons
+    async fn deliver_notification(
+        &self,
+        chat: ChatId,
+        notification: &PendingNotification,
+    ) -> Result<()> {
+        let mut issue = notification.issue.clone();
+        let kb = match IssueCard::get(&self.yt, &issue.id_readable).await {
+            Ok(card) => {
+                let states = card.next_states(&self.yt).await.unwrap_or_default();
+                issue.summary = card.summary.clone();
+                issue_card_keyboard(&card, &states)
+            }
+            Err(_) => InlineKeyboardMarkup::new(),
+        };
+
+        let mut context = Context::new();
+        context.insert("issue", &issue);
+        context.insert("author", &notification.author);
+        context.insert("text", &notification.text);
+        context.insert("youtrack_url", &self.yt.get_uri());
+        let txt_msg = self.templates.render("notification.md", &context).unwrap();
+
+        let sent = self
+            .api
+            .send(
+                chat.text(txt_msg)
+                    .reply_markup(kb)
+                    .parse_mode(ParseMode::Markdown),
+            )
+            .await?;
+        self.remember_issue_message(&sent, &issue.id_readable)?;
+        if let Some(login) = &notification.reply_to {
+            self.remember_reply_to(&sent, login)?;
+        }
+        Ok(())
+    }
+
+    /// Uploads file from the message to the issue and mentions it in a comment
+    async fn add_attachment(
+        &self,
+        msg: &Message,
+        issue_id: &str,
+        file: GetFile,
+        filename: String,
+        caption: &Option<String>,
+    ) -> Result<()> {
+        // youtrack-rs can't send multipart requests, the upload uses the raw token
+        let token = match self.get_youtrack(msg.from.id).await {
+            Some(yt) => yt.get_token().to_string(),
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "No valid access token founds, use /login command to login in youtrack",
+                ));
+                return Ok(());
+            }
+        };
+        let file = self.api.send(file).await?;
+        let url = match file.get_url(&self.telegram_token) {
+            Some(url) => url,
+            None => bail!("Unable to download file"),
+        };
+        let data = files::download(&self.telegram_client, &url).await?;
+        let api_url = format!("{}", self.yt.get_uri());
+        files::upload_attachment(&api_url, &token, issue_id, &filename, data).await?;
+
+        let text = match caption {
+            Some(caption) => format!("{}\n\n![]({})", caption, filename),
+            None => format!("![]({})", filename),
+        };
+        self.add_comment(msg, issue_id, &text).await
+    }
+
+    async fn vote_for_issue(&self, yt: &YouTrack, has_vote: bool, id: String) -> Result<bool> {
+        let json_has_vote = json!({"hasVote": !has_vote});
+        let i = yt.post(json_has_vote).issues();
+        let i = i.id(id.as_str());
+        let i = i.voters().execute::<Value>().await?;
+
+        let (headers, status, json) = i;
+        debug!("{:#?}", headers);
         debug!("{}", status);
         debug!("{:?}", json);
         if !status.is_success() {
@@ -338,26 +3932,757 @@ impl Bot {
                 bail!("Unable to vote for issue");
             }
         };
-        Ok(!has_vote)
+        Ok(!has_vote)
+    }
+
+    async fn handle_prioritize(
+        &self,
+        msg: &Message,
+        params: &Option<PrioritizeParams>,
+    ) -> Result<UserStateMessages> {
+        let chat = i64::from(msg.chat.id());
+        let mut con = self.redis.get_connection()?;
+        let open: Option<String> = con.get(format!("prioritize:{}", chat))?;
+        match params {
+            Some(PrioritizeParams::Stop) => match open {
+                Some(poll) => self.finish_prioritization(&poll, true).await?,
+                None => self
+                    .api
+                    .spawn(msg.text_reply("No prioritization poll is open in this chat")),
+            },
+            Some(PrioritizeParams::Start(query, count)) => {
+                if open.is_some() {
+                    self.api.spawn(msg.text_reply(
+                        "A prioritization poll is open already, close it with /prioritize stop",
+                    ));
+                    return Ok(UserStateMessages::Noop(Noop {}));
+                }
+                let query = match query {
+                    Some(query) => query.clone(),
+                    None => self
+                        .get_chat_config(chat)?
+                        .backlog_query
+                        .unwrap_or_else(|| self.backlog_query.clone()),
+                };
+                let yt = match self.get_youtrack(msg.from.id).await {
+                    Some(yt) => yt,
+                    None => {
+                        self.api.spawn(msg.text_reply(
+                            "No valid access token founds, use /login command to login in youtrack",
+                        ));
+                        return Ok(UserStateMessages::Noop(Noop {}));
+                    }
+                };
+                let issues: Vec<IssueHeader> =
+                    search_issues(yt, &query, *count as i32, "idReadable,summary").await?;
+                if issues.len() < prioritization::MIN_OPTIONS {
+                    self.api.spawn(msg.text_reply(format!(
+                        "Found {} issues, a poll needs at least {}",
+                        issues.len(),
+                        prioritization::MIN_OPTIONS
+                    )));
+                    return Ok(UserStateMessages::Noop(Noop {}));
+                }
+                let options: Vec<String> = issues.iter().map(poll_option).collect();
+                let mut poll = SendPoll::new(
+                    msg.chat.id(),
+                    "Which issues should we do first? Pick as many as you like",
+                    options,
+                );
+                // Answers of anonymous polls don't reach the bot
+                poll.not_anonymous().allows_multiple_answers();
+                let sent = self.api.send(poll).await?;
+                let (poll, message) = match &sent {
+                    MessageOrChannelPost::Message(sent) => match &sent.kind {
+                        MessageKind::Poll { data } => (data.id.clone(), i64::from(sent.id)),
+                        _ => bail!("Unexpected poll message"),
+                    },
+                    MessageOrChannelPost::ChannelPost(_) => {
+                        bail!("Polls aren't supported in channels")
+                    }
+                };
+                let session = PrioritySession {
+                    poll,
+                    chat,
+                    message,
+                    starter: i64::from(msg.from.id),
+                    query,
+                    issues,
+                    answers: HashMap::new(),
+                };
+                let _: () = con.set_ex(session.key(), &session, prioritization::SESSION_TTL)?;
+                let _: () = con.set_ex(
+                    format!("prioritize:{}", chat),
+                    &session.poll,
+                    prioritization::SESSION_TTL,
+                )?;
+            }
+            None => self.api.spawn(msg.text_reply(format!(
+                "Usage: /prioritize [query] [number of issues from {} to {}] or /prioritize stop",
+                prioritization::MIN_OPTIONS,
+                prioritization::MAX_OPTIONS
+            ))),
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    fn handle_poll_answer(&self, answer: &PollAnswer) -> Result<()> {
+        let mut con = self.redis.get_connection()?;
+        let key = format!("poll:{}", answer.poll_id);
+        let session: Option<PrioritySession> = con.get(&key)?;
+        if let Some(mut session) = session {
+            let options = answer.option_ids.iter().map(|o| *o as usize).collect();
+            session.answer(i64::from(answer.user.id), options);
+            let _: () = con.set_ex(&key, &session, prioritization::SESSION_TTL)?;
+        }
+        Ok(())
+    }
+
+    /// Closes the poll and posts the ranked result, `stop` is false when the poll
+    /// was closed in Telegram already
+    async fn finish_prioritization(&self, poll: &str, stop: bool) -> Result<()> {
+        let mut con = self.redis.get_connection()?;
+        let session: Option<PrioritySession> = con.get(format!("poll:{}", poll))?;
+        let session = match session {
+            Some(session) => session,
+            None => return Ok(()),
+        };
+        // Stopping the poll sends its closed update too, the result is posted once
+        let open_key = format!("prioritize:{}", session.chat);
+        let open: Option<String> = con.get(&open_key)?;
+        if open.as_deref() != Some(poll) {
+            return Ok(());
+        }
+        let _: () = con.del(&open_key)?;
+        if stop {
+            let stop = StopPoll::new(ChatId::new(session.chat), MessageId::new(session.message));
+            if let Err(e) = self.api.send(stop).await {
+                warn!("Unable to stop poll {}: {}", poll, e);
+            }
+        }
+
+        let mut context = Context::new();
+        context.insert("query", &session.query);
+        context.insert("participants", &session.answers.len());
+        context.insert("ranking", &session.ranking());
+        context.insert("youtrack_url", &self.yt.get_uri());
+        let txt_msg = self.templates.render("prioritize.md", &context).unwrap();
+        let mut reply = ChatId::new(session.chat).text(txt_msg);
+        reply
+            .reply_to(MessageId::new(session.message))
+            .parse_mode(ParseMode::Markdown);
+        if !session.answers.is_empty() {
+            let mut kb = InlineKeyboardMarkup::new();
+            kb.add_row(vec![CallbackParams::ApplyPriorityVotes(PollParams {
+                poll: session.poll.clone(),
+            })
+            .into()]);
+            reply.reply_markup(kb);
+        }
+        self.api.send(reply).await?;
+        Ok(())
+    }
+
+    /// Votes in YouTrack for the issues each logged in participant picked
+    async fn apply_priority_votes(&self, msg: &Message, user: UserId, poll: &str) -> Result<()> {
+        let mut con = self.redis.get_connection()?;
+        let key = format!("poll:{}", poll);
+        let session: Option<PrioritySession> = con.get(&key)?;
+        let session = match session {
+            Some(session) => session,
+            None => {
+                self.api
+                    .spawn(msg.text_reply("The poll result has expired or was applied already"));
+                return Ok(());
+            }
+        };
+        // Votes are cast on behalf of every participant, so not by anyone in the chat
+        if session.starter != i64::from(user) {
+            self.api
+                .spawn(msg.text_reply("Only the user who started the poll can apply its votes"));
+            return Ok(());
+        }
+        // Applied once, votes of the participants logged in later are theirs to cast
+        let _: () = con.del(&key)?;
+        self.api
+            .spawn(msg.edit_reply_markup(Some(reply_markup!(inline_keyboard, []))));
+
+        let mut applied = 0;
+        let mut logged_out = 0;
+        for user in session.answers.keys() {
+            match self.get_youtrack(UserId::new(*user)).await {
+                Some(yt) => {
+                    for issue in session.choice(*user) {
+                        if let Err(e) = self.vote_for_issue(yt, false, issue.clone()).await {
+                            warn!("Unable to vote for {} as {}: {}", issue, user, e);
+                        }
+                    }
+                    applied += 1;
+                }
+                None => logged_out += 1,
+            }
+        }
+        let mut text = format!("Applied votes of {} participants", applied);
+        if logged_out > 0 {
+            text.push_str(&format!(
+                ", {} more have to /login and vote themselves",
+                logged_out
+            ));
+        }
+        self.api.spawn(msg.text_reply(text));
+        Ok(())
+    }
+
+    async fn handle_estimate(
+        &self,
+        msg: &Message,
+        params: &Option<EstimateParams>,
+    ) -> Result<UserStateMessages> {
+        let params = match params {
+            Some(params) => params,
+            None => {
+                self.api
+                    .spawn(msg.text_reply("Usage: /estimate TP-123 [fibonacci|tshirt]"));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        let chat = i64::from(msg.chat.id());
+        let yt = match self.get_youtrack(msg.from.id).await {
+            Some(yt) => yt,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "No valid access token founds, use /login command to login in youtrack",
+                ));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        let card = match IssueCard::get(yt, &params.issue).await {
+            Ok(card) => card,
+            Err(e) => {
+                self.api
+                    .spawn(msg.text_reply(format!("Unable to fetch {}: {}", params.issue, e)));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        let mut con = self.redis.get_connection()?;
+        let latest: Option<i64> = con.get(estimation::issue_key(chat, &card.id_readable))?;
+        let running: Option<EstimationSession> = match latest {
+            Some(message) => con.get(estimation::session_key(chat, message))?,
+            None => None,
+        };
+        if let Some(EstimationSession {
+            revealed: false, ..
+        }) = running
+        {
+            self.api
+                .spawn(msg.text_reply(format!("{} is being estimated already", card.id_readable)));
+            return Ok(UserStateMessages::Noop(Noop {}));
+        }
+
+        let expected = match msg.chat {
+            MessageChat::Private(_) => 1,
+            _ => {
+                // Other bots in the chat can't be told apart and never vote,
+                // the host reveals the round then
+                let count = self
+                    .api
+                    .send(GetChatMembersCount::new(msg.chat.id()))
+                    .await?;
+                (count as usize).saturating_sub(1).max(1)
+            }
+        };
+        let mut session = EstimationSession {
+            chat,
+            message: 0,
+            issue: IssueHeader {
+                id_readable: card.id_readable,
+                summary: card.summary,
+                reporter: None,
+            },
+            host: i64::from(msg.from.id),
+            scale: params
+                .scale
+                .unwrap_or(self.get_chat_config(chat)?.estimation_scale),
+            expected,
+            votes: Vec::new(),
+            revealed: false,
+        };
+        // Keyed by the round message, so buttons of older rounds don't act on this one
+        session.message = match self.show_estimation(msg, &session, false).await? {
+            MessageOrChannelPost::Message(sent) => i64::from(sent.id),
+            MessageOrChannelPost::ChannelPost(_) => return Ok(UserStateMessages::Noop(Noop {})),
+        };
+        let _: () = con.set_ex(session.key(), &session, estimation::SESSION_TTL)?;
+        let _: () = con.set_ex(
+            estimation::issue_key(chat, &session.issue.id_readable),
+            session.message,
+            estimation::SESSION_TTL,
+        )?;
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    async fn show_estimation(
+        &self,
+        msg: &Message,
+        session: &EstimationSession,
+        edit: bool,
+    ) -> Result<MessageOrChannelPost> {
+        let mut context = Context::new();
+        context.insert("issue", &session.issue);
+        context.insert("youtrack_url", &self.yt.get_uri());
+        context.insert("revealed", &session.revealed);
+        context.insert("voters", &session.voters());
+        context.insert("expected", &session.expected);
+        context.insert("distribution", &session.distribution());
+        let txt_msg = self.templates.render("estimate.md", &context).unwrap();
+        let kb = estimation_keyboard(session);
+        let sent = if edit {
+            self.api
+                .send(
+                    msg.edit_text(txt_msg)
+                        .reply_markup(kb)
+                        .parse_mode(ParseMode::Markdown),
+                )
+                .await?
+        } else {
+            self.api
+                .send(
+                    msg.text_reply(txt_msg)
+                        .reply_markup(kb)
+                        .parse_mode(ParseMode::Markdown),
+                )
+                .await?
+        };
+        Ok(sent)
+    }
+
+    async fn handle_estimation_action(&self, cmd: BotCommand) -> Result<UserStateMessages> {
+        let (cb, issue) = match &cmd {
+            BotCommand::EstimationVote(cb, p) | BotCommand::EstimationApply(cb, p) => {
+                (cb, &p.issue)
+            }
+            BotCommand::EstimationReveal(cb, p) => (cb, &p.issue),
+            _ => return Ok(UserStateMessages::Noop(Noop {})),
+        };
+        let msg = cb.message.clone().unwrap();
+        let mut con = self.redis.get_connection()?;
+        let key = estimation::session_key(i64::from(msg.chat.id()), i64::from(msg.id));
+        let session: Option<EstimationSession> = con.get(&key)?;
+        let mut session = match session {
+            Some(session) => session,
+            None => {
+                self.api
+                    .spawn(msg.edit_reply_markup(Some(reply_markup!(inline_keyboard, []))));
+                self.api
+                    .spawn(msg.text_reply("This estimation round has expired"));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        let user = i64::from(cb.from.id);
+        let values = session.scale.values();
+        match &cmd {
+            BotCommand::EstimationVote(_, p) => {
+                let value = match values.get(p.value) {
+                    Some(value) if !session.revealed => value.to_string(),
+                    _ => return Ok(UserStateMessages::Noop(Noop {})),
+                };
+                session.vote(user, full_name(&cb.from), value);
+                let _: () = con.set_ex(&key, &session, estimation::SESSION_TTL)?;
+                self.show_estimation(&msg, &session, true).await?;
+            }
+            BotCommand::EstimationReveal(_, _) => {
+                if user != session.host {
+                    self.api
+                        .spawn(msg.text_reply("Only the one who started the round can reveal it"));
+                    return Ok(UserStateMessages::Noop(Noop {}));
+                }
+                session.revealed = true;
+                let _: () = con.set_ex(&key, &session, estimation::SESSION_TTL)?;
+                self.show_estimation(&msg, &session, true).await?;
+            }
+            BotCommand::EstimationApply(_, p) => {
+                if user != session.host {
+                    self.api
+                        .spawn(msg.text_reply("Only the one who started the round can apply it"));
+                    return Ok(UserStateMessages::Noop(Noop {}));
+                }
+                let presentation = match values
+                    .get(p.value)
+                    .and_then(|value| session.scale.presentation(value))
+                {
+                    Some(presentation) => presentation,
+                    None => return Ok(UserStateMessages::Noop(Noop {})),
+                };
+                let yt = match self.get_youtrack(cb.from.id).await {
+                    Some(yt) => yt,
+                    None => {
+                        self.api.spawn(msg.text_reply(
+                            "No valid access token founds, use /login command to login in youtrack",
+                        ));
+                        return Ok(UserStateMessages::Noop(Noop {}));
+                    }
+                };
+                let card = IssueCard::get(yt, issue).await?;
+                let field = match card.field(ESTIMATION_FIELD) {
+                    Some(field) => field,
+                    None => {
+                        self.api.spawn(
+                            msg.text_reply(format!("{} has no {} field", issue, ESTIMATION_FIELD)),
+                        );
+                        return Ok(UserStateMessages::Noop(Noop {}));
+                    }
+                };
+                let update = IssueDraftCustomField::period(
+                    field.id.clone(),
+                    field.name.clone(),
+                    presentation.clone(),
+                );
+                IssueCard::update_field(yt, issue, update).await?;
+                let _: () = con.del(&key)?;
+                self.api
+                    .spawn(msg.edit_reply_markup(Some(reply_markup!(inline_keyboard, []))));
+                self.api.spawn(msg.text_reply(format!(
+                    "{} of {} is set to {}",
+                    ESTIMATION_FIELD, issue, presentation
+                )));
+            }
+            _ => {}
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    async fn handle_triage(
+        &self,
+        msg: &Message,
+        query: &Option<String>,
+    ) -> Result<UserStateMessages> {
+        let query = match query {
+            Some(query) => query.clone(),
+            None => {
+                self.api.spawn(
+                    msg.text_reply("Usage: /triage query, e.g. /triage #Unassigned #Unresolved"),
+                );
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        let yt = match self.get_youtrack(msg.from.id).await {
+            Some(yt) => yt,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "No valid access token founds, use /login command to login in youtrack",
+                ));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        // Every issue is shown in this message in turn
+        let sent = self
+            .api
+            .send(msg.text_reply(format!("Triaging {}", query)))
+            .await?;
+        let message = match &sent {
+            MessageOrChannelPost::Message(sent) => i64::from(sent.id),
+            MessageOrChannelPost::ChannelPost(_) => return Ok(UserStateMessages::Noop(Noop {})),
+        };
+        let mut session = TriageSession {
+            chat: i64::from(msg.chat.id()),
+            message,
+            query,
+            issue: None,
+            seen: Vec::new(),
+        };
+        if let Err(e) = self.show_triage(yt, &mut session).await {
+            warn!("Error occured: {}", e);
+            self.api
+                .spawn(msg.text_reply(format!("Error occured: {}", e)));
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    /// Moves the triage message to the next issue, the session ends when there are none
+    async fn show_triage(&self, yt: &YouTrack, session: &mut TriageSession) -> Result<()> {
+        let page = session.page();
+        let issues = self
+            ._fetch_issues(yt, &session.query, page.top, page.skip)
+            .await?;
+        session.next(&issues);
+
+        let chat = ChatId::new(session.chat);
+        let message = MessageId::new(session.message);
+        let mut con = self.redis.get_connection()?;
+        let issue = match &session.issue {
+            Some(issue) => issue.clone(),
+            None => {
+                let _: () = con.del(session.key())?;
+                let text = format!(
+                    "No more issues to triage in {}, {} done",
+                    session.query,
+                    session.seen.len()
+                );
+                self.api
+                    .send(
+                        EditMessageText::new(chat, message, text)
+                            .reply_markup(reply_markup!(inline_keyboard, [])),
+                    )
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        let card = IssueCard::get(yt, &issue).await?;
+        let mut context = Context::new();
+        context.insert("issue", &card);
+        context.insert("fields", &card.field_values());
+        context.insert("youtrack_url", &self.yt.get_uri());
+        context.insert("query", &session.query);
+        context.insert("seen", &session.seen.len());
+        let txt_msg = self.templates.render("triage.md", &context).unwrap();
+        self.api
+            .send(
+                EditMessageText::new(chat, message, txt_msg)
+                    .reply_markup(triage_keyboard(&card))
+                    .parse_mode(ParseMode::Markdown),
+            )
+            .await?;
+        let _: () = con.set_ex(session.key(), &*session, triage::SESSION_TTL)?;
+        Ok(())
+    }
+
+    fn get_triage_session(&self, msg: &Message) -> Result<Option<TriageSession>> {
+        let mut con = self.redis.get_connection()?;
+        let key = triage::session_key(i64::from(msg.chat.id()), i64::from(msg.id));
+        Ok(con.get(key)?)
+    }
+
+    /// Titles of the values the field can be set to, along with the updates setting them
+    async fn triage_choices(
+        &self,
+        yt: &YouTrack,
+        card: &IssueCard,
+        field: TriageField,
+    ) -> Result<Vec<(String, IssueDraftCustomField)>> {
+        let (id, name) = match card.field(field.name()) {
+            Some(f) => (f.id.clone(), f.name.clone()),
+            None => bail!("{} has no {} field", card.id_readable, field.name()),
+        };
+        Ok(match field {
+            TriageField::Assignee => card
+                .user_values(yt, field.name())
+                .await?
+                .into_iter()
+                .map(|u| {
+                    let title = u.title();
+                    (
+                        title,
+                        IssueDraftCustomField::user(id.clone(), name.clone(), u.login),
+                    )
+                })
+                .collect(),
+            TriageField::Priority | TriageField::Type => card
+                .enum_values(yt, field.name())
+                .await?
+                .into_iter()
+                .map(|v| {
+                    let title = v.title();
+                    (
+                        title,
+                        IssueDraftCustomField::new(id.clone(), name.clone(), v.name),
+                    )
+                })
+                .collect(),
+        })
+    }
+
+    async fn handle_triage_action(&self, cmd: BotCommand) -> Result<UserStateMessages> {
+        let (cb, issue) = match &cmd {
+            BotCommand::TriageMenu(cb, p) => (cb, &p.issue),
+            BotCommand::TriageSet(cb, p) => (cb, &p.issue),
+            BotCommand::TriageBack(cb, p)
+            | BotCommand::TriageNeedInfo(cb, p)
+            | BotCommand::TriageSkip(cb, p)
+            | BotCommand::TriageStop(cb, p) => (cb, &p.issue),
+            _ => return Ok(UserStateMessages::Noop(Noop {})),
+        };
+        let msg = cb.message.clone().unwrap();
+        let mut session = match self.get_triage_session(&msg)? {
+            Some(session) => session,
+            None => {
+                self.api
+                    .spawn(msg.edit_reply_markup(Some(reply_markup!(inline_keyboard, []))));
+                self.api
+                    .spawn(msg.text_reply("This triage session has expired"));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        // Pressed on an issue somebody has moved past already
+        if !session.is_current(issue) {
+            return Ok(UserStateMessages::Noop(Noop {}));
+        }
+        if let BotCommand::TriageStop(_, _) = &cmd {
+            let mut con = self.redis.get_connection()?;
+            let _: () = con.del(session.key())?;
+            self.api
+                .spawn(msg.edit_reply_markup(Some(reply_markup!(inline_keyboard, []))));
+            return Ok(UserStateMessages::Noop(Noop {}));
+        }
+        let yt = match self.get_youtrack(cb.from.id).await {
+            Some(yt) => yt,
+            None => {
+                self.api.spawn(msg.text_reply(
+                    "No valid access token founds, use /login command to login in youtrack",
+                ));
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+        };
+        let res = match &cmd {
+            BotCommand::TriageMenu(_, p) => {
+                let card = IssueCard::get(yt, issue).await?;
+                let titles: Vec<String> = self
+                    .triage_choices(yt, &card, p.field)
+                    .await?
+                    .into_iter()
+                    .map(|(title, _)| title)
+                    .collect();
+                let kb = triage_values_keyboard(issue, p.field, &titles, p.page);
+                self.api.send(msg.edit_reply_markup(Some(kb))).await?;
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+            BotCommand::TriageBack(_, _) => {
+                let card = IssueCard::get(yt, issue).await?;
+                self.api
+                    .send(msg.edit_reply_markup(Some(triage_keyboard(&card))))
+                    .await?;
+                return Ok(UserStateMessages::Noop(Noop {}));
+            }
+            BotCommand::TriageSet(_, p) => {
+                let card = IssueCard::get(yt, issue).await?;
+                match self
+                    .triage_choices(yt, &card, p.field)
+                    .await?
+                    .into_iter()
+                    .nth(p.value)
+                {
+                    Some((_, update)) => IssueCard::update_field(yt, issue, update).await,
+                    None => return Ok(UserStateMessages::Noop(Noop {})),
+                }
+            }
+            BotCommand::TriageNeedInfo(_, _) => IssueComment::create(yt, issue, NEED_INFO_COMMENT)
+                .await
+                .map(|_| ()),
+            _ => Ok(()),
+        };
+        match res {
+            Ok(_) => {
+                session.advance();
+                self.show_triage(yt, &mut session).await?;
+            }
+            Err(e) => {
+                warn!("Error occured: {}", e);
+                self.api
+                    .spawn(msg.text_reply(format!("Error occured: {}", e)));
+            }
+        }
+        Ok(UserStateMessages::Noop(Noop {}))
+    }
+
+    /// "Duplicate of…" asks for the original issue and waits for it in the user state
+    async fn start_duplicate(
+        &self,
+        cb: &CallbackQuery,
+        params: &TriageParams,
+    ) -> Result<UserStateMessages> {
+        let msg = cb.message.clone().unwrap();
+        match self.get_triage_session(&msg)? {
+            Some(ref session) if session.is_current(&params.issue) => {}
+            _ => return Ok(UserStateMessages::Noop(Noop {})),
+        }
+        let kb = reply_markup!(force_reply);
+        self.api
+            .send(
+                msg.text_reply(format!(
+                    "Which issue does {} duplicate? Send its id or /cancel",
+                    params.issue
+                ))
+                .reply_markup(kb),
+            )
+            .await?;
+        Ok(UserStateMessages::StartDuplicate(StartDuplicate(
+            params.issue.clone(),
+            i64::from(msg.id),
+        )))
     }
 
-    fn get_state(&mut self, uid: UserId) -> Result<UserState> {
+    fn get_state(&mut self, chat: ChatId, uid: UserId) -> Result<UserState> {
         let mut con = self.redis.get_connection()?;
-        let key = format!("state:{}", uid);
-        match con.get(key)? {
-            Some(state) => Ok(state),
+        let key = state_key(chat, uid);
+        let stored: Option<StoredUserState> = match con.get(key) {
+            Ok(stored) => stored,
+            // States saved by an older version may no longer deserialize
+            Err(ref e) if e.kind() == redis::ErrorKind::TypeError => {
+                warn!("Discarding unreadable state of user {}: {}", uid, e);
+                None
+            }
+            Err(e) => return Err(e.into()),
+        };
+        match stored {
+            Some(stored) => {
+                let idle_for = now_timestamp().saturating_sub(stored.updated_at);
+                if stored.state.is_issue_draft() && idle_for > self.wizard_timeout {
+                    info!("Discarding abandoned draft of user {}", uid);
+                    self.api.spawn(chat.text(format!(
+                        "Your draft from {} was discarded",
+                        format_ago(idle_for)
+                    )));
+                    Ok(UserState::idle())
+                } else {
+                    Ok(stored.state)
+                }
+            }
             None => Ok(UserState::idle()),
         }
     }
 
-    fn get_state_by_update(&mut self, update: &Update) -> Result<(UserId, UserState)> {
-        let uid = match &update.kind {
-            UpdateKind::Message(m) => m.from.id,
-            UpdateKind::CallbackQuery(cb) => cb.from.id,
+    fn save_state(&self, chat: ChatId, uid: UserId, state: UserState) -> Result<()> {
+        let mut con = self.redis.get_connection()?;
+        let key = state_key(chat, uid);
+        let stored = StoredUserState {
+            state,
+            updated_at: now_timestamp(),
+        };
+        let _: () = con.set_ex(key, stored, self.state_ttl)?;
+        Ok(())
+    }
+
+    /// State of the user in the chat the update comes from
+    fn get_state_by_update(&mut self, update: &Update) -> Result<(ChatId, UserId, UserState)> {
+        let (chat, uid) = match &update.kind {
+            UpdateKind::Message(m) => (m.chat.id(), m.from.id),
+            UpdateKind::CallbackQuery(cb) => match &cb.message {
+                Some(m) => (m.chat.id(), cb.from.id),
+                None => (ChatId::new(i64::from(cb.from.id)), cb.from.id),
+            },
             _ => bail!("Unsupported update type"),
         };
-        let state = self.get_state(uid)?;
-        Ok((uid, state))
+        let state = self.get_state(chat, uid)?;
+        Ok((chat, uid, state))
+    }
+
+    /// Whether a command in the update is addressed to another bot in the group
+    async fn is_for_other_bot(&mut self, update: &Update) -> Result<bool> {
+        let addressee = match &update.kind {
+            UpdateKind::Message(m) => match command_addressee(m) {
+                Some(addressee) => addressee.to_lowercase(),
+                None => return Ok(false),
+            },
+            _ => return Ok(false),
+        };
+        if self.username.is_none() {
+            let me = self.api.send(GetMe).await?;
+            self.username = me.username;
+        }
+        Ok(match &self.username {
+            Some(username) => username.to_lowercase() != addressee,
+            None => false,
+        })
     }
 
     async fn handle_command_idle(
@@ -370,85 +4695,71 @@ impl Bot {
             BotCommand::Start(msg) => self.handle_start(msg).await?,
             BotCommand::Login(msg) => self.handle_login(msg).await?,
             BotCommand::NewIssue(msg) => self.handle_new_issue(msg).await?,
+            BotCommand::Comment(msg, p) => self.handle_comment(msg, p).await?,
+            BotCommand::IssueCommand(msg, p) => self.handle_issue_command(msg, p).await?,
+            BotCommand::IssueCard(msg, issue_id) => self.handle_issue_card(msg, issue_id).await?,
+            BotCommand::Spent(msg, p) => self.handle_spent(msg, p).await?,
+            BotCommand::Timer(msg, arg) => self.handle_timer(msg, arg).await?,
+            BotCommand::Timesheet(msg, p) => self.handle_timesheet(msg, p).await?,
+            BotCommand::History(msg, issue_id) => self.handle_history(msg, issue_id).await?,
+            BotCommand::Watch(msg, query) => self.handle_watch(msg, query).await?,
+            BotCommand::Unwatch(msg, query) => self.handle_unwatch(msg, query).await?,
+            BotCommand::Digest(msg, p) => self.handle_digest(msg, p).await?,
+            BotCommand::Timezone(msg, tz) => self.handle_timezone(msg, tz).await?,
+            BotCommand::Settings(msg, p) => self.handle_settings(msg, p).await?,
+            BotCommand::Board(msg, name) => self.handle_board(msg, name).await?,
+            BotCommand::Burndown(msg, name) => self.handle_burndown(msg, name).await?,
+            BotCommand::Velocity(msg, name) => self.handle_velocity(msg, name).await?,
+            BotCommand::Standup(msg, p) => self.handle_standup(msg, p).await?,
+            BotCommand::Config(msg, p) => self.handle_config(msg, p).await?,
+            BotCommand::Prioritize(msg, p) => self.handle_prioritize(msg, p).await?,
+            BotCommand::Estimate(msg, p) => self.handle_estimate(msg, p).await?,
+            BotCommand::Triage(msg, query) => self.handle_triage(msg, query).await?,
+            BotCommand::TriageDuplicate(cb, p) => self.start_duplicate(cb, p).await?,
+            BotCommand::BulkCommand(cb, p) => self.start_bulk_command(cb, p).await?,
+            BotCommand::Text(msg) | BotCommand::Attachment(msg) => self.handle_reply(msg).await?,
             _ => UserStateMessages::Noop(Noop {}),
         })
     }
 
-    async fn handle_command_in_backlog(
+    async fn handle_command_new_issue(
         &mut self,
-        state: &InBacklog,
+        state: &NewIssue,
         cmd: BotCommand,
     ) -> Result<UserStateMessages> {
-        let msg = match &cmd {
-            BotCommand::BacklogStop(cb) => {
-                let msg = cb.message.clone().unwrap();
-                self.api
-                    .send(msg.edit_reply_markup(Some(reply_markup!(inline_keyboard, []))))
-                    .await?;
-                UserStateMessages::StopBacklog(StopBacklog {})
-            }
-            BotCommand::BacklogNext(cb, p) | BotCommand::BacklogPrev(cb, p) => {
-                let msg = cb.message.clone().unwrap();
-                self.fetch_issues(cb.from.id, &msg, p).await?
-            }
-            BotCommand::BacklogVoteForIssue(cb, p) => {
+        let res = match &cmd {
+            BotCommand::PickerSelect(cb, _) | BotCommand::PickerPage(cb, _) => {
                 let msg = cb.message.clone().unwrap();
-                let user = cb.from.id;
-                match self.get_youtrack(user).await {
-                    Some(yt) => match self.vote_for_issue(yt, p.has_vote, p.id.clone()).await {
-                        Ok(_) => {
-                            self.fetch_issues(
-                                user,
-                                &msg,
-                                &BacklogParams::new_with_skip(state.top, state.skip),
-                            )
-                            .await?
-                        }
-                        Err(e) => {
-                            warn!("Error occured: {}", e);
-                            self.api
-                                .spawn(msg.text_reply(format!("Error occured: {}", e)));
-                            UserStateMessages::Noop(Noop {})
-                        }
-                    },
-                    None => {
-                        warn!("No youtrack instance for user {}", user);
-                        self.api
-                            .spawn(msg.edit_reply_markup(Some(reply_markup!(inline_keyboard, []))));
-                        self.api.spawn(msg.text_reply(format!(
-                            "No valid access token founds, use /login command to login in youtrack"
-                        )));
-                        UserStateMessages::StopBacklog(StopBacklog {})
+                let templates = self.get_issue_templates(msg.chat.id())?;
+                match self
+                    .pick(&cmd, PickerStep::Template, &templates, &[])
+                    .await?
+                {
+                    Some(index) => {
+                        let template = templates[index].clone();
+                        let kb = reply_markup!(force_reply);
+                        self.api.spawn(
+                            msg.text_reply(format!(
+                                "Creating {}. Please, enter issue summary.",
+                                template.name
+                            ))
+                            .reply_markup(kb),
+                        );
+                        UserStateMessages::IssueTemplateSelected(IssueTemplateSelected(template))
                     }
+                    None => UserStateMessages::Noop(Noop {}),
                 }
             }
-            _ => UserStateMessages::Noop(Noop {}),
-        };
-        Ok(msg)
-    }
-
-    async fn handle_command_new_issue(
-        &mut self,
-        state: &NewIssue,
-        cmd: BotCommand,
-    ) -> Result<UserStateMessages> {
-        let res = match &cmd {
             BotCommand::Text(msg) => {
                 if let Some(summary) = cmd.get_message_text() {
-                    let projects = self.get_projects().await?;
-                    let kb = make_reply_keyboard(projects, |s| s.name.clone().unwrap());
-                    self.api.spawn(
-                        msg.from
-                            .text(format!("Got it. Now select project for the issue."))
-                            .reply_markup(kb),
-                    );
-                    state.summary(summary)
+                    self.continue_draft(msg, state.template.clone(), summary, None, None, None)
+                        .await?
                 } else {
                     UserStateMessages::Noop(Noop {})
                 }
             }
             BotCommand::Cancel(msg) => {
-                self.api.spawn(msg.from.text("cancel"));
+                self.api.spawn(msg.text_reply("cancel"));
                 UserStateMessages::Cancel(Cancel {})
             }
             _ => UserStateMessages::Noop(Noop {}),
@@ -462,28 +4773,25 @@ impl Bot {
         cmd: BotCommand,
     ) -> Result<UserStateMessages> {
         let res = match &cmd {
-            BotCommand::Text(msg) => {
-                if let Some(project) = cmd.get_message_text() {
-                    match self.get_project(project).await {
-                        Ok(project) => {
-                            let stream = project.streams(&self.yt).await?;
-                            let values = stream.values.unwrap();
-                            let kb = make_reply_keyboard(values, |s| s.name.clone());
-                            self.api.spawn(
-                                msg.from
-                                    .text("Got it. Now select stream for the issue")
-                                    .reply_markup(kb),
-                            );
-                            state.project(project)
-                        }
-                        Err(_) => UserStateMessages::Noop(Noop {}),
+            BotCommand::Text(_) | BotCommand::PickerSelect(_, _) | BotCommand::PickerPage(_, _) => {
+                let projects = self.get_projects().await?;
+                match self.pick(&cmd, PickerStep::Project, &projects, &[]).await? {
+                    Some(index) => {
+                        self.continue_draft(
+                            cmd.get_message().unwrap(),
+                            state.template.clone(),
+                            state.summary.clone(),
+                            Some(projects[index].clone()),
+                            None,
+                            None,
+                        )
+                        .await?
                     }
-                } else {
-                    UserStateMessages::Noop(Noop {})
+                    None => UserStateMessages::Noop(Noop {}),
                 }
             }
             BotCommand::Cancel(msg) => {
-                self.api.spawn(msg.from.text("cancel"));
+                self.api.spawn(msg.text_reply("cancel"));
                 UserStateMessages::Cancel(Cancel {})
             }
             _ => UserStateMessages::Noop(Noop {}),
@@ -497,30 +4805,27 @@ impl Bot {
         cmd: BotCommand,
     ) -> Result<UserStateMessages> {
         let res = match &cmd {
-            BotCommand::Text(msg) => {
-                if let Some(stream) = cmd.get_message_text() {
-                    // TODO: parse stream
-                    let stream_bundle = state.project.streams(&self.yt).await?;
-                    if stream_bundle.has_value(&stream) {
-                        let type_bundle = state.project.types(&self.yt).await?;
-                        let values = type_bundle.values.unwrap();
-                        let kb = make_reply_keyboard(values, |s| s.name.clone());
-                        self.api.spawn(
-                            msg.from
-                                .text("Got it. Now select issue type.")
-                                .reply_markup(kb),
-                        );
+            BotCommand::Text(_) | BotCommand::PickerSelect(_, _) | BotCommand::PickerPage(_, _) => {
+                let streams = state.project.streams(&self.yt).await?;
+                let streams = streams.values.unwrap_or_default();
+                match self.pick(&cmd, PickerStep::Stream, &streams, &[]).await? {
+                    Some(index) => {
                         let field = state.project.get_project_custom_field("Stream").unwrap();
-                        state.stream(IssueStream(field.id.clone(), stream))
-                    } else {
-                        UserStateMessages::Noop(Noop {})
+                        self.continue_draft(
+                            cmd.get_message().unwrap(),
+                            state.template.clone(),
+                            state.summary.clone(),
+                            Some(state.project.clone()),
+                            Some(IssueStream(field.id.clone(), streams[index].name.clone())),
+                            None,
+                        )
+                        .await?
                     }
-                } else {
-                    UserStateMessages::Noop(Noop {})
+                    None => UserStateMessages::Noop(Noop {}),
                 }
             }
             BotCommand::Cancel(msg) => {
-                self.api.spawn(msg.from.text("cancel"));
+                self.api.spawn(msg.text_reply("cancel"));
                 UserStateMessages::Cancel(Cancel {})
             }
             _ => UserStateMessages::Noop(Noop {}),
@@ -534,26 +4839,27 @@ impl Bot {
         cmd: BotCommand,
     ) -> Result<UserStateMessages> {
         let res = match &cmd {
-            BotCommand::Text(msg) => {
-                if let Some(issue_type) = cmd.get_message_text() {
-                    // TODO: parse type
-                    //
-                    let type_bundle = state.project.types(&self.yt).await?;
-                    if type_bundle.has_value(&issue_type) {
-                        self.api
-                            .spawn(msg.from.text("Got it. Now type in issue description."));
-
+            BotCommand::Text(_) | BotCommand::PickerSelect(_, _) | BotCommand::PickerPage(_, _) => {
+                let types = state.project.types(&self.yt).await?;
+                let types = types.values.unwrap_or_default();
+                match self.pick(&cmd, PickerStep::Type, &types, &[]).await? {
+                    Some(index) => {
                         let field = state.project.get_project_custom_field("Type").unwrap();
-                        state.issue_type(IssueType(field.id.clone(), issue_type))
-                    } else {
-                        UserStateMessages::Noop(Noop {})
+                        self.continue_draft(
+                            cmd.get_message().unwrap(),
+                            state.template.clone(),
+                            state.summary.clone(),
+                            Some(state.project.clone()),
+                            Some(state.stream.clone()),
+                            Some(IssueType(field.id.clone(), types[index].name.clone())),
+                        )
+                        .await?
                     }
-                } else {
-                    UserStateMessages::Noop(Noop {})
+                    None => UserStateMessages::Noop(Noop {}),
                 }
             }
             BotCommand::Cancel(msg) => {
-                self.api.spawn(msg.from.text("cancel"));
+                self.api.spawn(msg.text_reply("cancel"));
                 UserStateMessages::Cancel(Cancel {})
             }
             _ => UserStateMessages::Noop(Noop {}),
@@ -565,6 +4871,112 @@ impl Bot {
         &mut self,
         state: &NewIssueSummaryProjectStreamType,
         cmd: BotCommand,
+    ) -> Result<UserStateMessages> {
+        // Some(None) leaves the issue unassigned
+        let user = match &cmd {
+            BotCommand::AssignToMe(cb) => match self.get_youtrack(cb.from.id).await {
+                Some(yt) => Some(Some(YouTrackUser::me(yt).await?)),
+                None => {
+                    let msg = cb.message.clone().unwrap();
+                    self.api.spawn(msg.text_reply(
+                        "No valid access token founds, use /login command to login in youtrack",
+                    ));
+                    None
+                }
+            },
+            BotCommand::Unassigned(_) => Some(None),
+            BotCommand::Text(_) | BotCommand::PickerSelect(_, _) | BotCommand::PickerPage(_, _) => {
+                let users = state.project.assignees(&self.yt).await?;
+                self.pick(
+                    &cmd,
+                    PickerStep::Assignee,
+                    &users,
+                    &assignee_extra_buttons(),
+                )
+                .await?
+                .map(|index| Some(users[index].clone()))
+            }
+            BotCommand::Cancel(msg) => {
+                self.api.spawn(msg.text_reply("cancel"));
+                return Ok(UserStateMessages::Cancel(Cancel {}));
+            }
+            _ => None,
+        };
+
+        let res = match user {
+            Some(user) => {
+                if let BotCommand::AssignToMe(cb) | BotCommand::Unassigned(cb) = &cmd {
+                    let msg = cb.message.clone().unwrap();
+                    self.api
+                        .spawn(msg.edit_reply_markup(Some(reply_markup!(inline_keyboard, []))));
+                }
+                let field = state.project.get_project_custom_field("Assignee");
+                let assignee = match (field, user) {
+                    (Some(field), Some(user)) => Some(IssueAssignee(field.id.clone(), user)),
+                    _ => None,
+                };
+                self.ask_tags(cmd.get_message().unwrap()).await?;
+                state.assignee(assignee)
+            }
+            None => UserStateMessages::Noop(Noop {}),
+        };
+        Ok(res)
+    }
+
+    async fn handle_command_new_issue_summary_project_stream_type_assignee(
+        &mut self,
+        state: &NewIssueSummaryProjectStreamTypeAssignee,
+        cmd: BotCommand,
+    ) -> Result<UserStateMessages> {
+        let res = match &cmd {
+            BotCommand::IssueTagToggle(cb, p) => {
+                let msg = cb.message.clone().unwrap();
+                let tags = IssueTag::list(&self.yt).await?;
+                match tags.iter().find(|t| t.id == p.id) {
+                    Some(tag) => {
+                        let selection = state.toggle_tag(tag.clone());
+                        let kb = issue_tags_keyboard(&tags, &selection.0);
+                        self.api.send(msg.edit_reply_markup(Some(kb))).await?;
+                        UserStateMessages::IssueTagsSelection(selection)
+                    }
+                    None => UserStateMessages::Noop(Noop {}),
+                }
+            }
+            BotCommand::IssueTagsDone(cb) => {
+                let msg = cb.message.clone().unwrap();
+                self.api
+                    .send(msg.edit_reply_markup(Some(reply_markup!(inline_keyboard, []))))
+                    .await?;
+                let skeleton = match &state.template {
+                    Some(template) => template.render_description(&cb.from)?,
+                    None => None,
+                };
+                match skeleton {
+                    // Plain text, the skeleton may contain anything
+                    Some(skeleton) => self.api.spawn(msg.text_reply(format!(
+                        "Got it. Now type in issue description, \
+                             you may copy this template:\n\n{}",
+                        skeleton
+                    ))),
+                    None => self
+                        .api
+                        .spawn(msg.text_reply("Got it. Now type in issue description.")),
+                }
+                state.tags(state.tags.clone())
+            }
+            BotCommand::Cancel(msg) => {
+                self.api.spawn(msg.text_reply("cancel"));
+                UserStateMessages::Cancel(Cancel {})
+            }
+            _ => UserStateMessages::Noop(Noop {}),
+        };
+        Ok(res)
+    }
+
+    async fn handle_command_new_issue_summary_project_stream_type_assignee_tags(
+        &mut self,
+        state: &NewIssueSummaryProjectStreamTypeAssigneeTags,
+        cmd: BotCommand,
     ) -> Result<UserStateMessages> {
         Ok(match &cmd {
             BotCommand::Text(msg) => {
@@ -583,8 +4995,7 @@ impl Bot {
                     let txt_msg = self.templates.render("new_issue.md", &context).unwrap();
 
                     self.api.spawn(
-                        msg.from
-                            .text(txt_msg)
+                        msg.text_reply(txt_msg)
                             .reply_markup(kb)
                             .parse_mode(ParseMode::Markdown),
                     );
@@ -594,23 +5005,23 @@ impl Bot {
                 }
             }
             BotCommand::Cancel(msg) => {
-                self.api.spawn(msg.from.text("cancel"));
+                self.api.spawn(msg.text_reply("cancel"));
                 UserStateMessages::Cancel(Cancel {})
             }
             _ => UserStateMessages::Noop(Noop {}),
         })
     }
 
-    async fn handle_command_new_issue_summary_project_stream_type_desc(
+    async fn handle_command_new_issue_summary_project_stream_type_assignee_tags_desc(
         &mut self,
-        state: &NewIssueSummaryProjectStreamTypeDesc,
+        state: &NewIssueSummaryProjectStreamTypeAssigneeTagsDesc,
         cmd: BotCommand,
     ) -> Result<UserStateMessages> {
         let user = cmd.get_user();
         let res = match &cmd {
             BotCommand::Save(msg) => {
                 if let Some(yt) = self.get_youtrack(user.id).await {
-                    self.api.spawn(msg.from.text("Saving issue"));
+                    self.api.spawn(msg.text_reply("Saving issue"));
                     let mut new_issue = IssueDraft::new();
                     let new_issue = new_issue
                         .summary(state.summary.clone())
@@ -627,7 +5038,31 @@ impl Bot {
                             state.issue_type.0.clone(),
                             "Type".to_string(),
                             state.issue_type.1.clone(),
-                        );
+                        )
+                        .tags(state.tags.clone());
+                    if let Some(IssueAssignee(field, user)) = &state.assignee {
+                        new_issue.assignee(field.clone(), user.login.clone());
+                    }
+                    let mut fields = self.get_chat_config(i64::from(msg.chat.id()))?.fields;
+                    if let Some(template) = &state.template {
+                        fields.extend(template.fields.clone());
+                    }
+                    // Stream and Type went through the wizard steps already
+                    for (name, value) in fields.iter() {
+                        if name == "Stream" || name == "Type" {
+                            continue;
+                        }
+                        let field = match state.project.get_project_custom_field(name) {
+                            Some(field) => field,
+                            None => continue,
+                        };
+                        match IssueDraftCustomField::for_field(field, value) {
+                            Some(field) => {
+                                new_issue.field(field);
+                            }
+                            None => warn!("Skipping field {}: unsupported value {}", name, value),
+                        }
+                    }
                     let i = yt.post(new_issue).issues().fields("idReadable");
                     let (headers, status, json) = i.execute::<Value>().await?;
 
@@ -637,8 +5072,14 @@ impl Bot {
                     if status.is_success() {
                         let issue_id = json.unwrap();
                         let issue_id = issue_id.get("idReadable").unwrap().as_str().unwrap();
-                        self.api
-                            .spawn(msg.from.text(format!("Issue {} created", issue_id)))
+                        let sent = self
+                            .api
+                            .send(msg.text_reply(format!("Issue {} created", issue_id)))
+                            .await?;
+                        self.remember_issue_message(&sent, issue_id)?;
+                        if let Err(e) = self.show_issue_card(yt, msg, issue_id).await {
+                            warn!("Unable to show issue card: {}", e);
+                        }
                     } else {
                         if let Ok(err) = serde_json::from_value::<YoutrackError>(json.unwrap()) {
                             // TODO: wrap into YoutrackError kind
@@ -656,7 +5097,124 @@ impl Bot {
                 }
             }
             BotCommand::Cancel(msg) => {
-                self.api.spawn(msg.from.text("Issue discarded"));
+                self.api.spawn(msg.text_reply("Issue discarded"));
+                UserStateMessages::Cancel(Cancel {})
+            }
+            _ => UserStateMessages::Noop(Noop {}),
+        };
+        Ok(res)
+    }
+
+    async fn handle_command_composing_command(
+        &mut self,
+        state: &ComposingCommand,
+        cmd: BotCommand,
+    ) -> Result<UserStateMessages> {
+        let user = cmd.get_user().id;
+        let query = cmd.get_message_text();
+        let res = match &cmd {
+            // Text replying to the composer replaces the command being composed
+            BotCommand::Text(msg) => {
+                let query = query.unwrap_or_default().trim().to_string();
+                match self.command_assist(user, msg, &state.issue, &query).await? {
+                    Some(assist) => {
+                        self.show_command(msg, &state.target(), &query, &assist, false)
+                            .await?;
+                        UserStateMessages::CommandQuery(CommandQuery(query))
+                    }
+                    None => UserStateMessages::Noop(Noop {}),
+                }
+            }
+            BotCommand::CommandSuggestion(cb, p) => {
+                let msg = cb.message.clone().unwrap();
+                // Suggestions aren't stored, but are the same for the same query
+                let suggestion = self
+                    .command_assist(user, &msg, &state.issue, &state.query)
+                    .await?
+                    .and_then(|assist| assist.suggestions.get(p.index).cloned());
+                let query = match suggestion {
+                    Some(suggestion) => suggestion.complete(&state.query),
+                    None => return Ok(UserStateMessages::Noop(Noop {})),
+                };
+                match self
+                    .command_assist(user, &msg, &state.issue, &query)
+                    .await?
+                {
+                    Some(assist) => {
+                        self.show_command(&msg, &state.target(), &query, &assist, true)
+                            .await?;
+                        UserStateMessages::CommandQuery(CommandQuery(query))
+                    }
+                    None => UserStateMessages::Noop(Noop {}),
+                }
+            }
+            BotCommand::CommandApply(cb) => {
+                let msg = cb.message.clone().unwrap();
+                let applied = if state.selection.is_empty() {
+                    self.apply_command(user, &msg, &state.issue, &state.query)
+                        .await?
+                } else {
+                    self.apply_bulk_command(user, &msg, &state.selection, &state.query)
+                        .await?
+                };
+                if applied {
+                    self.api
+                        .spawn(msg.edit_reply_markup(Some(reply_markup!(inline_keyboard, []))));
+                    UserStateMessages::StopCommand(StopCommand {})
+                } else {
+                    UserStateMessages::Noop(Noop {})
+                }
+            }
+            BotCommand::CommandStop(cb) => {
+                let msg = cb.message.clone().unwrap();
+                self.api
+                    .spawn(msg.edit_reply_markup(Some(reply_markup!(inline_keyboard, []))));
+                UserStateMessages::StopCommand(StopCommand {})
+            }
+            BotCommand::Cancel(msg) => {
+                self.api.spawn(msg.text_reply("Command discarded"));
+                UserStateMessages::Cancel(Cancel {})
+            }
+            _ => UserStateMessages::Noop(Noop {}),
+        };
+        Ok(res)
+    }
+
+    async fn handle_command_marking_duplicate(
+        &mut self,
+        state: &MarkingDuplicate,
+        cmd: BotCommand,
+    ) -> Result<UserStateMessages> {
+        let text = cmd.get_message_text();
+        let res = match &cmd {
+            BotCommand::Text(msg) => {
+                let original = match text.as_ref().and_then(|t| t.split_whitespace().next()) {
+                    Some(original) => original.to_string(),
+                    None => return Ok(UserStateMessages::Noop(Noop {})),
+                };
+                let query = format!("duplicates {}", original);
+                if !self
+                    .apply_command(msg.from.id, msg, &state.issue, &query)
+                    .await?
+                {
+                    return Ok(UserStateMessages::Noop(Noop {}));
+                }
+                let mut con = self.redis.get_connection()?;
+                let key = triage::session_key(i64::from(msg.chat.id()), state.triage);
+                let session: Option<TriageSession> = con.get(key)?;
+                if let (Some(mut session), Some(yt)) =
+                    (session, self.get_youtrack(msg.from.id).await)
+                {
+                    if session.is_current(&state.issue) {
+                        session.advance();
+                        self.show_triage(yt, &mut session).await?;
+                    }
+                }
+                UserStateMessages::DuplicateDone(DuplicateDone {})
+            }
+            BotCommand::Cancel(msg) => {
+                self.api
+                    .spawn(msg.text_reply("Marking as duplicate is cancelled"));
                 UserStateMessages::Cancel(Cancel {})
             }
             _ => UserStateMessages::Noop(Noop {}),
@@ -669,18 +5227,61 @@ impl Bot {
     }
 
     async fn handle_command(&mut self, state: UserState, cmd: BotCommand) -> Result<UserState> {
-        let state_cmd = match_user_state!(
-            UserState,
-            state,
-            Idle,
-            InBacklog,
-            NewIssue,
-            NewIssueSummary,
-            NewIssueSummaryProject,
-            NewIssueSummaryProjectStream,
-            NewIssueSummaryProjectStreamType,
-            NewIssueSummaryProjectStreamTypeDesc
-        );
+        if let (Some(feature), Some(msg)) = (cmd.feature(), cmd.get_message()) {
+            if !self
+                .get_chat_config(i64::from(msg.chat.id()))?
+                .is_enabled(feature)
+            {
+                self.api.spawn(msg.text_reply(format!(
+                    "{} commands are turned off in this chat",
+                    feature.name()
+                )));
+                return Ok(state);
+            }
+        }
+        // Other text and commands end the command composer and are handled as usual
+        let state = match state {
+            UserState::ComposingCommand(_) if !self.is_command_reply(&cmd)? => {
+                if let Some(msg) = cmd.get_message() {
+                    self.api.spawn(
+                        msg.chat
+                            .text("Command discarded, reply to the command message to edit it"),
+                    );
+                }
+                UserState::idle()
+            }
+            state => state,
+        };
+        let state_cmd = if cmd.is_backlog_action() {
+            self.handle_backlog_action(cmd).await?
+        } else if cmd.is_issue_card_action() {
+            self.handle_issue_card_action(cmd).await?
+        } else if cmd.is_settings_action() {
+            self.handle_settings_action(cmd).await?
+        } else if cmd.is_board_action() {
+            self.handle_board_action(cmd).await?
+        } else if cmd.is_estimation_action() {
+            self.handle_estimation_action(cmd).await?
+        } else if cmd.is_triage_action() {
+            self.handle_triage_action(cmd).await?
+        } else {
+            match_user_state!(
+                UserState,
+                state,
+                Idle,
+                NewIssue,
+                NewIssueSummary,
+                NewIssueSummaryProject,
+                NewIssueSummaryProjectStream,
+                NewIssueSummaryProjectStreamType,
+                NewIssueSummaryProjectStreamTypeAssignee,
+                NewIssueSummaryProjectStreamTypeAssigneeTags,
+                NewIssueSummaryProjectStreamTypeAssigneeTagsDesc,
+                ComposingCommand,
+                AnsweringStandup,
+                MarkingDuplicate
+            )
+        };
         let new_state = state.execute(state_cmd);
         if let UserState::Error = new_state {
             bail!("Invalid transition")
@@ -690,16 +5291,24 @@ impl Bot {
 
     pub async fn dispatch_update(&mut self, update: Update) -> Result<()> {
         debug!("Got update: {:?}", update);
-        let (uid, state) = self.get_state_by_update(&update)?;
-        debug!("UID: {}, STATE: {:?}", uid, state);
+        match &update.kind {
+            UpdateKind::PollAnswer(answer) => return self.handle_poll_answer(answer),
+            // Closed by /prioritize stop or in Telegram, the result is posted either way
+            UpdateKind::Poll(poll) if poll.is_closed => {
+                return self.finish_prioritization(&poll.id, false).await
+            }
+            UpdateKind::Poll(_) => return Ok(()),
+            _ => {}
+        }
+        if self.is_for_other_bot(&update).await? {
+            return Ok(());
+        }
+        let (chat, uid, state) = self.get_state_by_update(&update)?;
+        debug!("CHAT: {}, UID: {}, STATE: {:?}", chat, uid, state);
         let command: BotCommand = update.try_into()?;
 
         match self.handle_command(state, command).await {
-            Ok(new_state) => {
-                let mut con = self.redis.get_connection()?;
-                let key = format!("state:{}", uid);
-                con.set(key, new_state)?;
-            }
+            Ok(new_state) => self.save_state(chat, uid, new_state)?,
             Err(e) => {
                 warn!("Could not handle command: {}", e);
             }
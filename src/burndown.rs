@@ -0,0 +1,259 @@
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde_json::Value;
+
+use super::models::{Activity, SprintIssue};
+
+/// Minutes of a period field value, as returned in custom fields and activities
+pub fn period_minutes(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) => n.as_i64(),
+        Value::Object(_) => value["minutes"].as_i64(),
+        Value::Array(values) => values.iter().filter_map(period_minutes).next(),
+        _ => None,
+    }
+}
+
+/// Issue weight in a sprint: estimation in minutes, or one when the board has no estimation
+pub fn issue_weight(issue: &SprintIssue, field: Option<&str>) -> i64 {
+    match field {
+        Some(field) => issue
+            .custom_fields
+            .iter()
+            .find(|f| f.name == field)
+            .and_then(|f| period_minutes(&f.value))
+            .unwrap_or(0),
+        None => 1,
+    }
+}
+
+// Value in effect at the time, changes are sorted and the first one is at i64::MIN
+fn value_at<T: Copy>(changes: &[(i64, T)], t: i64) -> T {
+    changes
+        .iter()
+        .take_while(|(at, _)| *at <= t)
+        .last()
+        .map(|(_, v)| *v)
+        .unwrap_or(changes[0].1)
+}
+
+/// Estimation and resolution of an issue over time, rebuilt from its activities.
+/// Issues count for the whole sprint even when added to it later.
+pub struct IssueTimeline {
+    estimation: Vec<(i64, i64)>,
+    resolved: Vec<(i64, bool)>,
+}
+
+impl IssueTimeline {
+    /// `activities` are oldest first
+    pub fn new(issue: &SprintIssue, field: Option<&str>, activities: &[Activity]) -> Self {
+        let mut estimation = Vec::new();
+        let changes: Vec<&Activity> = match field {
+            Some(field) => activities
+                .iter()
+                .filter(|a| a.category.id == "CustomFieldCategory" && a.field_name() == Some(field))
+                .collect(),
+            None => Vec::new(),
+        };
+        match changes.first() {
+            Some(first) => estimation.push((i64::MIN, period_minutes(&first.removed).unwrap_or(0))),
+            None => estimation.push((i64::MIN, issue_weight(issue, field))),
+        }
+        estimation.extend(
+            changes
+                .iter()
+                .map(|a| (a.timestamp, period_minutes(&a.added).unwrap_or(0))),
+        );
+
+        let mut resolved = Vec::new();
+        let changes: Vec<&Activity> = activities
+            .iter()
+            .filter(|a| a.category.id == "IssueResolvedCategory")
+            .collect();
+        match changes.first() {
+            // Reopening comes first when the issue was resolved from the start
+            Some(first) => resolved.push((i64::MIN, first.added.is_null())),
+            None => {
+                resolved.push((i64::MIN, false));
+                if let Some(at) = issue.resolved {
+                    resolved.push((at, true));
+                }
+            }
+        }
+        resolved.extend(changes.iter().map(|a| (a.timestamp, !a.added.is_null())));
+
+        IssueTimeline {
+            estimation,
+            resolved,
+        }
+    }
+
+    pub fn remaining_at(&self, t: i64) -> i64 {
+        if value_at(&self.resolved, t) {
+            0
+        } else {
+            value_at(&self.estimation, t)
+        }
+    }
+}
+
+/// Remaining work at the sprint start and at the end of each elapsed day
+pub struct Burndown {
+    pub start: NaiveDate,
+    pub finish: NaiveDate,
+    pub remaining: Vec<i64>,
+}
+
+impl Burndown {
+    pub fn new(
+        timelines: &[IssueTimeline],
+        tz: Tz,
+        start: i64,
+        finish: i64,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let remaining_at = |t: i64| timelines.iter().map(|i| i.remaining_at(t)).sum::<i64>();
+        let start_date = tz.timestamp_millis(start).date().naive_local();
+        let finish_date = tz.timestamp_millis(finish).date().naive_local();
+        let now = now.timestamp_millis();
+
+        let mut remaining = vec![remaining_at(start)];
+        let mut date = start_date;
+        while date <= finish_date {
+            let day_end = tz
+                .from_local_datetime(&date.succ().and_hms(0, 0, 0))
+                .earliest()
+                .map(|t| t.timestamp_millis())
+                .unwrap_or(now);
+            remaining.push(remaining_at(day_end.min(now)));
+            if day_end >= now {
+                break;
+            }
+            date = date.succ();
+        }
+        Burndown {
+            start: start_date,
+            finish: finish_date,
+            remaining,
+        }
+    }
+
+    /// Sprint length in days, at least one for sprints finishing before they start
+    pub fn days(&self) -> usize {
+        (self.finish - self.start).num_days().max(0) as usize + 1
+    }
+
+    pub fn total(&self) -> i64 {
+        self.remaining[0]
+    }
+
+    /// Where the ideal line is after the elapsed days
+    pub fn ideal(&self) -> i64 {
+        let elapsed = (self.remaining.len() - 1).min(self.days());
+        self.total() - self.total() * elapsed as i64 / self.days() as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const ESTIMATION: Option<&str> = Some("Estimation");
+
+    fn issue(estimation: Option<i64>, resolved: Option<i64>) -> SprintIssue {
+        let fields = match estimation {
+            Some(minutes) => {
+                json!([{"id": "1", "name": "Estimation", "value": {"minutes": minutes}}])
+            }
+            None => json!([]),
+        };
+        serde_json::from_value(json!({
+            "idReadable": "TP-1",
+            "summary": "Issue",
+            "customFields": fields,
+            "resolved": resolved,
+        }))
+        .unwrap()
+    }
+
+    fn activity(timestamp: i64, category: &str, added: Value, removed: Value) -> Activity {
+        serde_json::from_value(json!({
+            "id": timestamp.to_string(),
+            "timestamp": timestamp,
+            "author": {"id": "1", "login": "root"},
+            "category": {"id": category},
+            "field": {"name": "Estimation"},
+            "added": added,
+            "removed": removed,
+        }))
+        .unwrap()
+    }
+
+    fn ms(day: u32, hour: u32) -> i64 {
+        Utc.ymd(2020, 6, day).and_hms(hour, 0, 0).timestamp_millis()
+    }
+
+    #[test]
+    fn weighs_issues_by_estimation() {
+        assert_eq!(issue_weight(&issue(Some(90), None), ESTIMATION), 90);
+        assert_eq!(issue_weight(&issue(None, None), ESTIMATION), 0);
+        assert_eq!(issue_weight(&issue(Some(90), None), None), 1);
+    }
+
+    #[test]
+    fn timeline_follows_estimation_changes() {
+        let changes = vec![activity(
+            1000,
+            "CustomFieldCategory",
+            json!({"minutes": 120}),
+            json!({"minutes": 240}),
+        )];
+        let timeline = IssueTimeline::new(&issue(Some(120), None), ESTIMATION, &changes);
+        assert_eq!(timeline.remaining_at(0), 240);
+        assert_eq!(timeline.remaining_at(1000), 120);
+        assert_eq!(timeline.remaining_at(5000), 120);
+    }
+
+    #[test]
+    fn timeline_follows_resolution() {
+        let changes = vec![
+            activity(1000, "IssueResolvedCategory", json!(1000), Value::Null),
+            activity(2000, "IssueResolvedCategory", Value::Null, json!(1000)),
+        ];
+        let timeline = IssueTimeline::new(&issue(Some(60), None), ESTIMATION, &changes);
+        assert_eq!(timeline.remaining_at(500), 60);
+        assert_eq!(timeline.remaining_at(1500), 0);
+        assert_eq!(timeline.remaining_at(2500), 60);
+    }
+
+    #[test]
+    fn resolved_issues_without_history_are_done_from_resolution() {
+        let timeline = IssueTimeline::new(&issue(Some(60), Some(3000)), ESTIMATION, &[]);
+        assert_eq!(timeline.remaining_at(2999), 60);
+        assert_eq!(timeline.remaining_at(3000), 0);
+    }
+
+    #[test]
+    fn burndown_has_a_value_per_elapsed_day() {
+        let timelines = vec![
+            IssueTimeline::new(&issue(None, Some(ms(1, 10))), None, &[]),
+            IssueTimeline::new(&issue(None, Some(ms(2, 10))), None, &[]),
+            IssueTimeline::new(&issue(None, None), None, &[]),
+        ];
+        let now = Utc.ymd(2020, 6, 2).and_hms(12, 0, 0);
+        let burndown = Burndown::new(&timelines, Tz::UTC, ms(1, 0), ms(3, 12), now);
+        assert_eq!(burndown.days(), 3);
+        assert_eq!(burndown.remaining, vec![3, 2, 1]);
+        assert_eq!(burndown.total(), 3);
+        assert_eq!(burndown.ideal(), 1);
+    }
+
+    #[test]
+    fn sprint_finishing_before_start_lasts_a_day() {
+        let now = Utc.ymd(2020, 6, 10).and_hms(0, 0, 0);
+        let burndown = Burndown::new(&[], Tz::UTC, ms(3, 0), ms(1, 0), now);
+        assert_eq!(burndown.days(), 1);
+        assert_eq!(burndown.ideal(), 0);
+    }
+}
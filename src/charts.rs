@@ -0,0 +1,144 @@
+use image::png::PngEncoder;
+use image::ColorType;
+use plotters::prelude::*;
+
+use super::errors::*;
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 480;
+
+// Text needs system fonts the container doesn't have, so charts carry ticks and
+// gridlines only and the numbers go to the photo caption instead
+
+/// Horizontal gridlines, each row is a whole number of units, see `top`
+pub const GRID_ROWS: usize = 4;
+
+/// Upper bound of the value axis with some headroom, a multiple of GRID_ROWS
+pub fn top(values: &[f64]) -> f64 {
+    let max = values.iter().cloned().fold(1.0, f64::max) * 1.1;
+    (max / GRID_ROWS as f64).ceil() * GRID_ROWS as f64
+}
+
+/// Axes with a tick per column and per grid row
+fn axes(columns: usize, top: f64) -> Vec<Vec<(f64, f64)>> {
+    let right = columns as f64;
+    let (tick_x, tick_y) = (right / 100.0, top / 40.0);
+    let mut lines = vec![vec![(0.0, 0.0), (right, 0.0)], vec![(0.0, 0.0), (0.0, top)]];
+    lines.extend((0..=columns).map(|c| vec![(c as f64, 0.0), (c as f64, tick_y)]));
+    lines.extend((1..=GRID_ROWS).map(|r| {
+        let y = top * r as f64 / GRID_ROWS as f64;
+        vec![(0.0, y), (tick_x, y)]
+    }));
+    lines
+}
+
+fn chart_error<E: std::fmt::Debug>(e: E) -> Error {
+    format!("Unable to draw chart: {:?}", e).into()
+}
+
+fn encode_png(buf: &[u8]) -> Result<Vec<u8>> {
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png)
+        .encode(buf, WIDTH, HEIGHT, ColorType::Rgb8)
+        .map_err(chart_error)?;
+    Ok(png)
+}
+
+/// Burndown of `remaining` values, one per elapsed day and one for the start,
+/// against the ideal line from the first value to zero in `days`
+pub fn burndown(days: usize, remaining: &[f64]) -> Result<Vec<u8>> {
+    let mut buf = vec![0u8; (WIDTH * HEIGHT * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut buf, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&WHITE).map_err(chart_error)?;
+        let total = remaining.first().cloned().unwrap_or(0.0);
+        let top = top(remaining);
+        let columns = days.max(1);
+        let days = columns as f64;
+        let mut chart = ChartBuilder::on(&root)
+            .margin(20)
+            .build_ranged(0f64..days, 0f64..top)
+            .map_err(chart_error)?;
+
+        let mut grid: Vec<Vec<(f64, f64)>> = (0..=columns)
+            .map(|d| vec![(d as f64, 0.0), (d as f64, top)])
+            .collect();
+        grid.extend((1..GRID_ROWS).map(|r| {
+            let y = top * r as f64 / GRID_ROWS as f64;
+            vec![(0.0, y), (days, y)]
+        }));
+        for line in grid {
+            chart
+                .draw_series(LineSeries::new(line, &BLACK.mix(0.1)))
+                .map_err(chart_error)?;
+        }
+        for line in axes(columns, top) {
+            chart
+                .draw_series(LineSeries::new(line, &BLACK))
+                .map_err(chart_error)?;
+        }
+
+        chart
+            .draw_series(LineSeries::new(
+                vec![(0.0, total), (days, 0.0)],
+                BLUE.mix(0.5).stroke_width(2),
+            ))
+            .map_err(chart_error)?;
+        let actual: Vec<(f64, f64)> = remaining
+            .iter()
+            .enumerate()
+            .map(|(d, v)| (d as f64, *v))
+            .collect();
+        chart
+            .draw_series(LineSeries::new(actual.clone(), RED.stroke_width(3)))
+            .map_err(chart_error)?;
+        chart
+            .draw_series(actual.into_iter().map(|p| Circle::new(p, 4, RED.filled())))
+            .map_err(chart_error)?;
+    }
+    encode_png(&buf)
+}
+
+/// Bar per sprint, oldest first, with the average as a line
+pub fn velocity(values: &[f64]) -> Result<Vec<u8>> {
+    let mut buf = vec![0u8; (WIDTH * HEIGHT * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut buf, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&WHITE).map_err(chart_error)?;
+        let top = top(values);
+        let columns = values.len().max(1);
+        let count = columns as f64;
+        let mut chart = ChartBuilder::on(&root)
+            .margin(20)
+            .build_ranged(0f64..count, 0f64..top)
+            .map_err(chart_error)?;
+
+        for r in 1..GRID_ROWS {
+            let y = top * r as f64 / GRID_ROWS as f64;
+            chart
+                .draw_series(LineSeries::new(vec![(0.0, y), (count, y)], &BLACK.mix(0.1)))
+                .map_err(chart_error)?;
+        }
+        chart
+            .draw_series(values.iter().enumerate().map(|(i, v)| {
+                Rectangle::new(
+                    [(i as f64 + 0.15, 0.0), (i as f64 + 0.85, *v)],
+                    GREEN.filled(),
+                )
+            }))
+            .map_err(chart_error)?;
+        let average = values.iter().sum::<f64>() / count;
+        chart
+            .draw_series(LineSeries::new(
+                vec![(0.0, average), (count, average)],
+                BLUE.mix(0.5).stroke_width(2),
+            ))
+            .map_err(chart_error)?;
+        for line in axes(columns, top) {
+            chart
+                .draw_series(LineSeries::new(line, &BLACK))
+                .map_err(chart_error)?;
+        }
+    }
+    encode_png(&buf)
+}
@@ -0,0 +1,72 @@
+use super::states::{ChatConfig, Feature, Language};
+
+impl Feature {
+    pub fn all() -> Vec<Feature> {
+        vec![
+            Feature::Backlog,
+            Feature::Issues,
+            Feature::TimeTracking,
+            Feature::Watches,
+            Feature::Boards,
+            Feature::Standups,
+            Feature::Estimation,
+        ]
+    }
+
+    /// Name used by /config enable and /config disable
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Backlog => "backlog",
+            Feature::Issues => "issues",
+            Feature::TimeTracking => "time",
+            Feature::Watches => "watches",
+            Feature::Boards => "boards",
+            Feature::Standups => "standups",
+            Feature::Estimation => "estimation",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Feature> {
+        Feature::all()
+            .into_iter()
+            .find(|f| f.name() == name.to_lowercase())
+    }
+}
+
+impl Language {
+    pub fn parse(name: &str) -> Option<Language> {
+        match name.to_lowercase().as_str() {
+            "ru" => Some(Language::Ru),
+            "en" => Some(Language::En),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Ru => "ru",
+            Language::En => "en",
+        }
+    }
+
+    /// Translated template name, Russian ones are at the top of templates/
+    pub fn template(self, name: &str) -> String {
+        match self {
+            Language::Ru => name.to_string(),
+            Language::En => format!("en/{}", name),
+        }
+    }
+}
+
+impl ChatConfig {
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        !self.disabled.contains(&feature)
+    }
+
+    pub fn set_enabled(&mut self, feature: Feature, enabled: bool) {
+        self.disabled.retain(|f| *f != feature);
+        if !enabled {
+            self.disabled.push(feature);
+        }
+    }
+}
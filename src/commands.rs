@@ -1,3 +1,4 @@
+use chrono::{NaiveTime, Weekday};
 use serde::{Deserialize, Serialize};
 use std::convert::{From, TryFrom};
 use telegram_bot::types::{
@@ -5,6 +6,13 @@ use telegram_bot::types::{
 };
 
 use crate::errors::*;
+use crate::prioritization::{DEFAULT_OPTIONS, MAX_OPTIONS, MIN_OPTIONS};
+use crate::states::{
+    BulkAction, DigestPeriod, EstimationScale, Feature, Language, NotificationCategory, QuietHours,
+    TriageField,
+};
+
+pub const BACKLOG_PAGE_SIZE: i32 = 5;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename = "bp")]
@@ -50,6 +58,510 @@ pub struct VoteForIssueParams {
     pub id: String,
     #[serde(rename = "v")]
     pub has_vote: bool,
+    // Backlog page to refresh after voting
+    #[serde(rename = "s", default)]
+    pub skip: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SelectIssueParams {
+    #[serde(rename = "i")]
+    pub id: String,
+    #[serde(rename = "c")]
+    pub selected: bool,
+    // Backlog page to refresh after toggling
+    #[serde(rename = "s")]
+    pub skip: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BulkCommandParams {
+    #[serde(rename = "a")]
+    pub action: BulkAction,
+    #[serde(rename = "s")]
+    pub skip: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "tp")]
+pub struct IssueTagParams {
+    #[serde(rename = "i")]
+    pub id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "ip")]
+pub struct IssueParams {
+    #[serde(rename = "i")]
+    pub id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "sp")]
+pub struct IssueStateParams {
+    #[serde(rename = "i")]
+    pub issue: String,
+    #[serde(rename = "s")]
+    pub state: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "hp")]
+pub struct HistoryParams {
+    #[serde(rename = "i")]
+    pub issue: String,
+    #[serde(rename = "p")]
+    pub page: i32,
+}
+
+impl HistoryParams {
+    pub fn new(issue: String) -> Self {
+        Self { issue, page: 0 }
+    }
+
+    pub fn next(&self) -> Self {
+        Self {
+            issue: self.issue.clone(),
+            page: self.page + 1,
+        }
+    }
+
+    pub fn prev(&self) -> Option<Self> {
+        if self.page > 0 {
+            Some(Self {
+                issue: self.issue.clone(),
+                page: self.page - 1,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommentParams {
+    pub issue: String,
+    pub text: String,
+}
+
+impl CommentParams {
+    /// Parses `TP-123 comment text`
+    pub fn parse(args: &str) -> Option<Self> {
+        let mut parts = args.splitn(2, char::is_whitespace);
+        let issue = parts.next().unwrap_or("").trim();
+        let text = parts.next().unwrap_or("").trim();
+        if issue.is_empty() || text.is_empty() {
+            None
+        } else {
+            Some(Self {
+                issue: issue.to_string(),
+                text: text.to_string(),
+            })
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IssueCommandParams {
+    pub issue: String,
+    pub query: String,
+}
+
+impl IssueCommandParams {
+    /// Parses `TP-123 command`, command may be empty
+    pub fn parse(args: &str) -> Option<Self> {
+        let mut parts = args.splitn(2, char::is_whitespace);
+        let issue = parts.next().unwrap_or("").trim();
+        let query = parts.next().unwrap_or("").trim();
+        if issue.is_empty() {
+            None
+        } else {
+            Some(Self {
+                issue: issue.to_string(),
+                query: query.to_string(),
+            })
+        }
+    }
+}
+
+/// Parses durations like `1h30m`, `2h` or `45m`, bare numbers are minutes
+pub fn parse_duration(s: &str) -> Option<u64> {
+    if let Ok(minutes) = s.parse::<u64>() {
+        return Some(minutes).filter(|m| *m > 0);
+    }
+    let mut minutes = 0;
+    let mut number = String::new();
+    for c in s.to_lowercase().chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'h' | 'm' if !number.is_empty() => {
+                let n: u64 = number.parse().ok()?;
+                let n = if c == 'h' { n.checked_mul(60)? } else { n };
+                minutes = minutes.checked_add(n)?;
+                number.clear();
+            }
+            _ => return None,
+        }
+    }
+    if number.is_empty() && minutes > 0 {
+        Some(minutes)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpentParams {
+    pub issue: String,
+    pub minutes: u64,
+    pub comment: Option<String>,
+    pub work_type: Option<String>,
+}
+
+impl SpentParams {
+    /// Parses `TP-123 1h30m optional comment [work type]`
+    pub fn parse(args: &str) -> Option<Self> {
+        let mut parts = args.splitn(3, char::is_whitespace);
+        let issue = parts.next().unwrap_or("").trim();
+        let minutes = parse_duration(parts.next().unwrap_or("").trim())?;
+        let mut rest = parts.next().unwrap_or("").trim();
+        if issue.is_empty() {
+            return None;
+        }
+        let mut work_type = None;
+        if rest.ends_with(']') {
+            if let Some(pos) = rest.rfind('[') {
+                work_type = Some(rest[pos + 1..rest.len() - 1].trim().to_string());
+                rest = rest[..pos].trim();
+            }
+        }
+        Some(Self {
+            issue: issue.to_string(),
+            minutes,
+            comment: Some(rest.to_string()).filter(|c| !c.is_empty()),
+            work_type: work_type.filter(|t| !t.is_empty()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimesheetPeriod {
+    Week,
+    Month,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimesheetAuthor {
+    Me,
+    User(String),
+    Team,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimesheetParams {
+    pub period: TimesheetPeriod,
+    pub author: TimesheetAuthor,
+    pub csv: bool,
+}
+
+impl TimesheetParams {
+    /// Parses `[week|month] [@login|@all] [csv]` in any order
+    pub fn parse(args: &str) -> Option<Self> {
+        let mut params = Self {
+            period: TimesheetPeriod::Week,
+            author: TimesheetAuthor::Me,
+            csv: false,
+        };
+        for arg in args.split_whitespace() {
+            match arg {
+                "week" => params.period = TimesheetPeriod::Week,
+                "month" => params.period = TimesheetPeriod::Month,
+                "csv" => params.csv = true,
+                "@all" => params.author = TimesheetAuthor::Team,
+                "@me" => params.author = TimesheetAuthor::Me,
+                _ if arg.starts_with('@') && arg.len() > 1 => {
+                    params.author = TimesheetAuthor::User(arg[1..].to_string())
+                }
+                _ => return None,
+            }
+        }
+        Some(params)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DigestParams {
+    List,
+    Add(DigestPeriod, NaiveTime, String),
+    Remove(u32),
+}
+
+impl DigestParams {
+    /// Parses `daily 09:30 query`, `weekly mon 09:30 query` or `stop 1`
+    pub fn parse(args: &str) -> Option<Self> {
+        let mut parts = args.split_whitespace();
+        let period = match parts.next() {
+            None => return Some(DigestParams::List),
+            Some("stop") => return parts.next()?.parse().ok().map(DigestParams::Remove),
+            Some("daily") => DigestPeriod::Daily,
+            Some("weekly") => DigestPeriod::Weekly(parts.next()?.parse::<Weekday>().ok()?),
+            Some(_) => return None,
+        };
+        let time = NaiveTime::parse_from_str(parts.next()?, "%H:%M").ok()?;
+        let query: Vec<&str> = parts.collect();
+        if query.is_empty() {
+            None
+        } else {
+            Some(DigestParams::Add(period, time, query.join(" ")))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StandupParams {
+    Show,
+    Schedule(NaiveTime),
+    Off,
+    Join,
+    Leave,
+    Comments(bool),
+    Answer,
+}
+
+impl StandupParams {
+    /// Parses `/standup 10:00`, `/standup join`, `/standup comments on` and the like
+    pub fn parse(args: &str) -> Option<StandupParams> {
+        let mut parts = args.split_whitespace();
+        let params = match parts.next() {
+            None => StandupParams::Show,
+            Some("off") => StandupParams::Off,
+            Some("join") => StandupParams::Join,
+            Some("leave") => StandupParams::Leave,
+            Some("answer") => StandupParams::Answer,
+            Some("comments") => match parts.next()? {
+                "on" => StandupParams::Comments(true),
+                "off" => StandupParams::Comments(false),
+                _ => return None,
+            },
+            Some(time) => StandupParams::Schedule(NaiveTime::parse_from_str(time, "%H:%M").ok()?),
+        };
+        Some(params)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingsParams {
+    Menu,
+    Quiet(Option<QuietHours>),
+}
+
+impl SettingsParams {
+    /// Parses `/settings`, `/settings quiet 22:00-08:00` and `/settings quiet off`
+    pub fn parse(args: &str) -> Option<SettingsParams> {
+        let mut parts = args.split_whitespace();
+        match parts.next() {
+            None => Some(SettingsParams::Menu),
+            Some("quiet") => match parts.next()? {
+                "off" => Some(SettingsParams::Quiet(None)),
+                range => QuietHours::parse(range).map(|q| SettingsParams::Quiet(Some(q))),
+            },
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigParams {
+    Show,
+    /// None resets to the global value
+    BacklogQuery(Option<String>),
+    Project(Option<String>),
+    /// Field name and value, none removes the default
+    Field(String, Option<String>),
+    Language(Language),
+    Feature(Feature, bool),
+    EstimationScale(EstimationScale),
+}
+
+impl ConfigParams {
+    /// Parses `/config`, `/config backlog <query>`, `/config project <name>`,
+    /// `/config field <name> <value>`, `/config language en`, `/config enable|disable <feature>`
+    /// and `/config scale fibonacci|tshirt`
+    pub fn parse(args: &str) -> Option<ConfigParams> {
+        let mut parts = args.splitn(2, char::is_whitespace);
+        let value =
+            |rest: Option<&str>| rest.map(|r| r.trim().to_string()).filter(|r| !r.is_empty());
+        match parts.next() {
+            None | Some("") => Some(ConfigParams::Show),
+            Some("backlog") => Some(ConfigParams::BacklogQuery(value(parts.next()))),
+            Some("project") => Some(ConfigParams::Project(value(parts.next()))),
+            Some("field") => {
+                let mut field = parts.next()?.trim().splitn(2, char::is_whitespace);
+                let name = field.next().filter(|n| !n.is_empty())?.to_string();
+                Some(ConfigParams::Field(name, value(field.next())))
+            }
+            Some("language") => Language::parse(parts.next()?.trim()).map(ConfigParams::Language),
+            Some("scale") => {
+                EstimationScale::parse(parts.next()?.trim()).map(ConfigParams::EstimationScale)
+            }
+            Some("enable") => {
+                Feature::parse(parts.next()?.trim()).map(|f| ConfigParams::Feature(f, true))
+            }
+            Some("disable") => {
+                Feature::parse(parts.next()?.trim()).map(|f| ConfigParams::Feature(f, false))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrioritizeParams {
+    /// None is the chat backlog query
+    Start(Option<String>, usize),
+    Stop,
+}
+
+impl PrioritizeParams {
+    /// Parses `/prioritize [query] [n]` and `/prioritize stop`
+    pub fn parse(args: &str) -> Option<PrioritizeParams> {
+        if args == "stop" {
+            return Some(PrioritizeParams::Stop);
+        }
+        let mut words: Vec<&str> = args.split_whitespace().collect();
+        let count = match words.last().and_then(|w| w.parse::<usize>().ok()) {
+            Some(count) => {
+                words.pop();
+                count
+            }
+            None => DEFAULT_OPTIONS,
+        };
+        if count < MIN_OPTIONS || count > MAX_OPTIONS {
+            return None;
+        }
+        let query = Some(words.join(" ")).filter(|q| !q.is_empty());
+        Some(PrioritizeParams::Start(query, count))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EstimateParams {
+    pub issue: String,
+    /// Overrides the chat scale
+    pub scale: Option<EstimationScale>,
+}
+
+impl EstimateParams {
+    /// Parses `/estimate TP-123 [fibonacci|tshirt]`
+    pub fn parse(args: &str) -> Option<EstimateParams> {
+        let mut parts = args.split_whitespace();
+        let issue = parts.next()?.to_string();
+        let scale = match parts.next() {
+            Some(scale) => Some(EstimationScale::parse(scale)?),
+            None => None,
+        };
+        Some(EstimateParams { issue, scale })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EstimationParams {
+    #[serde(rename = "i")]
+    pub issue: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EstimationVoteParams {
+    #[serde(rename = "i")]
+    pub issue: String,
+    /// Index in the scale values
+    #[serde(rename = "v")]
+    pub value: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TriageParams {
+    #[serde(rename = "i")]
+    pub issue: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TriageFieldParams {
+    #[serde(rename = "i")]
+    pub issue: String,
+    #[serde(rename = "f")]
+    pub field: TriageField,
+    #[serde(rename = "p")]
+    pub page: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TriageValueParams {
+    #[serde(rename = "i")]
+    pub issue: String,
+    #[serde(rename = "f")]
+    pub field: TriageField,
+    /// Index in the field values
+    #[serde(rename = "v")]
+    pub value: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PollParams {
+    #[serde(rename = "p")]
+    pub poll: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoardParams {
+    #[serde(rename = "a")]
+    pub agile: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoardColumnParams {
+    #[serde(rename = "a")]
+    pub agile: String,
+    #[serde(rename = "c")]
+    pub column: usize,
+    #[serde(rename = "s")]
+    pub skip: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotificationCategoryParams {
+    #[serde(rename = "c")]
+    pub category: NotificationCategory,
+}
+
+/// New issue wizard step a picker keyboard belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PickerStep {
+    #[serde(rename = "t")]
+    Template,
+    #[serde(rename = "p")]
+    Project,
+    #[serde(rename = "s")]
+    Stream,
+    #[serde(rename = "y")]
+    Type,
+    #[serde(rename = "a")]
+    Assignee,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "pp")]
+pub struct PickerParams {
+    #[serde(rename = "s")]
+    pub step: PickerStep,
+    #[serde(rename = "i")]
+    pub index: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SuggestionParams {
+    #[serde(rename = "i")]
+    pub index: usize,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -63,6 +575,94 @@ pub enum CallbackParams {
     VoteForIssue(VoteForIssueParams),
     #[serde(rename = "bs")]
     BacklogStop,
+    #[serde(rename = "bm")]
+    BacklogSelect(BacklogParams),
+    #[serde(rename = "bx")]
+    BacklogSelectDone(BacklogParams),
+    #[serde(rename = "si")]
+    SelectIssue(SelectIssueParams),
+    #[serde(rename = "bv")]
+    BulkVote(BacklogParams),
+    #[serde(rename = "bb")]
+    BulkCommand(BulkCommandParams),
+    #[serde(rename = "tt")]
+    ToggleIssueTag(IssueTagParams),
+    #[serde(rename = "td")]
+    IssueTagsDone,
+    #[serde(rename = "ps")]
+    PickerSelect(PickerParams),
+    #[serde(rename = "pp")]
+    PickerPage(PickerParams),
+    #[serde(rename = "am")]
+    AssignToMe,
+    #[serde(rename = "ua")]
+    Unassigned,
+    #[serde(rename = "cs")]
+    CommandSuggestion(SuggestionParams),
+    #[serde(rename = "ca")]
+    CommandApply,
+    #[serde(rename = "cx")]
+    CommandStop,
+    #[serde(rename = "ss")]
+    SetIssueState(IssueStateParams),
+    #[serde(rename = "ai")]
+    AssignIssueToMe(IssueParams),
+    #[serde(rename = "hi")]
+    ShowHistory(IssueParams),
+    #[serde(rename = "hn")]
+    HistoryNext(HistoryParams),
+    #[serde(rename = "hb")]
+    HistoryPrev(HistoryParams),
+    #[serde(rename = "nt")]
+    SettingsToggle(NotificationCategoryParams),
+    #[serde(rename = "nq")]
+    SettingsQuiet,
+    #[serde(rename = "nb")]
+    SettingsBatching,
+    #[serde(rename = "nd")]
+    SettingsDone,
+    #[serde(rename = "sa")]
+    AddToSprint(IssueParams),
+    #[serde(rename = "sn")]
+    MoveToNextSprint(IssueParams),
+    #[serde(rename = "bo")]
+    BoardOverview(BoardParams),
+    #[serde(rename = "bc")]
+    BoardColumn(BoardColumnParams),
+    #[serde(rename = "bd")]
+    BoardPin(BoardParams),
+    #[serde(rename = "pv")]
+    ApplyPriorityVotes(PollParams),
+    #[serde(rename = "ev")]
+    EstimationVote(EstimationVoteParams),
+    #[serde(rename = "er")]
+    EstimationReveal(EstimationParams),
+    #[serde(rename = "ea")]
+    EstimationApply(EstimationVoteParams),
+    #[serde(rename = "tm")]
+    TriageMenu(TriageFieldParams),
+    #[serde(rename = "tv")]
+    TriageSet(TriageValueParams),
+    #[serde(rename = "tb")]
+    TriageBack(TriageParams),
+    #[serde(rename = "tu")]
+    TriageDuplicate(TriageParams),
+    #[serde(rename = "tn")]
+    TriageNeedInfo(TriageParams),
+    #[serde(rename = "tk")]
+    TriageSkip(TriageParams),
+    #[serde(rename = "tx")]
+    TriageStop(TriageParams),
+}
+
+impl CallbackParams {
+    pub fn button<T: ToString>(&self, text: T) -> InlineKeyboardButton {
+        let val = serde_json::to_string(self).unwrap();
+        if val.len() > 64 {
+            panic!("Callback paramater too big: {}", val);
+        }
+        InlineKeyboardButton::callback(text.to_string(), val)
+    }
 }
 
 impl From<CallbackParams> for InlineKeyboardButton {
@@ -78,12 +678,53 @@ impl From<CallbackParams> for InlineKeyboardButton {
                     p.id.clone()
                 }
             }
+            CallbackParams::BacklogSelect(_) => "select".to_string(),
+            CallbackParams::BacklogSelectDone(_) => "done".to_string(),
+            CallbackParams::SelectIssue(p) => {
+                if p.selected {
+                    format!("{} {}", emoji!("white_check_mark"), p.id)
+                } else {
+                    p.id.clone()
+                }
+            }
+            CallbackParams::BulkVote(_) => "vote".to_string(),
+            CallbackParams::BulkCommand(p) => p.action.name().to_string(),
+            CallbackParams::ToggleIssueTag(p) => p.id.clone(),
+            CallbackParams::IssueTagsDone => "done".to_string(),
+            CallbackParams::PickerSelect(p) => p.index.to_string(),
+            CallbackParams::PickerPage(p) => format!("page {}", p.index + 1),
+            CallbackParams::AssignToMe => "assign to me".to_string(),
+            CallbackParams::Unassigned => "unassigned".to_string(),
+            CallbackParams::CommandSuggestion(p) => p.index.to_string(),
+            CallbackParams::CommandApply => "apply".to_string(),
+            CallbackParams::CommandStop => "cancel".to_string(),
+            CallbackParams::SetIssueState(p) => p.state.clone(),
+            CallbackParams::AssignIssueToMe(_) => "assign to me".to_string(),
+            CallbackParams::ShowHistory(_) => "history".to_string(),
+            CallbackParams::HistoryNext(_) => "older".to_string(),
+            CallbackParams::HistoryPrev(_) => "newer".to_string(),
+            CallbackParams::SettingsToggle(p) => p.category.name().to_string(),
+            CallbackParams::SettingsQuiet => "quiet hours".to_string(),
+            CallbackParams::SettingsBatching => "delivery".to_string(),
+            CallbackParams::SettingsDone => "done".to_string(),
+            CallbackParams::AddToSprint(_) => "add to current sprint".to_string(),
+            CallbackParams::MoveToNextSprint(_) => "move to next sprint".to_string(),
+            CallbackParams::BoardOverview(_) => "back".to_string(),
+            CallbackParams::BoardColumn(p) => format!("column {}", p.column + 1),
+            CallbackParams::BoardPin(_) => "pin as default".to_string(),
+            CallbackParams::ApplyPriorityVotes(_) => "vote in YouTrack".to_string(),
+            CallbackParams::EstimationVote(p) => p.value.to_string(),
+            CallbackParams::EstimationReveal(_) => "reveal".to_string(),
+            CallbackParams::EstimationApply(p) => format!("apply {}", p.value),
+            CallbackParams::TriageMenu(p) => p.field.name().to_lowercase(),
+            CallbackParams::TriageSet(p) => p.value.to_string(),
+            CallbackParams::TriageBack(_) => "back".to_string(),
+            CallbackParams::TriageDuplicate(_) => "duplicate of…".to_string(),
+            CallbackParams::TriageNeedInfo(_) => "need info".to_string(),
+            CallbackParams::TriageSkip(_) => "skip".to_string(),
+            CallbackParams::TriageStop(_) => "stop".to_string(),
         };
-        let val = serde_json::to_string(&item).unwrap();
-        if val.len() > 64 {
-            panic!("Callback paramater too big: {}", val);
-        }
-        InlineKeyboardButton::callback(text, val)
+        item.button(text)
     }
 }
 
@@ -94,11 +735,70 @@ pub enum BotCommand {
     Login(Message),
     Stop(Message),
     Text(Message),
+    Attachment(Message),
     NewIssue(Message),
+    Comment(Message, Option<CommentParams>),
+    IssueCommand(Message, Option<IssueCommandParams>),
+    IssueCard(Message, Option<String>),
+    Spent(Message, Option<SpentParams>),
+    Timer(Message, Option<String>),
+    Timesheet(Message, Option<TimesheetParams>),
+    History(Message, Option<String>),
+    Watch(Message, Option<String>),
+    Unwatch(Message, Option<String>),
+    Digest(Message, Option<DigestParams>),
+    Timezone(Message, Option<String>),
+    Settings(Message, Option<SettingsParams>),
+    Board(Message, Option<String>),
+    Burndown(Message, Option<String>),
+    Velocity(Message, Option<String>),
+    Standup(Message, Option<StandupParams>),
+    Config(Message, Option<ConfigParams>),
+    Prioritize(Message, Option<PrioritizeParams>),
+    Estimate(Message, Option<EstimateParams>),
+    Triage(Message, Option<String>),
     BacklogStop(CallbackQuery),
     BacklogNext(CallbackQuery, BacklogParams),
     BacklogPrev(CallbackQuery, BacklogParams),
     BacklogVoteForIssue(CallbackQuery, VoteForIssueParams),
+    BacklogSelect(CallbackQuery, BacklogParams),
+    BacklogSelectDone(CallbackQuery, BacklogParams),
+    SelectIssue(CallbackQuery, SelectIssueParams),
+    BulkVote(CallbackQuery, BacklogParams),
+    BulkCommand(CallbackQuery, BulkCommandParams),
+    IssueTagToggle(CallbackQuery, IssueTagParams),
+    IssueTagsDone(CallbackQuery),
+    PickerSelect(CallbackQuery, PickerParams),
+    PickerPage(CallbackQuery, PickerParams),
+    AssignToMe(CallbackQuery),
+    Unassigned(CallbackQuery),
+    CommandSuggestion(CallbackQuery, SuggestionParams),
+    CommandApply(CallbackQuery),
+    CommandStop(CallbackQuery),
+    SetIssueState(CallbackQuery, IssueStateParams),
+    AssignIssueToMe(CallbackQuery, IssueParams),
+    ShowHistory(CallbackQuery, IssueParams),
+    HistoryPage(CallbackQuery, HistoryParams),
+    SettingsToggle(CallbackQuery, NotificationCategoryParams),
+    SettingsQuiet(CallbackQuery),
+    SettingsBatching(CallbackQuery),
+    SettingsDone(CallbackQuery),
+    AddToSprint(CallbackQuery, IssueParams),
+    MoveToNextSprint(CallbackQuery, IssueParams),
+    BoardOverview(CallbackQuery, BoardParams),
+    BoardColumn(CallbackQuery, BoardColumnParams),
+    BoardPin(CallbackQuery, BoardParams),
+    ApplyPriorityVotes(CallbackQuery, PollParams),
+    EstimationVote(CallbackQuery, EstimationVoteParams),
+    EstimationReveal(CallbackQuery, EstimationParams),
+    EstimationApply(CallbackQuery, EstimationVoteParams),
+    TriageMenu(CallbackQuery, TriageFieldParams),
+    TriageSet(CallbackQuery, TriageValueParams),
+    TriageBack(CallbackQuery, TriageParams),
+    TriageDuplicate(CallbackQuery, TriageParams),
+    TriageNeedInfo(CallbackQuery, TriageParams),
+    TriageSkip(CallbackQuery, TriageParams),
+    TriageStop(CallbackQuery, TriageParams),
     Save(Message),
     Cancel(Message),
 }
@@ -117,6 +817,144 @@ impl BotCommand {
         }
     }
 
+    /// Issue card buttons act on their issue whatever the user is doing
+    pub fn is_issue_card_action(&self) -> bool {
+        match self {
+            BotCommand::SetIssueState(_, _)
+            | BotCommand::AssignIssueToMe(_, _)
+            | BotCommand::ShowHistory(_, _)
+            | BotCommand::HistoryPage(_, _)
+            | BotCommand::AddToSprint(_, _)
+            | BotCommand::MoveToNextSprint(_, _) => true,
+            _ => false,
+        }
+    }
+
+    /// Backlog buttons carry their page, so anyone in the chat can use them.
+    /// Bulk commands aren't among them, they are composed in the user state
+    pub fn is_backlog_action(&self) -> bool {
+        match self {
+            BotCommand::BacklogStop(_)
+            | BotCommand::BacklogNext(_, _)
+            | BotCommand::BacklogPrev(_, _)
+            | BotCommand::BacklogVoteForIssue(_, _)
+            | BotCommand::BacklogSelect(_, _)
+            | BotCommand::BacklogSelectDone(_, _)
+            | BotCommand::SelectIssue(_, _)
+            | BotCommand::BulkVote(_, _)
+            | BotCommand::ApplyPriorityVotes(_, _) => true,
+            _ => false,
+        }
+    }
+
+    /// Settings menu buttons don't depend on the conversation state either
+    pub fn is_settings_action(&self) -> bool {
+        match self {
+            BotCommand::SettingsToggle(_, _)
+            | BotCommand::SettingsQuiet(_)
+            | BotCommand::SettingsBatching(_)
+            | BotCommand::SettingsDone(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_board_action(&self) -> bool {
+        match self {
+            BotCommand::BoardOverview(_, _)
+            | BotCommand::BoardColumn(_, _)
+            | BotCommand::BoardPin(_, _) => true,
+            _ => false,
+        }
+    }
+
+    /// Feature the command belongs to, commands of disabled features are ignored
+    pub fn feature(&self) -> Option<Feature> {
+        match self {
+            BotCommand::Backlog(_, _)
+            | BotCommand::BacklogStop(_)
+            | BotCommand::BacklogNext(_, _)
+            | BotCommand::BacklogPrev(_, _)
+            | BotCommand::BacklogVoteForIssue(_, _)
+            | BotCommand::BacklogSelect(_, _)
+            | BotCommand::BacklogSelectDone(_, _)
+            | BotCommand::SelectIssue(_, _)
+            | BotCommand::BulkVote(_, _)
+            | BotCommand::BulkCommand(_, _)
+            | BotCommand::Prioritize(_, _)
+            | BotCommand::ApplyPriorityVotes(_, _)
+            | BotCommand::Triage(_, _)
+            | BotCommand::TriageMenu(_, _)
+            | BotCommand::TriageSet(_, _)
+            | BotCommand::TriageBack(_, _)
+            | BotCommand::TriageDuplicate(_, _)
+            | BotCommand::TriageNeedInfo(_, _)
+            | BotCommand::TriageSkip(_, _)
+            | BotCommand::TriageStop(_, _) => Some(Feature::Backlog),
+            BotCommand::NewIssue(_)
+            | BotCommand::Comment(_, _)
+            | BotCommand::IssueCommand(_, _)
+            | BotCommand::IssueCard(_, _)
+            | BotCommand::History(_, _)
+            | BotCommand::IssueTagToggle(_, _)
+            | BotCommand::IssueTagsDone(_)
+            | BotCommand::PickerSelect(_, _)
+            | BotCommand::PickerPage(_, _)
+            | BotCommand::AssignToMe(_)
+            | BotCommand::Unassigned(_)
+            | BotCommand::CommandSuggestion(_, _)
+            | BotCommand::CommandApply(_)
+            | BotCommand::CommandStop(_)
+            | BotCommand::SetIssueState(_, _)
+            | BotCommand::AssignIssueToMe(_, _)
+            | BotCommand::ShowHistory(_, _)
+            | BotCommand::HistoryPage(_, _) => Some(Feature::Issues),
+            BotCommand::Spent(_, _) | BotCommand::Timer(_, _) | BotCommand::Timesheet(_, _) => {
+                Some(Feature::TimeTracking)
+            }
+            BotCommand::Watch(_, _) | BotCommand::Unwatch(_, _) | BotCommand::Digest(_, _) => {
+                Some(Feature::Watches)
+            }
+            BotCommand::Board(_, _)
+            | BotCommand::Burndown(_, _)
+            | BotCommand::Velocity(_, _)
+            | BotCommand::BoardOverview(_, _)
+            | BotCommand::BoardColumn(_, _)
+            | BotCommand::BoardPin(_, _)
+            | BotCommand::AddToSprint(_, _)
+            | BotCommand::MoveToNextSprint(_, _) => Some(Feature::Boards),
+            BotCommand::Standup(_, _) => Some(Feature::Standups),
+            BotCommand::Estimate(_, _)
+            | BotCommand::EstimationVote(_, _)
+            | BotCommand::EstimationReveal(_, _)
+            | BotCommand::EstimationApply(_, _) => Some(Feature::Estimation),
+            _ => None,
+        }
+    }
+
+    /// Planning poker buttons belong to a round, not to the conversation state
+    pub fn is_estimation_action(&self) -> bool {
+        match self {
+            BotCommand::EstimationVote(_, _)
+            | BotCommand::EstimationReveal(_, _)
+            | BotCommand::EstimationApply(_, _) => true,
+            _ => false,
+        }
+    }
+
+    /// Triage buttons act on the session of their message. "Duplicate of…" isn't
+    /// one of them, it waits for the issue id in the user state
+    pub fn is_triage_action(&self) -> bool {
+        match self {
+            BotCommand::TriageMenu(_, _)
+            | BotCommand::TriageSet(_, _)
+            | BotCommand::TriageBack(_, _)
+            | BotCommand::TriageNeedInfo(_, _)
+            | BotCommand::TriageSkip(_, _)
+            | BotCommand::TriageStop(_, _) => true,
+            _ => false,
+        }
+    }
+
     pub fn get_user(&self) -> &User {
         match self {
             BotCommand::Start(m) => &m.from,
@@ -124,21 +962,175 @@ impl BotCommand {
             BotCommand::Login(m) => &m.from,
             BotCommand::Stop(m) => &m.from,
             BotCommand::Text(m) => &m.from,
+            BotCommand::Attachment(m) => &m.from,
             BotCommand::NewIssue(m) => &m.from,
+            BotCommand::Comment(m, _) => &m.from,
+            BotCommand::IssueCommand(m, _) => &m.from,
+            BotCommand::IssueCard(m, _) => &m.from,
+            BotCommand::Spent(m, _) => &m.from,
+            BotCommand::Timer(m, _) => &m.from,
+            BotCommand::Timesheet(m, _) => &m.from,
+            BotCommand::History(m, _) => &m.from,
+            BotCommand::Watch(m, _) => &m.from,
+            BotCommand::Unwatch(m, _) => &m.from,
+            BotCommand::Digest(m, _) => &m.from,
+            BotCommand::Timezone(m, _) => &m.from,
+            BotCommand::Settings(m, _) => &m.from,
+            BotCommand::Board(m, _) => &m.from,
+            BotCommand::Burndown(m, _) => &m.from,
+            BotCommand::Velocity(m, _) => &m.from,
+            BotCommand::Standup(m, _) => &m.from,
+            BotCommand::Config(m, _) => &m.from,
+            BotCommand::Prioritize(m, _) => &m.from,
+            BotCommand::Estimate(m, _) => &m.from,
+            BotCommand::Triage(m, _) => &m.from,
             BotCommand::BacklogStop(m) => &m.from,
             BotCommand::BacklogNext(m, _) => &m.from,
             BotCommand::BacklogPrev(m, _) => &m.from,
             BotCommand::BacklogVoteForIssue(m, _) => &m.from,
+            BotCommand::BacklogSelect(m, _) => &m.from,
+            BotCommand::BacklogSelectDone(m, _) => &m.from,
+            BotCommand::SelectIssue(m, _) => &m.from,
+            BotCommand::BulkVote(m, _) => &m.from,
+            BotCommand::BulkCommand(m, _) => &m.from,
+            BotCommand::IssueTagToggle(m, _) => &m.from,
+            BotCommand::IssueTagsDone(m) => &m.from,
+            BotCommand::PickerSelect(m, _) => &m.from,
+            BotCommand::PickerPage(m, _) => &m.from,
+            BotCommand::AssignToMe(m) => &m.from,
+            BotCommand::Unassigned(m) => &m.from,
+            BotCommand::CommandSuggestion(m, _) => &m.from,
+            BotCommand::CommandApply(m) => &m.from,
+            BotCommand::CommandStop(m) => &m.from,
+            BotCommand::SetIssueState(m, _) => &m.from,
+            BotCommand::AssignIssueToMe(m, _) => &m.from,
+            BotCommand::ShowHistory(m, _) => &m.from,
+            BotCommand::HistoryPage(m, _) => &m.from,
+            BotCommand::SettingsToggle(m, _) => &m.from,
+            BotCommand::SettingsQuiet(m) => &m.from,
+            BotCommand::SettingsBatching(m) => &m.from,
+            BotCommand::SettingsDone(m) => &m.from,
+            BotCommand::AddToSprint(m, _) => &m.from,
+            BotCommand::MoveToNextSprint(m, _) => &m.from,
+            BotCommand::BoardOverview(m, _) => &m.from,
+            BotCommand::BoardColumn(m, _) => &m.from,
+            BotCommand::BoardPin(m, _) => &m.from,
+            BotCommand::ApplyPriorityVotes(m, _) => &m.from,
+            BotCommand::EstimationVote(m, _) => &m.from,
+            BotCommand::EstimationReveal(m, _) => &m.from,
+            BotCommand::EstimationApply(m, _) => &m.from,
+            BotCommand::TriageMenu(m, _) => &m.from,
+            BotCommand::TriageSet(m, _) => &m.from,
+            BotCommand::TriageBack(m, _) => &m.from,
+            BotCommand::TriageDuplicate(m, _) => &m.from,
+            BotCommand::TriageNeedInfo(m, _) => &m.from,
+            BotCommand::TriageSkip(m, _) => &m.from,
+            BotCommand::TriageStop(m, _) => &m.from,
             BotCommand::Save(m) => &m.from,
             BotCommand::Cancel(m) => &m.from,
         }
     }
+
+    /// Message to answer in the chat the command comes from: the command itself
+    /// or the message with the pressed button
+    pub fn get_message(&self) -> Option<&Message> {
+        match self {
+            BotCommand::Start(m) => Some(m),
+            BotCommand::Backlog(m, _) => Some(m),
+            BotCommand::Login(m) => Some(m),
+            BotCommand::Stop(m) => Some(m),
+            BotCommand::Text(m) => Some(m),
+            BotCommand::Attachment(m) => Some(m),
+            BotCommand::NewIssue(m) => Some(m),
+            BotCommand::Comment(m, _) => Some(m),
+            BotCommand::IssueCommand(m, _) => Some(m),
+            BotCommand::IssueCard(m, _) => Some(m),
+            BotCommand::Spent(m, _) => Some(m),
+            BotCommand::Timer(m, _) => Some(m),
+            BotCommand::Timesheet(m, _) => Some(m),
+            BotCommand::History(m, _) => Some(m),
+            BotCommand::Watch(m, _) => Some(m),
+            BotCommand::Unwatch(m, _) => Some(m),
+            BotCommand::Digest(m, _) => Some(m),
+            BotCommand::Timezone(m, _) => Some(m),
+            BotCommand::Settings(m, _) => Some(m),
+            BotCommand::Board(m, _) => Some(m),
+            BotCommand::Burndown(m, _) => Some(m),
+            BotCommand::Velocity(m, _) => Some(m),
+            BotCommand::Standup(m, _) => Some(m),
+            BotCommand::Config(m, _) => Some(m),
+            BotCommand::Prioritize(m, _) => Some(m),
+            BotCommand::Estimate(m, _) => Some(m),
+            BotCommand::Triage(m, _) => Some(m),
+            BotCommand::BacklogStop(m) => m.message.as_ref(),
+            BotCommand::BacklogNext(m, _) => m.message.as_ref(),
+            BotCommand::BacklogPrev(m, _) => m.message.as_ref(),
+            BotCommand::BacklogVoteForIssue(m, _) => m.message.as_ref(),
+            BotCommand::BacklogSelect(m, _) => m.message.as_ref(),
+            BotCommand::BacklogSelectDone(m, _) => m.message.as_ref(),
+            BotCommand::SelectIssue(m, _) => m.message.as_ref(),
+            BotCommand::BulkVote(m, _) => m.message.as_ref(),
+            BotCommand::BulkCommand(m, _) => m.message.as_ref(),
+            BotCommand::IssueTagToggle(m, _) => m.message.as_ref(),
+            BotCommand::IssueTagsDone(m) => m.message.as_ref(),
+            BotCommand::PickerSelect(m, _) => m.message.as_ref(),
+            BotCommand::PickerPage(m, _) => m.message.as_ref(),
+            BotCommand::AssignToMe(m) => m.message.as_ref(),
+            BotCommand::Unassigned(m) => m.message.as_ref(),
+            BotCommand::CommandSuggestion(m, _) => m.message.as_ref(),
+            BotCommand::CommandApply(m) => m.message.as_ref(),
+            BotCommand::CommandStop(m) => m.message.as_ref(),
+            BotCommand::SetIssueState(m, _) => m.message.as_ref(),
+            BotCommand::AssignIssueToMe(m, _) => m.message.as_ref(),
+            BotCommand::ShowHistory(m, _) => m.message.as_ref(),
+            BotCommand::HistoryPage(m, _) => m.message.as_ref(),
+            BotCommand::SettingsToggle(m, _) => m.message.as_ref(),
+            BotCommand::SettingsQuiet(m) => m.message.as_ref(),
+            BotCommand::SettingsBatching(m) => m.message.as_ref(),
+            BotCommand::SettingsDone(m) => m.message.as_ref(),
+            BotCommand::AddToSprint(m, _) => m.message.as_ref(),
+            BotCommand::MoveToNextSprint(m, _) => m.message.as_ref(),
+            BotCommand::BoardOverview(m, _) => m.message.as_ref(),
+            BotCommand::BoardColumn(m, _) => m.message.as_ref(),
+            BotCommand::BoardPin(m, _) => m.message.as_ref(),
+            BotCommand::ApplyPriorityVotes(m, _) => m.message.as_ref(),
+            BotCommand::EstimationVote(m, _) => m.message.as_ref(),
+            BotCommand::EstimationReveal(m, _) => m.message.as_ref(),
+            BotCommand::EstimationApply(m, _) => m.message.as_ref(),
+            BotCommand::TriageMenu(m, _) => m.message.as_ref(),
+            BotCommand::TriageSet(m, _) => m.message.as_ref(),
+            BotCommand::TriageBack(m, _) => m.message.as_ref(),
+            BotCommand::TriageDuplicate(m, _) => m.message.as_ref(),
+            BotCommand::TriageNeedInfo(m, _) => m.message.as_ref(),
+            BotCommand::TriageSkip(m, _) => m.message.as_ref(),
+            BotCommand::TriageStop(m, _) => m.message.as_ref(),
+            BotCommand::Save(m) => Some(m),
+            BotCommand::Cancel(m) => Some(m),
+        }
+    }
+}
+
+/// Bot a command like `/backlog@ourbot` is addressed to, if any
+pub fn command_addressee(msg: &Message) -> Option<&str> {
+    match msg.kind {
+        MessageKind::Text { ref data, .. } if data.starts_with('/') => data
+            .split(char::is_whitespace)
+            .next()
+            .and_then(|command| command.splitn(2, '@').nth(1)),
+        _ => None,
+    }
 }
 
 impl TryFrom<Message> for BotCommand {
     type Error = Error;
 
     fn try_from(msg: Message) -> Result<Self> {
+        match msg.kind {
+            MessageKind::Document { .. } | MessageKind::Photo { .. } => {
+                return Ok(BotCommand::Attachment(msg))
+            }
+            _ => {}
+        };
         if let MessageKind::Text { ref data, .. } = msg.kind {
             debug!(
                 "<{}>: {} {} {}",
@@ -147,14 +1139,52 @@ impl TryFrom<Message> for BotCommand {
                 &msg.chat.id(),
                 data
             );
-            let cmd = match data.as_str() {
-                "/backlog" => BotCommand::Backlog(msg, BacklogParams::new(5)),
+            let mut parts = data.splitn(2, char::is_whitespace);
+            // Commands in groups may be addressed as /backlog@ourbot
+            let command = parts
+                .next()
+                .unwrap_or("")
+                .splitn(2, '@')
+                .next()
+                .unwrap_or("");
+            let args = parts.next().unwrap_or("").trim().to_string();
+            let cmd = match command {
+                "/backlog" => BotCommand::Backlog(msg, BacklogParams::new(BACKLOG_PAGE_SIZE)),
                 "/start" => BotCommand::Start(msg),
                 "/login" => BotCommand::Login(msg),
                 "/stop" => BotCommand::Stop(msg),
                 "/new_issue" => BotCommand::NewIssue(msg),
                 "/save" => BotCommand::Save(msg),
                 "/cancel" => BotCommand::Cancel(msg),
+                "/comment" => BotCommand::Comment(msg, CommentParams::parse(&args)),
+                "/issue" => {
+                    let issue = args.split_whitespace().next().map(|a| a.to_string());
+                    BotCommand::IssueCard(msg, issue)
+                }
+                "/history" => {
+                    let issue = args.split_whitespace().next().map(|a| a.to_string());
+                    BotCommand::History(msg, issue)
+                }
+                "/watch" => BotCommand::Watch(msg, Some(args).filter(|a| !a.is_empty())),
+                "/unwatch" => BotCommand::Unwatch(msg, Some(args).filter(|a| !a.is_empty())),
+                "/digest" => BotCommand::Digest(msg, DigestParams::parse(&args)),
+                "/timezone" => BotCommand::Timezone(msg, Some(args).filter(|a| !a.is_empty())),
+                "/settings" => BotCommand::Settings(msg, SettingsParams::parse(&args)),
+                "/board" => BotCommand::Board(msg, Some(args).filter(|a| !a.is_empty())),
+                "/burndown" => BotCommand::Burndown(msg, Some(args).filter(|a| !a.is_empty())),
+                "/velocity" => BotCommand::Velocity(msg, Some(args).filter(|a| !a.is_empty())),
+                "/standup" => BotCommand::Standup(msg, StandupParams::parse(&args)),
+                "/config" => BotCommand::Config(msg, ConfigParams::parse(&args)),
+                "/prioritize" => BotCommand::Prioritize(msg, PrioritizeParams::parse(&args)),
+                "/estimate" => BotCommand::Estimate(msg, EstimateParams::parse(&args)),
+                "/triage" => BotCommand::Triage(msg, Some(args).filter(|a| !a.is_empty())),
+                "/spent" => BotCommand::Spent(msg, SpentParams::parse(&args)),
+                "/timer" => {
+                    let arg = args.split_whitespace().next().map(|a| a.to_string());
+                    BotCommand::Timer(msg, arg)
+                }
+                "/timesheet" => BotCommand::Timesheet(msg, TimesheetParams::parse(&args)),
+                "/cmd" => BotCommand::IssueCommand(msg, IssueCommandParams::parse(&args)),
                 _ => BotCommand::Text(msg),
             };
             Ok(cmd)
@@ -175,6 +1205,45 @@ impl TryFrom<CallbackQuery> for BotCommand {
                 CallbackParams::BacklogNext(p) => BotCommand::BacklogNext(cb, p),
                 CallbackParams::BacklogPrev(p) => BotCommand::BacklogPrev(cb, p),
                 CallbackParams::VoteForIssue(p) => BotCommand::BacklogVoteForIssue(cb, p),
+                CallbackParams::BacklogSelect(p) => BotCommand::BacklogSelect(cb, p),
+                CallbackParams::BacklogSelectDone(p) => BotCommand::BacklogSelectDone(cb, p),
+                CallbackParams::SelectIssue(p) => BotCommand::SelectIssue(cb, p),
+                CallbackParams::BulkVote(p) => BotCommand::BulkVote(cb, p),
+                CallbackParams::BulkCommand(p) => BotCommand::BulkCommand(cb, p),
+                CallbackParams::ToggleIssueTag(p) => BotCommand::IssueTagToggle(cb, p),
+                CallbackParams::IssueTagsDone => BotCommand::IssueTagsDone(cb),
+                CallbackParams::PickerSelect(p) => BotCommand::PickerSelect(cb, p),
+                CallbackParams::PickerPage(p) => BotCommand::PickerPage(cb, p),
+                CallbackParams::AssignToMe => BotCommand::AssignToMe(cb),
+                CallbackParams::Unassigned => BotCommand::Unassigned(cb),
+                CallbackParams::CommandSuggestion(p) => BotCommand::CommandSuggestion(cb, p),
+                CallbackParams::CommandApply => BotCommand::CommandApply(cb),
+                CallbackParams::CommandStop => BotCommand::CommandStop(cb),
+                CallbackParams::SetIssueState(p) => BotCommand::SetIssueState(cb, p),
+                CallbackParams::AssignIssueToMe(p) => BotCommand::AssignIssueToMe(cb, p),
+                CallbackParams::ShowHistory(p) => BotCommand::ShowHistory(cb, p),
+                CallbackParams::HistoryNext(p) => BotCommand::HistoryPage(cb, p),
+                CallbackParams::HistoryPrev(p) => BotCommand::HistoryPage(cb, p),
+                CallbackParams::SettingsToggle(p) => BotCommand::SettingsToggle(cb, p),
+                CallbackParams::SettingsQuiet => BotCommand::SettingsQuiet(cb),
+                CallbackParams::SettingsBatching => BotCommand::SettingsBatching(cb),
+                CallbackParams::SettingsDone => BotCommand::SettingsDone(cb),
+                CallbackParams::AddToSprint(p) => BotCommand::AddToSprint(cb, p),
+                CallbackParams::MoveToNextSprint(p) => BotCommand::MoveToNextSprint(cb, p),
+                CallbackParams::BoardOverview(p) => BotCommand::BoardOverview(cb, p),
+                CallbackParams::BoardColumn(p) => BotCommand::BoardColumn(cb, p),
+                CallbackParams::BoardPin(p) => BotCommand::BoardPin(cb, p),
+                CallbackParams::ApplyPriorityVotes(p) => BotCommand::ApplyPriorityVotes(cb, p),
+                CallbackParams::EstimationVote(p) => BotCommand::EstimationVote(cb, p),
+                CallbackParams::EstimationReveal(p) => BotCommand::EstimationReveal(cb, p),
+                CallbackParams::EstimationApply(p) => BotCommand::EstimationApply(cb, p),
+                CallbackParams::TriageMenu(p) => BotCommand::TriageMenu(cb, p),
+                CallbackParams::TriageSet(p) => BotCommand::TriageSet(cb, p),
+                CallbackParams::TriageBack(p) => BotCommand::TriageBack(cb, p),
+                CallbackParams::TriageDuplicate(p) => BotCommand::TriageDuplicate(cb, p),
+                CallbackParams::TriageNeedInfo(p) => BotCommand::TriageNeedInfo(cb, p),
+                CallbackParams::TriageSkip(p) => BotCommand::TriageSkip(cb, p),
+                CallbackParams::TriageStop(p) => BotCommand::TriageStop(cb, p),
             })
         } else {
             bail!("No callback query data")
@@ -193,3 +1262,117 @@ impl TryFrom<Update> for BotCommand {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("45"), Some(45));
+        assert_eq!(parse_duration("45m"), Some(45));
+        assert_eq!(parse_duration("2h"), Some(120));
+        assert_eq!(parse_duration("1h30m"), Some(90));
+        assert_eq!(parse_duration("1H30M"), Some(90));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("0"), None);
+        assert_eq!(parse_duration("0h"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("1h30"), None);
+        assert_eq!(parse_duration("1d"), None);
+        assert_eq!(parse_duration("-5"), None);
+    }
+
+    #[test]
+    fn rejects_overflowing_durations() {
+        assert_eq!(parse_duration(&format!("{}h", u64::MAX / 60 + 1)), None);
+        assert_eq!(parse_duration(&format!("{}m1m", u64::MAX)), None);
+    }
+
+    #[test]
+    fn parses_config_params() {
+        assert_eq!(ConfigParams::parse(""), Some(ConfigParams::Show));
+        assert_eq!(
+            ConfigParams::parse("backlog project: TP #Unresolved"),
+            Some(ConfigParams::BacklogQuery(Some(
+                "project: TP #Unresolved".to_string()
+            )))
+        );
+        assert_eq!(
+            ConfigParams::parse("backlog"),
+            Some(ConfigParams::BacklogQuery(None))
+        );
+        assert_eq!(
+            ConfigParams::parse("project Team Project"),
+            Some(ConfigParams::Project(Some("Team Project".to_string())))
+        );
+        assert_eq!(
+            ConfigParams::parse("field Priority Show-stopper"),
+            Some(ConfigParams::Field(
+                "Priority".to_string(),
+                Some("Show-stopper".to_string())
+            ))
+        );
+        assert_eq!(
+            ConfigParams::parse("field Priority"),
+            Some(ConfigParams::Field("Priority".to_string(), None))
+        );
+        assert_eq!(
+            ConfigParams::parse("language EN"),
+            Some(ConfigParams::Language(Language::En))
+        );
+        assert_eq!(
+            ConfigParams::parse("disable Issues"),
+            Some(ConfigParams::Feature(Feature::Issues, false))
+        );
+        assert_eq!(
+            ConfigParams::parse("scale tshirt"),
+            Some(ConfigParams::EstimationScale(EstimationScale::TShirt))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_config_params() {
+        assert_eq!(ConfigParams::parse("field"), None);
+        assert_eq!(ConfigParams::parse("enable"), None);
+        assert_eq!(ConfigParams::parse("enable everything"), None);
+        assert_eq!(ConfigParams::parse("scale linear"), None);
+        assert_eq!(ConfigParams::parse("language de"), None);
+    }
+
+    #[test]
+    fn parses_prioritize_params() {
+        assert_eq!(
+            PrioritizeParams::parse(""),
+            Some(PrioritizeParams::Start(None, DEFAULT_OPTIONS))
+        );
+        assert_eq!(
+            PrioritizeParams::parse("stop"),
+            Some(PrioritizeParams::Stop)
+        );
+        assert_eq!(
+            PrioritizeParams::parse("#Unresolved 3"),
+            Some(PrioritizeParams::Start(Some("#Unresolved".to_string()), 3))
+        );
+        assert_eq!(
+            PrioritizeParams::parse("Fix versions: 2.0"),
+            Some(PrioritizeParams::Start(
+                Some("Fix versions: 2.0".to_string()),
+                DEFAULT_OPTIONS
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_prioritize_option_counts_out_of_range() {
+        assert_eq!(PrioritizeParams::parse("1"), None);
+        assert_eq!(
+            PrioritizeParams::parse(&format!("#Unresolved {}", MAX_OPTIONS + 1)),
+            None
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use super::states::{Digest, DigestPeriod};
+
+// Unresolved issues not updated for this long are reported as stale
+pub const STALE_DAYS: i64 = 14;
+
+/// Issue created or resolved in the digest period
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DigestIssue {
+    #[serde(alias = "idReadable")]
+    pub id_readable: String,
+    pub summary: String,
+    /// Milliseconds since epoch
+    #[serde(default)]
+    pub created: Option<i64>,
+    #[serde(default)]
+    pub resolved: Option<i64>,
+}
+
+/// Query without its sort clause, so more conditions can be added
+pub fn without_sort(query: &str) -> &str {
+    match query.to_lowercase().find("sort by") {
+        // Lowercase may shift non-ASCII positions, such queries are kept as is
+        Some(pos) => query.get(..pos).map_or(query, |q| q.trim_end()),
+        None => query,
+    }
+}
+
+/// Whole days covering the range, in the `2020-01-31` query format. Query dates are in the
+/// time zone of the YouTrack account, so the range is one day wider on each side
+pub fn query_dates(from: i64, to: i64) -> (String, String) {
+    let date = |ms: i64| {
+        NaiveDateTime::from_timestamp(ms.div_euclid(1000), 0)
+            .date()
+            .format("%Y-%m-%d")
+            .to_string()
+    };
+    let day = Duration::days(1).num_milliseconds();
+    (date(from - day), date(to + day))
+}
+
+impl DigestPeriod {
+    pub fn duration(&self) -> Duration {
+        match self {
+            DigestPeriod::Daily => Duration::days(1),
+            DigestPeriod::Weekly(_) => Duration::weeks(1),
+        }
+    }
+}
+
+impl Digest {
+    pub fn job(&self) -> String {
+        format!("digest:{}:{}", self.chat, self.id)
+    }
+
+    /// Milliseconds range `[from, to)` the digest covers, from the previous run
+    /// or one period back for the first one
+    pub fn window(&self, now: DateTime<Utc>) -> (i64, i64) {
+        let from = self
+            .last_run
+            .unwrap_or_else(|| (now - self.period.duration()).timestamp_millis());
+        (from, now.timestamp_millis())
+    }
+
+    /// First time after `now` the digest is due at, in the chat time zone
+    pub fn next_run(&self, tz: Tz, now: DateTime<Utc>) -> DateTime<Utc> {
+        let local_now = now.with_timezone(&tz);
+        let mut date = local_now.date().naive_local();
+        loop {
+            let matches = match &self.period {
+                DigestPeriod::Daily => true,
+                DigestPeriod::Weekly(weekday) => date.weekday() == *weekday,
+            };
+            if matches {
+                // Skipped by a DST switch when none
+                if let Some(run) = tz.from_local_datetime(&date.and_time(self.time)).earliest() {
+                    if run > local_now {
+                        return run.with_timezone(&Utc);
+                    }
+                }
+            }
+            date = date.succ();
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let when = match &self.period {
+            DigestPeriod::Daily => "daily".to_string(),
+            DigestPeriod::Weekly(weekday) => format!("weekly on {}", weekday),
+        };
+        format!("{} at {}: {}", when, self.time.format("%H:%M"), self.query)
+    }
+}
@@ -11,6 +11,9 @@ error_chain! {
         Io(::std::io::Error);
         JsonError(::serde_json::error::Error);
         RedisError(::redis::RedisError);
+        Hyper(::hyper::Error);
+        Http(::hyper::http::Error);
+        InvalidUri(::hyper::http::uri::InvalidUri);
     }
 
 }
@@ -0,0 +1,99 @@
+use super::states::{EstimationScale, EstimationSession, EstimationVote};
+
+/// Seconds an unfinished round is kept
+pub const SESSION_TTL: usize = 24 * 3600;
+
+/// Field the agreed value is written to
+pub const ESTIMATION_FIELD: &str = "Estimation";
+
+const UNSURE: &str = "?";
+
+impl EstimationScale {
+    pub fn parse(name: &str) -> Option<EstimationScale> {
+        match name.to_lowercase().as_str() {
+            "fibonacci" => Some(EstimationScale::Fibonacci),
+            "tshirt" => Some(EstimationScale::TShirt),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EstimationScale::Fibonacci => "fibonacci",
+            EstimationScale::TShirt => "tshirt",
+        }
+    }
+
+    pub fn values(self) -> Vec<&'static str> {
+        match self {
+            EstimationScale::Fibonacci => vec!["1", "2", "3", "5", "8", "13", "21", UNSURE],
+            EstimationScale::TShirt => vec!["XS", "S", "M", "L", "XL", UNSURE],
+        }
+    }
+
+    /// Period the value is written as, points are days. None for "not sure".
+    pub fn presentation(self, value: &str) -> Option<String> {
+        match self {
+            EstimationScale::Fibonacci => value.parse::<u32>().ok().map(|d| format!("{}d", d)),
+            EstimationScale::TShirt => match value {
+                "XS" => Some("2h".to_string()),
+                "S" => Some("1d".to_string()),
+                "M" => Some("3d".to_string()),
+                "L" => Some("1w".to_string()),
+                "XL" => Some("2w".to_string()),
+                _ => None,
+            },
+        }
+    }
+}
+
+pub fn session_key(chat: i64, message: i64) -> String {
+    format!("estimate:{}:{}", chat, message)
+}
+
+/// Message id of the latest round on the issue in the chat
+pub fn issue_key(chat: i64, issue: &str) -> String {
+    format!("estimate_issue:{}:{}", chat, issue)
+}
+
+impl EstimationSession {
+    pub fn key(&self) -> String {
+        session_key(self.chat, self.message)
+    }
+
+    /// Records or changes the vote, reveals the round once everyone expected voted
+    pub fn vote(&mut self, id: i64, name: String, value: String) {
+        match self.votes.iter_mut().find(|v| v.id == id) {
+            Some(vote) => vote.value = value,
+            None => self.votes.push(EstimationVote { id, name, value }),
+        }
+        if self.votes.len() >= self.expected {
+            self.revealed = true;
+        }
+    }
+
+    pub fn voters(&self) -> Vec<String> {
+        self.votes.iter().map(|v| v.name.clone()).collect()
+    }
+
+    /// Values with the names of their voters, most popular first, ties in scale order
+    pub fn distribution(&self) -> Vec<(String, Vec<String>)> {
+        let mut distribution: Vec<(String, Vec<String>)> = self
+            .scale
+            .values()
+            .into_iter()
+            .map(|value| {
+                let names: Vec<String> = self
+                    .votes
+                    .iter()
+                    .filter(|v| v.value == value)
+                    .map(|v| v.name.clone())
+                    .collect();
+                (value.to_string(), names)
+            })
+            .filter(|(_, names)| !names.is_empty())
+            .collect();
+        distribution.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+        distribution
+    }
+}
@@ -0,0 +1,83 @@
+use hyper::client::HttpConnector;
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::{Body, Client, Request, Response, Uri};
+use hyper_rustls::HttpsConnector;
+use hyper_socks2::SocksConnector;
+use uuid::Uuid;
+
+use super::errors::*;
+
+pub type ProxyConnector = HttpsConnector<SocksConnector<HttpsConnector<HttpConnector>>>;
+
+/// Client for Telegram file downloads, goes through SOCKS5_PROXY like the bot api
+#[derive(Clone)]
+pub enum HttpClient {
+    Direct(Client<HttpsConnector<HttpConnector>>),
+    Proxy(Client<ProxyConnector>),
+}
+
+impl HttpClient {
+    async fn get(&self, uri: Uri) -> Result<Response<Body>> {
+        let res = match self {
+            HttpClient::Direct(client) => client.get(uri).await?,
+            HttpClient::Proxy(client) => client.get(uri).await?,
+        };
+        Ok(res)
+    }
+}
+
+fn https_client() -> Client<HttpsConnector<HttpConnector>> {
+    Client::builder().build(HttpsConnector::new())
+}
+
+pub async fn download(client: &HttpClient, url: &str) -> Result<Vec<u8>> {
+    let uri: Uri = url.parse()?;
+    let res = client.get(uri).await?;
+    if !res.status().is_success() {
+        bail!("Unable to download file")
+    }
+    let body = hyper::body::to_bytes(res.into_body()).await?;
+    Ok(body.to_vec())
+}
+
+/// Uploads file to the issue attachments, youtrack-rs can't send multipart requests
+pub async fn upload_attachment(
+    api_url: &str,
+    token: &str,
+    issue_id: &str,
+    filename: &str,
+    data: Vec<u8>,
+) -> Result<()> {
+    let boundary = format!("------------{}", Uuid::new_v4().to_simple());
+    let filename = filename.replace('"', "_");
+
+    let mut body: Vec<u8> = Vec::new();
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n",
+            boundary, filename
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(&data);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+    let req = Request::post(format!(
+        "{}issues/{}/attachments?fields=id",
+        api_url, issue_id
+    ))
+    .header(AUTHORIZATION, format!("Bearer {}", token))
+    .header(
+        CONTENT_TYPE,
+        format!("multipart/form-data; boundary={}", boundary),
+    )
+    .body(Body::from(body))?;
+
+    let res = https_client().request(req).await?;
+    debug!("{}", res.status());
+    if !res.status().is_success() {
+        bail!("Unable to upload attachment")
+    }
+    Ok(())
+}
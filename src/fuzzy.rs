@@ -0,0 +1,83 @@
+use strsim::normalized_levenshtein;
+
+/// Value which can be looked up by typing its name
+pub trait Named {
+    /// Name displayed on the picker button
+    fn title(&self) -> String;
+
+    /// All names the value is matched against
+    fn names(&self) -> Vec<String> {
+        vec![self.title()]
+    }
+}
+
+const MAX_SUGGESTIONS: usize = 6;
+
+// Scores below this are not worth suggesting
+const MIN_SCORE: f64 = 0.5;
+// Prefix and substring matches score above this, typo matches never do
+const CONFIDENT_SCORE: f64 = 0.8;
+// How far the best match must be ahead of the next one to be picked
+const CLEAR_LEAD: f64 = 0.2;
+
+#[derive(Debug, PartialEq)]
+pub enum Lookup {
+    Found(usize),
+    Ambiguous(Vec<usize>),
+    NotFound,
+}
+
+fn score(query: &str, name: &str) -> f64 {
+    let name = name.to_lowercase();
+    if name == query {
+        1.0
+    } else if name.starts_with(query) {
+        0.9
+    } else if name.split_whitespace().any(|w| w.starts_with(query)) {
+        0.85
+    } else if name.contains(query) {
+        0.8
+    } else {
+        0.75 * normalized_levenshtein(query, &name)
+    }
+}
+
+/// Looks up typed text among values, returns indices of matched values
+pub fn lookup<T: Named>(query: &str, values: &[T]) -> Lookup {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Lookup::NotFound;
+    }
+
+    let mut scored: Vec<(usize, f64)> = values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let best = v
+                .names()
+                .iter()
+                .map(|n| score(&query, n))
+                .fold(0.0, f64::max);
+            (i, best)
+        })
+        .filter(|(_, s)| *s >= MIN_SCORE)
+        .collect();
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+    match scored.as_slice() {
+        [] => Lookup::NotFound,
+        [(i, s)] if *s >= CONFIDENT_SCORE => Lookup::Found(*i),
+        [(i, s), (_, next), ..]
+            if *s >= 1.0 || (*s >= CONFIDENT_SCORE && s - next >= CLEAR_LEAD) =>
+        {
+            Lookup::Found(*i)
+        }
+        _ => Lookup::Ambiguous(
+            scored
+                .iter()
+                .take(MAX_SUGGESTIONS)
+                .map(|(i, _)| *i)
+                .collect(),
+        ),
+    }
+}
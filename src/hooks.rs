@@ -0,0 +1,109 @@
+use ring::{constant_time, hmac};
+use serde::{Deserialize, Serialize};
+
+/// Field change reported by a workflow
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HookChange {
+    pub field: String,
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
+}
+
+/// Notification pushed by a YouTrack workflow to `POST /hooks/youtrack`.
+///
+/// ```json
+/// {"issue": "TP-123", "summary": "Login fails", "event": "updated", "author": "jdoe",
+///  "changes": [{"field": "State", "from": "Open", "to": "Fixed"}],
+///  "comment": "Fixed in 1.2", "users": ["asmith"], "chats": [-1001234567890]}
+/// ```
+///
+/// Only `issue` and `event` are required. `users` are YouTrack logins, notified privately
+/// once they have used /login, `chats` are Telegram chat ids.
+///
+/// The request is authenticated either by `Authorization: Bearer <secret>` or by
+/// `X-Hook-Signature: sha256=<hex HMAC-SHA256 of the body keyed with the secret>`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HookPayload {
+    pub issue: String,
+    #[serde(default)]
+    pub summary: Option<String>,
+    pub event: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub changes: Vec<HookChange>,
+    #[serde(default)]
+    pub comment: Option<String>,
+    #[serde(default)]
+    pub users: Vec<String>,
+    #[serde(default)]
+    pub chats: Vec<i64>,
+}
+
+impl HookPayload {
+    /// What has been done, one line per change
+    pub fn describe(&self) -> String {
+        let mut lines = Vec::new();
+        match self.event.as_str() {
+            "created" => lines.push("created the issue".to_string()),
+            "updated" | "commented" if !self.changes.is_empty() || self.comment.is_some() => {}
+            event => lines.push(event.to_string()),
+        }
+        for change in &self.changes {
+            lines.push(format!(
+                "{}: {} → {}",
+                change.field,
+                change.from.as_deref().unwrap_or("none"),
+                change.to.as_deref().unwrap_or("none")
+            ));
+        }
+        if let Some(comment) = &self.comment {
+            lines.push(format!("commented: {}", comment));
+        }
+        lines.join("\n")
+    }
+}
+
+fn strip_prefix<'a>(value: &'a str, prefix: &str) -> Option<&'a str> {
+    if value.starts_with(prefix) {
+        Some(&value[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Checks the bearer token or the body signature against the shared secret
+pub fn verify(
+    secret: &str,
+    authorization: Option<&str>,
+    signature: Option<&str>,
+    body: &[u8],
+) -> bool {
+    if let Some(token) = authorization.and_then(|a| strip_prefix(a, "Bearer ")) {
+        if constant_time::verify_slices_are_equal(token.as_bytes(), secret.as_bytes()).is_ok() {
+            return true;
+        }
+    }
+    match signature
+        .and_then(|s| strip_prefix(s, "sha256="))
+        .and_then(decode_hex)
+    {
+        Some(signature) => {
+            let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+            hmac::verify(&key, body, &signature).is_ok()
+        }
+        None => false,
+    }
+}
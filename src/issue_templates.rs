@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use telegram_bot::types::User;
+use tera::{Context, Tera};
+
+use super::errors::*;
+use super::fuzzy::Named;
+
+/// Prefilled issue, e.g. bug report with steps/expected/actual sections.
+///
+/// Global templates are read from the JSON file given by `--issue-templates`,
+/// chat specific ones from the `issue_templates:<chat id>` redis key in the same format:
+///
+/// ```json
+/// [{"name": "Bug report", "project": "TP", "fields": {"Type": "Bug"},
+///   "description": "Reported by {{ reporter }} on {{ date }}\n\nSteps:\n\nExpected:\n\nActual:\n"}]
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IssueTemplate {
+    pub name: String,
+    /// Project name or short name
+    #[serde(default)]
+    pub project: Option<String>,
+    /// Custom field values by field name, e.g. Stream or Type
+    #[serde(default)]
+    pub fields: HashMap<String, String>,
+    /// Tera template of the description skeleton
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+pub type IssueTemplates = Vec<IssueTemplate>;
+
+impl Named for IssueTemplate {
+    fn title(&self) -> String {
+        self.name.clone()
+    }
+}
+
+impl IssueTemplate {
+    pub fn load(path: &str) -> Result<IssueTemplates> {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    pub fn field<T: ToString>(&self, name: T) -> Option<&String> {
+        self.fields.get(&name.to_string())
+    }
+
+    pub fn render_description(&self, reporter: &User) -> Result<Option<String>> {
+        match &self.description {
+            Some(description) => {
+                let reporter = match &reporter.last_name {
+                    Some(last_name) => format!("{} {}", reporter.first_name, last_name),
+                    None => reporter.first_name.clone(),
+                };
+                let mut context = Context::new();
+                context.insert("reporter", &reporter);
+                context.insert(
+                    "date",
+                    &chrono::Local::today().format("%Y-%m-%d").to_string(),
+                );
+                match Tera::one_off(description, &context, false) {
+                    Ok(description) => Ok(Some(description)),
+                    Err(e) => bail!(format!("Invalid template {}: {}", self.name, e)),
+                }
+            }
+            None => Ok(None),
+        }
+    }
+}
@@ -14,15 +14,33 @@ extern crate machine;
 extern crate paste;
 
 use futures::StreamExt;
+use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use structopt::StructOpt;
 
+mod backlog;
 mod bot;
+mod burndown;
+mod charts;
+mod chat_config;
 mod commands;
+mod digests;
 mod errors;
+mod estimation;
+mod files;
+mod fuzzy;
+mod hooks;
+mod issue_templates;
 mod models;
+mod notifications;
 mod opts;
+mod prioritization;
+mod scheduler;
+mod standups;
 mod states;
+mod timesheet;
+mod triage;
 mod yt_oauth;
 
 use bot::*;
@@ -42,15 +60,44 @@ async fn main() -> Result<()> {
 
     let rt = tokio::task::LocalSet::new();
     let system = actix_rt::System::run_in_tokio("test", &rt);
-    let srv = yt_oauth::run(bot.clone(), opt.addr).unwrap();
-
-    while let Some(update) = stream.next().await {
-        let update = update?;
-        {
-            let mut bot = bot.lock().unwrap();
-            let res = bot.dispatch_update(update).await;
-            if let Err(e) = res {
-                warn!("Error occured: {}", e);
+    let srv = yt_oauth::run(bot.clone(), opt.addr, opt.hook_secret.clone()).unwrap();
+
+    // Background jobs run between updates, so they never race with the handlers
+    let mut ticks = tokio::time::interval(Duration::from_secs(opt.watch_interval));
+    // Watches are polled one per turn, so updates are not held up by a long queue
+    let mut watch_jobs: VecDeque<(i64, String)> = VecDeque::new();
+
+    loop {
+        tokio::select! {
+            update = stream.next() => {
+                let update = match update {
+                    Some(update) => update?,
+                    None => break,
+                };
+                let mut bot = bot.lock().unwrap();
+                let res = bot.dispatch_update(update).await;
+                if let Err(e) = res {
+                    warn!("Error occured: {}", e);
+                }
+            }
+            _ = ticks.tick() => {
+                let mut bot = bot.lock().unwrap();
+                if watch_jobs.is_empty() {
+                    match bot.watch_jobs() {
+                        Ok(jobs) => watch_jobs.extend(jobs),
+                        Err(e) => warn!("Unable to list watches: {}", e),
+                    }
+                }
+                if let Err(e) = bot.on_tick().await {
+                    warn!("Error occured: {}", e);
+                }
+            }
+            _ = futures::future::ready(()), if !watch_jobs.is_empty() => {
+                let (chat, query) = watch_jobs.pop_front().unwrap();
+                let bot = bot.lock().unwrap();
+                if let Err(e) = bot.poll_watch_job(chat, &query).await {
+                    warn!("Unable to poll {} for chat {}: {}", query, chat, e);
+                }
             }
         }
     }
@@ -1,7 +1,12 @@
+use hyper::{HeaderMap, StatusCode};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use url::form_urlencoded::byte_serialize;
 use youtrack_rs::client::{Executor, YouTrack};
 
 use super::errors::*;
+use super::fuzzy::Named;
 
 #[derive(Serialize, Deserialize)]
 pub struct IssueVoters {
@@ -20,6 +25,1002 @@ pub struct Issue {
 
 pub type Issues = Vec<Issue>;
 
+/// First `top` issues matching the query
+pub async fn search_issues<T>(yt: &YouTrack, query: &str, top: i32, fields: &str) -> Result<Vec<T>>
+where
+    T: Send + Sync + for<'de> Deserialize<'de>,
+{
+    // youtrack-rs passes query parameters as is
+    let query: String = byte_serialize(query.as_bytes()).collect();
+    let issues = yt
+        .get()
+        .issues()
+        .query(&query)
+        .top(top.to_string().as_str())
+        .skip("0")
+        .fields(fields)
+        .execute::<Value>()
+        .await?;
+    check_response(issues, "Unable to fetch issues from youtrack")
+}
+
+/// Checks the response status only, for requests answered with an empty body
+fn check_status(response: &(HeaderMap, StatusCode, Option<Value>), error: &str) -> Result<()> {
+    let (headers, status, value) = response;
+
+    debug!("{:#?}", headers);
+    debug!("{}", status);
+
+    if !status.is_success() {
+        match value
+            .as_ref()
+            .and_then(|value| YoutrackError::deserialize(value).ok())
+        {
+            Some(err) => bail!(err.error_description),
+            None => bail!(error.to_string()),
+        }
+    };
+    Ok(())
+}
+
+/// Parses the response body, failed requests report the YouTrack error description
+fn check_response<T: DeserializeOwned>(
+    response: (HeaderMap, StatusCode, Option<Value>),
+    error: &str,
+) -> Result<T> {
+    check_status(&response, error)?;
+    match response
+        .2
+        .and_then(|value| serde_json::from_value(value).ok())
+    {
+        Some(value) => Ok(value),
+        None => bail!(error.to_string()),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct YouTrackUser {
+    pub id: String,
+    pub login: String,
+    #[serde(rename = "fullName")]
+    pub full_name: Option<String>,
+}
+
+pub type YouTrackUsers = Vec<YouTrackUser>;
+
+impl Named for YouTrackUser {
+    fn title(&self) -> String {
+        self.display_name()
+    }
+
+    fn names(&self) -> Vec<String> {
+        vec![self.display_name(), self.login.clone()]
+    }
+}
+
+impl YouTrackUser {
+    pub async fn me(yt: &YouTrack) -> Result<Self> {
+        let me = yt
+            .get()
+            .users()
+            .me()
+            .fields("id,login,fullName")
+            .execute::<Value>()
+            .await?;
+        check_response(me, "Unable to fetch current user")
+    }
+
+    pub fn display_name(&self) -> String {
+        match &self.full_name {
+            Some(name) if !name.is_empty() => name.clone(),
+            _ => self.login.clone(),
+        }
+    }
+
+    /// Case-insensitive substring match against login and full name
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        self.login.to_lowercase().contains(&query)
+            || self.display_name().to_lowercase().contains(&query)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IssueComment {
+    pub id: String,
+    pub text: Option<String>,
+}
+
+impl IssueComment {
+    pub async fn create(yt: &YouTrack, issue_id: &str, text: &str) -> Result<Self> {
+        let comment = yt
+            .post(json!({ "text": text }))
+            .issues()
+            .id(issue_id)
+            .comments()
+            .fields("id,text")
+            .execute::<Value>()
+            .await?;
+        check_response(comment, "Unable to add comment")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IssueTag {
+    pub id: String,
+    pub name: String,
+}
+
+pub type IssueTags = Vec<IssueTag>;
+
+impl IssueTag {
+    pub async fn list(yt: &YouTrack) -> Result<IssueTags> {
+        let tags = yt
+            .get()
+            .issue_tags()
+            .top("-1")
+            .skip("0")
+            .fields("id,name")
+            .execute::<Value>()
+            .await?;
+        let mut tags = check_response(tags, "Unable to fetch tags from youtrack")?;
+        tags.sort_by_cached_key(|t| t.name.to_lowercase());
+        Ok(tags)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IssueProjectCustomField {
+    pub bundle: Option<Bundle>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IssueCustomField {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub value: Value,
+    #[serde(rename = "projectCustomField")]
+    pub project_field: Option<IssueProjectCustomField>,
+}
+
+fn field_value_name(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Object(_) => ["fullName", "name", "login", "presentation", "text"]
+            .iter()
+            .filter_map(|key| value[key].as_str())
+            .next()
+            .map(|s| s.to_string()),
+        _ => None,
+    }
+}
+
+/// Human readable field value, multiple values are joined with commas
+fn display_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Array(values) => {
+            let names: Vec<String> = values.iter().filter_map(field_value_name).collect();
+            if names.is_empty() {
+                None
+            } else {
+                Some(names.join(", "))
+            }
+        }
+        value => field_value_name(value),
+    }
+}
+
+impl IssueCustomField {
+    pub fn display_value(&self) -> Option<String> {
+        display_value(&self.value)
+    }
+
+    pub fn bundle_id(&self) -> Option<&str> {
+        match &self.project_field {
+            Some(IssueProjectCustomField {
+                bundle: Some(bundle),
+            }) => Some(bundle.id.as_str()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct StateBundleElement {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub archived: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct StateBundle {
+    pub id: String,
+    #[serde(default)]
+    pub values: Vec<StateBundleElement>,
+}
+
+/// Single issue with its custom fields, shown as a message with action buttons
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IssueCard {
+    pub id: String,
+    #[serde(alias = "idReadable")]
+    pub id_readable: String,
+    pub summary: String,
+    pub project: Option<ProjectId>,
+    #[serde(rename = "customFields")]
+    pub custom_fields: Vec<IssueCustomField>,
+}
+
+impl IssueCard {
+    pub async fn get(yt: &YouTrack, issue_id: &str) -> Result<Self> {
+        let card = yt
+            .get()
+            .issues()
+            .id(issue_id)
+            .fields("id,idReadable,summary,project(id),customFields(id,name,value(name,login,fullName,presentation),projectCustomField(bundle(id)))")
+            .execute::<Value>()
+            .await?;
+        check_response(card, "Unable to fetch issue")
+    }
+
+    pub fn field<T: ToString>(&self, name: T) -> Option<&IssueCustomField> {
+        let name = name.to_string();
+        self.custom_fields.iter().find(|f| f.name == name)
+    }
+
+    /// Field names along with values of the fields which are set
+    pub fn field_values(&self) -> Vec<(String, String)> {
+        self.custom_fields
+            .iter()
+            .filter_map(|f| f.display_value().map(|v| (f.name.clone(), v)))
+            .collect()
+    }
+
+    /// States the issue can be moved to from its State bundle
+    pub async fn next_states(&self, yt: &YouTrack) -> Result<Vec<StateBundleElement>> {
+        let field = match self.field("State") {
+            Some(field) => field,
+            None => return Ok(Vec::new()),
+        };
+        let bundle_id = match field.bundle_id() {
+            Some(bundle_id) => bundle_id,
+            None => return Ok(Vec::new()),
+        };
+        let bundle = yt
+            .get()
+            .admin()
+            .custom_field_settings()
+            .bundles()
+            .state()
+            .id(bundle_id)
+            .fields("id,values(id,name,archived)")
+            .execute::<Value>()
+            .await?;
+        let bundle = check_response(bundle, "Unable to fetch issue states")?;
+        let current = field.display_value();
+        Ok(bundle
+            .values
+            .into_iter()
+            .filter(|s| !s.archived && Some(&s.name) != current.as_ref())
+            .collect())
+    }
+
+    /// Values of an enum field like Priority from its bundle
+    pub async fn enum_values(&self, yt: &YouTrack, name: &str) -> Result<BundleElements> {
+        let bundle_id = match self.field(name).and_then(|f| f.bundle_id()) {
+            Some(bundle_id) => bundle_id,
+            None => bail!("{} has no {} field", self.id_readable, name),
+        };
+        let bundle = yt
+            .get()
+            .admin()
+            .custom_field_settings()
+            .bundles()
+            .enum_()
+            .id(bundle_id)
+            .fields("id,values(id,name)")
+            .execute::<Value>()
+            .await?;
+        let bundle = check_response(bundle, "Unable to fetch field values")?;
+        Ok(bundle.values.unwrap_or_default())
+    }
+
+    /// Users a user field like Assignee can be set to
+    pub async fn user_values(&self, yt: &YouTrack, name: &str) -> Result<YouTrackUsers> {
+        match self.field(name).and_then(|f| f.bundle_id()) {
+            Some(bundle_id) => bundle_users(yt, bundle_id).await,
+            None => bail!("{} has no {} field", self.id_readable, name),
+        }
+    }
+
+    pub async fn update_field(
+        yt: &YouTrack,
+        issue_id: &str,
+        field: IssueDraftCustomField,
+    ) -> Result<()> {
+        let res = yt
+            .post(json!({ "customFields": [field] }))
+            .issues()
+            .id(issue_id)
+            .fields("id")
+            .execute::<Value>()
+            .await?;
+        check_response::<Value>(res, "Unable to update issue")?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IssueHeader {
+    #[serde(alias = "idReadable")]
+    pub id_readable: String,
+    pub summary: String,
+    #[serde(default)]
+    pub reporter: Option<YouTrackUser>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ActivityCategory {
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ActivityField {
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Activity {
+    pub id: String,
+    /// Milliseconds since epoch
+    pub timestamp: i64,
+    pub author: YouTrackUser,
+    pub category: ActivityCategory,
+    pub field: Option<ActivityField>,
+    /// Issue or comment the activity is about
+    #[serde(default)]
+    pub target: Value,
+    #[serde(default)]
+    pub added: Value,
+    #[serde(default)]
+    pub removed: Value,
+}
+
+const ACTIVITY_FIELDS: &str = "id,timestamp,author(id,login,fullName),category(id),field(name),\
+     target(idReadable,summary,reporter(id,login,fullName),\
+     issue(idReadable,summary,reporter(id,login,fullName))),\
+     added(name,login,fullName,presentation,text),removed(name,login,fullName,presentation,text)";
+
+// Long comments would not fit into a single message along with other activities
+const ACTIVITY_TEXT_LIMIT: usize = 200;
+// Activities per /history page, a full page of shortened ones fits into a message
+const HISTORY_PAGE_SIZE: usize = 8;
+
+fn shorten(text: String) -> String {
+    if text.chars().count() > ACTIVITY_TEXT_LIMIT {
+        let text: String = text.chars().take(ACTIVITY_TEXT_LIMIT).collect();
+        format!("{}…", text)
+    } else {
+        text
+    }
+}
+
+impl Activity {
+    /// What has been done, e.g. `State: Open → Fixed`
+    pub fn describe(&self) -> String {
+        let added = self.added_text();
+        let removed = display_value(&self.removed).map(shorten);
+        let text = match (self.category.id.as_str(), added, removed) {
+            ("CommentsCategory", Some(text), _) => format!("commented: {}", text),
+            ("CommentsCategory", None, _) => "removed a comment".to_string(),
+            ("AttachmentsCategory", Some(names), _) => format!("attached {}", names),
+            ("AttachmentsCategory", None, Some(names)) => format!("removed {}", names),
+            ("IssueCreatedCategory", _, _) => "created the issue".to_string(),
+            (category, added, removed) => {
+                let field = self
+                    .field
+                    .as_ref()
+                    .and_then(|f| f.name.clone())
+                    .unwrap_or_else(|| category.trim_end_matches("Category").to_string());
+                format!(
+                    "{}: {} → {}",
+                    field,
+                    removed.unwrap_or_else(|| "none".to_string()),
+                    added.unwrap_or_else(|| "none".to_string())
+                )
+            }
+        };
+        shorten(text)
+    }
+
+    pub fn issue(&self) -> Option<IssueHeader> {
+        let target = if self.target.get("idReadable").is_some() {
+            &self.target
+        } else {
+            &self.target["issue"]
+        };
+        serde_json::from_value(target.clone()).ok()
+    }
+
+    pub fn field_name(&self) -> Option<&str> {
+        self.field.as_ref().and_then(|f| f.name.as_deref())
+    }
+
+    /// Added value shortened to fit into a message, e.g. comment text
+    pub fn added_text(&self) -> Option<String> {
+        display_value(&self.added).map(shorten)
+    }
+
+    /// Logins of added users, e.g. new assignee
+    pub fn added_logins(&self) -> Vec<String> {
+        let values = match &self.added {
+            Value::Array(values) => values.clone(),
+            Value::Null => Vec::new(),
+            value => vec![value.clone()],
+        };
+        values
+            .iter()
+            .filter_map(|v| v["login"].as_str())
+            .map(|login| login.to_string())
+            .collect()
+    }
+
+    /// Logins @mentioned in the added comment
+    pub fn mentions(&self) -> Vec<String> {
+        let text = match &self.added {
+            Value::Array(values) => values.iter().filter_map(|v| v["text"].as_str()).collect(),
+            _ => Vec::new(),
+        }
+        .join(" ");
+        let mut logins: Vec<String> = text
+            .split_whitespace()
+            .filter(|word| word.starts_with('@'))
+            .map(|word| {
+                word[1..]
+                    .trim_end_matches(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .to_string()
+            })
+            .filter(|login| !login.is_empty())
+            .collect();
+        logins.sort();
+        logins.dedup();
+        logins
+    }
+
+    pub fn is_added(&self) -> bool {
+        match &self.added {
+            Value::Null => false,
+            Value::Array(values) => !values.is_empty(),
+            _ => true,
+        }
+    }
+
+    /// Whether watchers should be told about it: new issues, state and assignee changes, comments
+    pub fn is_notable(&self) -> bool {
+        match self.category.id.as_str() {
+            "IssueCreatedCategory" => true,
+            "CustomFieldCategory" => match self.field_name() {
+                Some("State") | Some("Assignee") => true,
+                _ => false,
+            },
+            "CommentsCategory" => self.is_added(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ActivityPage {
+    #[serde(default)]
+    pub activities: Vec<Activity>,
+    #[serde(rename = "hasBefore", default)]
+    pub has_before: bool,
+    #[serde(rename = "hasAfter", default)]
+    pub has_after: bool,
+    #[serde(rename = "beforeCursor")]
+    pub before_cursor: Option<String>,
+    #[serde(rename = "afterCursor")]
+    pub after_cursor: Option<String>,
+}
+
+impl ActivityPage {
+    /// Issue activities, newest first
+    pub async fn fetch(yt: &YouTrack, issue_id: &str, cursor: Option<&str>) -> Result<Self> {
+        let fields = format!("activities({}),hasBefore,beforeCursor", ACTIVITY_FIELDS);
+        let page = yt
+            .get()
+            .issues()
+            .id(issue_id)
+            .activities_page()
+            .categories("CustomFieldCategory,CommentsCategory,AttachmentsCategory,SummaryCategory,DescriptionCategory")
+            .reverse("true")
+            .top(&HISTORY_PAGE_SIZE.to_string())
+            .fields(&fields);
+        // With reverse order older activities are before the cursor
+        let page = match cursor {
+            Some(cursor) => page.cursor(cursor),
+            None => page,
+        };
+        let page = page.execute::<Value>().await?;
+        check_response(page, "Unable to fetch issue history")
+    }
+
+    /// All field changes and resolutions of the issue, oldest first
+    pub async fn changes(yt: &YouTrack, issue_id: &str) -> Result<Vec<Activity>> {
+        let mut activities = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page = yt
+                .get()
+                .issues()
+                .id(issue_id)
+                .activities_page()
+                .categories("CustomFieldCategory,IssueResolvedCategory")
+                .reverse("false")
+                .fields("activities(id,timestamp,author(id,login,fullName),category(id),field(name),added(name,minutes),removed(name,minutes)),hasAfter,afterCursor");
+            let page = match &cursor {
+                Some(cursor) => page.cursor(cursor),
+                None => page,
+            };
+            let page = page.execute::<Value>().await?;
+            let page = check_response(page, "Unable to fetch issue history")?;
+            activities.extend(page.activities);
+            match page.after_cursor {
+                Some(after) if page.has_after => cursor = Some(after),
+                _ => return Ok(activities),
+            }
+        }
+    }
+
+    /// Activities of issues matching the query, oldest first, after the cursor or since the timestamp
+    pub async fn watch(
+        yt: &YouTrack,
+        query: &str,
+        cursor: Option<&str>,
+        since: i64,
+    ) -> Result<Self> {
+        let since = since.to_string();
+        // youtrack-rs passes query parameters as is
+        let query: String = byte_serialize(query.as_bytes()).collect();
+        let fields = format!("activities({}),afterCursor", ACTIVITY_FIELDS);
+        let page = yt
+            .get()
+            .activities_page()
+            .categories("IssueCreatedCategory,CustomFieldCategory,CommentsCategory")
+            .reverse("false")
+            .fields(&fields);
+        // Empty query watches all issues
+        let page = if query.is_empty() {
+            page
+        } else {
+            page.issue_query(&query)
+        };
+        let page = match cursor {
+            Some(cursor) => page.cursor(cursor),
+            None => page.start(&since),
+        };
+        let page = page.execute::<Value>().await?;
+        check_response(page, "Unable to fetch activities")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WorkItemType {
+    pub id: String,
+    pub name: String,
+}
+
+pub type WorkItemTypes = Vec<WorkItemType>;
+
+impl Named for WorkItemType {
+    fn title(&self) -> String {
+        self.name.clone()
+    }
+}
+
+impl WorkItemType {
+    /// Work types enabled in the project's time tracking settings
+    pub async fn list(yt: &YouTrack, project_id: &str) -> Result<WorkItemTypes> {
+        let types = yt
+            .get()
+            .admin()
+            .projects()
+            .id(project_id)
+            .time_tracking_settings()
+            .work_item_types()
+            .fields("id,name")
+            .execute::<Value>()
+            .await?;
+        check_response(types, "Unable to fetch work item types")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WorkItemDuration {
+    pub minutes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WorkItemDraft {
+    pub duration: WorkItemDuration,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub work_type: Option<WorkItemType>,
+    /// Milliseconds since epoch
+    pub date: u64,
+}
+
+impl WorkItemDraft {
+    pub fn new(minutes: u64, date: u64) -> Self {
+        Self {
+            duration: WorkItemDuration { minutes },
+            text: None,
+            work_type: None,
+            date,
+        }
+    }
+
+    pub fn text(&mut self, text: Option<String>) -> &mut Self {
+        self.text = text;
+        self
+    }
+
+    pub fn work_type(&mut self, work_type: Option<WorkItemType>) -> &mut Self {
+        self.work_type = work_type;
+        self
+    }
+
+    pub async fn create(&self, yt: &YouTrack, issue_id: &str) -> Result<()> {
+        let res = yt
+            .post(self)
+            .issues()
+            .id(issue_id)
+            .time_tracking()
+            .work_items()
+            .fields("id")
+            .execute::<Value>()
+            .await?;
+        check_response::<Value>(res, "Unable to log work")?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WorkItem {
+    pub id: String,
+    /// Milliseconds since epoch
+    pub date: i64,
+    pub duration: WorkItemDuration,
+    pub text: Option<String>,
+    pub issue: IssueHeader,
+    pub author: YouTrackUser,
+}
+
+pub type WorkItems = Vec<WorkItem>;
+
+impl WorkItem {
+    /// Work items logged between the dates (inclusive), by the author if given
+    pub async fn list(
+        yt: &YouTrack,
+        author: Option<&str>,
+        start_date: &str,
+        end_date: &str,
+    ) -> Result<WorkItems> {
+        let items = yt
+            .get()
+            .work_items()
+            .start_date(start_date)
+            .end_date(end_date)
+            .top("-1")
+            .skip("0")
+            .fields("id,date,duration(minutes),text,issue(idReadable,summary),author(id,login,fullName)");
+        let items = match author {
+            Some(author) => items.author(author),
+            None => items,
+        };
+        let items = items.execute::<Value>().await?;
+        check_response(items, "Unable to fetch work items")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AgileField {
+    #[serde(default)]
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AgileColumn {
+    pub presentation: String,
+    #[serde(rename = "isResolved", default)]
+    pub is_resolved: bool,
+    #[serde(rename = "fieldValues", default)]
+    pub field_values: Vec<AgileField>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AgileColumnSettings {
+    pub field: Option<AgileField>,
+    #[serde(default)]
+    pub columns: Vec<AgileColumn>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SprintIssue {
+    #[serde(default)]
+    pub id: String,
+    #[serde(alias = "idReadable")]
+    pub id_readable: String,
+    pub summary: String,
+    #[serde(default)]
+    pub votes: i32,
+    #[serde(alias = "customFields", default)]
+    pub custom_fields: Vec<IssueCustomField>,
+    /// Milliseconds since epoch, none for unresolved issues
+    #[serde(default)]
+    pub resolved: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Sprint {
+    pub id: String,
+    pub name: String,
+    pub goal: Option<String>,
+    /// Milliseconds since epoch
+    pub start: Option<i64>,
+    pub finish: Option<i64>,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default)]
+    pub issues: Vec<SprintIssue>,
+}
+
+/// Agile board along with its current sprint issues
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Agile {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "currentSprint")]
+    pub current_sprint: Option<Sprint>,
+    #[serde(rename = "columnSettings")]
+    pub column_settings: Option<AgileColumnSettings>,
+    #[serde(rename = "estimationField")]
+    pub estimation_field: Option<AgileField>,
+    #[serde(default)]
+    pub sprints: Vec<Sprint>,
+}
+
+pub type Agiles = Vec<Agile>;
+
+impl Sprint {
+    pub fn contains(&self, issue_id: &str) -> bool {
+        self.issues.iter().any(|i| i.id == issue_id)
+    }
+
+    /// Adds a single issue to the sprint, by its database id
+    pub async fn add_issue(&self, yt: &YouTrack, agile_id: &str, issue_id: &str) -> Result<()> {
+        if self.contains(issue_id) {
+            return Ok(());
+        }
+        let res = yt
+            .post(json!({ "id": issue_id }))
+            .agiles()
+            .id(agile_id)
+            .sprints()
+            .sprint_id(&self.id)
+            .issues()
+            .fields("id")
+            .execute::<Value>()
+            .await?;
+        check_response::<Value>(res, "Unable to add issue to sprint")?;
+        Ok(())
+    }
+
+    /// Removes a single issue from the sprint, other sprint issues are left untouched
+    pub async fn remove_issue(&self, yt: &YouTrack, agile_id: &str, issue_id: &str) -> Result<()> {
+        if !self.contains(issue_id) {
+            return Ok(());
+        }
+        let res = yt
+            .delete()
+            .agiles()
+            .id(agile_id)
+            .sprints()
+            .sprint_id(&self.id)
+            .issues()
+            .issue_id(issue_id)
+            .execute::<Value>()
+            .await?;
+        check_status(&res, "Unable to remove issue from sprint")
+    }
+}
+
+impl Named for Agile {
+    fn title(&self) -> String {
+        self.name.clone()
+    }
+}
+
+impl Agile {
+    pub async fn list(yt: &YouTrack) -> Result<Agiles> {
+        let agiles = yt
+            .get()
+            .agiles()
+            .fields("id,name")
+            .execute::<Value>()
+            .await?;
+        check_response(agiles, "Unable to fetch boards")
+    }
+
+    pub async fn get(yt: &YouTrack, agile_id: &str) -> Result<Self> {
+        let agile = yt
+            .get()
+            .agiles()
+            .id(agile_id)
+            .fields("id,name,currentSprint(id,name,goal,start,finish,issues(idReadable,summary,votes,resolved,customFields(id,name,value(name,minutes)))),columnSettings(field(name),columns(presentation,isResolved,fieldValues(name))),estimationField(name),sprints(id,name,goal,start,finish,archived)")
+            .execute::<Value>()
+            .await?;
+        check_response(agile, "Unable to fetch board")
+    }
+
+    /// All sprints of the board with their issues
+    pub async fn sprints(yt: &YouTrack, agile_id: &str) -> Result<Vec<Sprint>> {
+        let sprints = yt
+            .get()
+            .agiles()
+            .id(agile_id)
+            .sprints()
+            .fields("id,name,goal,start,finish,archived,issues(id,idReadable,summary,resolved,customFields(id,name,value(name,minutes)))")
+            .execute::<Value>()
+            .await?;
+        check_response(sprints, "Unable to fetch sprints")
+    }
+
+    /// Sprint planned right after the current one
+    pub fn next_sprint<'a>(&self, sprints: &'a [Sprint]) -> Option<&'a Sprint> {
+        let after = self
+            .current_sprint
+            .as_ref()
+            .and_then(|s| s.start)
+            .unwrap_or(0);
+        sprints
+            .iter()
+            .filter(|s| !s.archived && s.start.map_or(false, |start| start > after))
+            .min_by_key(|s| s.start)
+    }
+
+    pub fn estimation_field_name(&self) -> Option<&str> {
+        self.estimation_field.as_ref().map(|f| f.name.as_str())
+    }
+
+    pub fn columns(&self) -> &[AgileColumn] {
+        match &self.column_settings {
+            Some(settings) => &settings.columns,
+            None => &[],
+        }
+    }
+
+    /// Index of the board column the issue is shown in
+    pub fn column_of(&self, issue: &SprintIssue) -> Option<usize> {
+        let field = self.column_settings.as_ref()?.field.as_ref()?;
+        let value = issue
+            .custom_fields
+            .iter()
+            .find(|f| f.name == field.name)?
+            .display_value()?;
+        self.columns()
+            .iter()
+            .position(|c| c.field_values.iter().any(|v| v.name == value))
+    }
+
+    /// Current sprint issues shown in the column
+    pub fn column_issues(&self, column: usize) -> Vec<&SprintIssue> {
+        match &self.current_sprint {
+            Some(sprint) => sprint
+                .issues
+                .iter()
+                .filter(|i| self.column_of(i) == Some(column))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ParsedCommand {
+    pub description: Option<String>,
+    pub error: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommandSuggestion {
+    pub option: String,
+    pub description: Option<String>,
+    pub prefix: Option<String>,
+    pub suffix: Option<String>,
+    #[serde(rename = "completionStart")]
+    pub completion_start: usize,
+    #[serde(rename = "completionEnd")]
+    pub completion_end: usize,
+}
+
+impl CommandSuggestion {
+    /// Command query with this suggestion applied
+    pub fn complete(&self, query: &str) -> String {
+        let chars: Vec<char> = query.chars().collect();
+        let start = self.completion_start.min(chars.len());
+        let end = self.completion_end.min(chars.len()).max(start);
+        let head: String = chars[..start].iter().collect();
+        let tail: String = chars[end..].iter().collect();
+        format!(
+            "{}{}{}{}{}",
+            head,
+            self.prefix.clone().unwrap_or_default(),
+            self.option,
+            self.suffix.clone().unwrap_or_default(),
+            tail
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommandAssist {
+    #[serde(default)]
+    pub commands: Vec<ParsedCommand>,
+    #[serde(default)]
+    pub suggestions: Vec<CommandSuggestion>,
+}
+
+fn command_list(issues: &[&str], query: &str) -> Value {
+    let issues: Vec<Value> = issues
+        .iter()
+        .map(|id| json!({ "idReadable": id }))
+        .collect();
+    json!({
+        "query": query,
+        "caret": query.chars().count(),
+        "issues": issues,
+    })
+}
+
+impl CommandAssist {
+    /// Parses command query and suggests completions at its end
+    pub async fn fetch(yt: &YouTrack, issue_id: &str, query: &str) -> Result<Self> {
+        let assist = yt
+            .post(command_list(&[issue_id], query))
+            .commands()
+            .assist()
+            .fields("commands(description,error),suggestions(option,description,prefix,suffix,completionStart,completionEnd)")
+            .execute::<Value>()
+            .await?;
+        check_response(assist, "Unable to parse command")
+    }
+
+    pub async fn apply(yt: &YouTrack, issue_id: &str, query: &str) -> Result<()> {
+        Self::apply_to_all(yt, &[issue_id], query).await
+    }
+
+    /// Applies the command to all the issues in one request
+    pub async fn apply_to_all(yt: &YouTrack, issues: &[&str], query: &str) -> Result<()> {
+        let res = yt
+            .post(command_list(issues, query))
+            .commands()
+            .fields("query")
+            .execute::<Value>()
+            .await?;
+        check_response::<Value>(res, "Unable to apply command")?;
+        Ok(())
+    }
+
+    pub fn is_valid(&self) -> bool {
+        !self.commands.is_empty() && self.commands.iter().all(|c| !c.error)
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct YoutrackError {
     pub error: String,
@@ -54,20 +1055,32 @@ pub struct Bundle {
     pub values: Option<BundleElements>,
 }
 
-impl Bundle {
-    pub fn has_value<T>(&self, name: T) -> bool
-    where
-        T: ToString,
-    {
-        let name = name.to_string();
-        if let Some(values) = &self.values {
-            values.iter().any(|x| x.name == name)
-        } else {
-            false
-        }
+impl Named for BundleElement {
+    fn title(&self) -> String {
+        self.name.clone()
     }
 }
 
+/// Users of a user bundle, sorted by name
+async fn bundle_users(yt: &YouTrack, bundle_id: &str) -> Result<YouTrackUsers> {
+    let users = yt
+        .get()
+        .admin()
+        .custom_field_settings()
+        .bundles()
+        .user()
+        .id(bundle_id)
+        .aggregated_users()
+        .top("-1")
+        .skip("0")
+        .fields("id,login,fullName")
+        .execute::<Value>()
+        .await?;
+    let mut users = check_response(users, "Unable to fetch assignees")?;
+    users.sort_by_cached_key(|u| u.display_name());
+    Ok(users)
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct ProjectCustomField {
     pub id: String,
@@ -95,6 +1108,20 @@ pub struct Project {
 
 pub type Projects = Vec<Project>;
 
+impl Named for Project {
+    fn title(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.id.clone())
+    }
+
+    fn names(&self) -> Vec<String> {
+        let mut names = vec![self.title()];
+        if let Some(short_name) = &self.short_name {
+            names.push(short_name.clone());
+        }
+        names
+    }
+}
+
 impl Project {
     pub async fn list(yt: &YouTrack) -> Result<Projects> {
         let projects = yt
@@ -103,7 +1130,7 @@ impl Project {
             .projects()
             .top("-1")
             .skip("0")
-            .fields("id,name,short_name,fields(id,field(id,name,fieldType(id)),canBeEmpty,ordinal,bundle(id))")
+            .fields("id,name,shortName,fields(id,field(id,name,fieldType(id)),canBeEmpty,ordinal,bundle(id))")
             .execute::<Projects>()
             .await?;
         let (headers, status, projects) = projects;
@@ -167,16 +1194,38 @@ impl Project {
     pub async fn types(&self, yt: &YouTrack) -> Result<Bundle> {
         self.get_bundle(yt, "Type").await
     }
+
+    /// Users from the project's Assignee user bundle
+    pub async fn assignees(&self, yt: &YouTrack) -> Result<YouTrackUsers> {
+        match self.get_project_custom_field("Assignee") {
+            Some(ProjectCustomField {
+                bundle: Some(bundle),
+                ..
+            }) => bundle_users(yt, &bundle.id).await,
+            _ => bail!("Project has no Assignee field"),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
-pub struct IssueDraftCustomFieldValue {
-    pub name: String,
+#[serde(untagged)]
+pub enum IssueDraftCustomFieldValue {
+    Enum { name: String },
+    User { login: String },
+    Id { id: String },
+    Period { presentation: String },
+    Text { text: String },
+    Many(Vec<IssueDraftCustomFieldValue>),
+    Simple(Value),
 }
 
 impl IssueDraftCustomFieldValue {
     pub fn new(name: String) -> Self {
-        Self { name }
+        IssueDraftCustomFieldValue::Enum { name }
+    }
+
+    pub fn user(login: String) -> Self {
+        IssueDraftCustomFieldValue::User { login }
     }
 }
 
@@ -198,6 +1247,102 @@ impl IssueDraftCustomField {
             type_: "SingleEnumIssueCustomField".to_string(),
         }
     }
+
+    pub fn user(id: String, name: String, login: String) -> Self {
+        Self {
+            value: IssueDraftCustomFieldValue::user(login),
+            name,
+            id,
+            type_: "SingleUserIssueCustomField".to_string(),
+        }
+    }
+
+    pub fn state(id: String, name: String, state_id: String) -> Self {
+        Self {
+            value: IssueDraftCustomFieldValue::Id { id: state_id },
+            name,
+            id,
+            type_: "StateIssueCustomField".to_string(),
+        }
+    }
+
+    /// Period like 1w 2d 4h in the YouTrack time tracking format
+    pub fn period(id: String, name: String, presentation: String) -> Self {
+        Self {
+            value: IssueDraftCustomFieldValue::Period { presentation },
+            name,
+            id,
+            type_: "PeriodIssueCustomField".to_string(),
+        }
+    }
+
+    /// Value of the project field given as text, the issue field type follows the
+    /// project field type. Multi-value fields take comma separated values.
+    /// None if the text doesn't fit the field or the field type isn't supported.
+    pub fn for_field(field: &ProjectCustomField, value: &str) -> Option<Self> {
+        let field_type = field.field.field_type.id.as_str();
+        let (kind, multi) = match field_type.find('[') {
+            Some(pos) => (&field_type[..pos], field_type.ends_with("[*]")),
+            None => (field_type, false),
+        };
+        let named = |name: &str| match kind {
+            "user" => IssueDraftCustomFieldValue::user(name.to_string()),
+            _ => IssueDraftCustomFieldValue::new(name.to_string()),
+        };
+        let (value, type_) = match kind {
+            "enum" | "user" | "ownedField" | "version" | "build" if multi => (
+                IssueDraftCustomFieldValue::Many(
+                    value.split(',').map(|v| named(v.trim())).collect(),
+                ),
+                "Multi",
+            ),
+            "enum" | "user" | "ownedField" | "version" | "build" | "state" => {
+                (named(value), "Single")
+            }
+            "period" => (
+                IssueDraftCustomFieldValue::Period {
+                    presentation: value.to_string(),
+                },
+                "",
+            ),
+            "text" => (
+                IssueDraftCustomFieldValue::Text {
+                    text: value.to_string(),
+                },
+                "",
+            ),
+            "string" => (
+                IssueDraftCustomFieldValue::Simple(Value::String(value.to_string())),
+                "",
+            ),
+            "integer" => (
+                IssueDraftCustomFieldValue::Simple(value.parse::<i64>().ok()?.into()),
+                "",
+            ),
+            "float" => (
+                IssueDraftCustomFieldValue::Simple(value.parse::<f64>().ok()?.into()),
+                "",
+            ),
+            _ => return None,
+        };
+        let type_ = match kind {
+            "enum" => format!("{}EnumIssueCustomField", type_),
+            "user" => format!("{}UserIssueCustomField", type_),
+            "ownedField" => format!("{}OwnedIssueCustomField", type_),
+            "version" => format!("{}VersionIssueCustomField", type_),
+            "build" => format!("{}BuildIssueCustomField", type_),
+            "state" => "StateIssueCustomField".to_string(),
+            "period" => "PeriodIssueCustomField".to_string(),
+            "text" => "TextIssueCustomField".to_string(),
+            _ => "SimpleIssueCustomField".to_string(),
+        };
+        Some(Self {
+            value,
+            name: field.field.name.clone(),
+            id: field.id.clone(),
+            type_,
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -207,6 +1352,7 @@ pub struct IssueDraft {
     pub project: Option<ProjectId>,
     #[serde(rename = "customFields")]
     pub custom_fields: Vec<IssueDraftCustomField>,
+    pub tags: IssueTags,
 }
 
 impl IssueDraft {
@@ -216,6 +1362,7 @@ impl IssueDraft {
             description: "".to_string(),
             project: None,
             custom_fields: Vec::new(),
+            tags: Vec::new(),
         }
     }
 
@@ -239,4 +1386,23 @@ impl IssueDraft {
             .push(IssueDraftCustomField::new(id, name, value));
         self
     }
+
+    pub fn field(&mut self, field: IssueDraftCustomField) -> &mut Self {
+        self.custom_fields.push(field);
+        self
+    }
+
+    pub fn assignee(&mut self, id: String, login: String) -> &mut Self {
+        self.custom_fields.push(IssueDraftCustomField::user(
+            id,
+            "Assignee".to_string(),
+            login,
+        ));
+        self
+    }
+
+    pub fn tags(&mut self, tags: IssueTags) -> &mut Self {
+        self.tags = tags;
+        self
+    }
 }
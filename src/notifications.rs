@@ -0,0 +1,212 @@
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+
+use super::states::{Batching, NotificationCategory, NotificationSettings, QuietHours};
+
+impl NotificationCategory {
+    pub fn all() -> Vec<NotificationCategory> {
+        vec![
+            NotificationCategory::Mentions,
+            NotificationCategory::Assignments,
+            NotificationCategory::Reported,
+            NotificationCategory::Watches,
+            NotificationCategory::Digests,
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NotificationCategory::Mentions => "mentions",
+            NotificationCategory::Assignments => "assignments",
+            NotificationCategory::Reported => "reported issues",
+            NotificationCategory::Watches => "watched queries",
+            NotificationCategory::Digests => "digests",
+        }
+    }
+}
+
+impl QuietHours {
+    /// Parses ranges like 22:00-08:00
+    pub fn parse(s: &str) -> Option<QuietHours> {
+        let mut parts = s.splitn(2, '-');
+        let from = NaiveTime::parse_from_str(parts.next()?.trim(), "%H:%M").ok()?;
+        let to = NaiveTime::parse_from_str(parts.next()?.trim(), "%H:%M").ok()?;
+        if from == to {
+            None
+        } else {
+            Some(QuietHours { from, to })
+        }
+    }
+
+    /// Choices offered by the settings button, in the order it cycles through them
+    pub fn presets() -> Vec<QuietHours> {
+        [(22, 8), (23, 7), (21, 9), (0, 8)]
+            .iter()
+            .map(|&(from, to)| QuietHours {
+                from: NaiveTime::from_hms(from, 0, 0),
+                to: NaiveTime::from_hms(to, 0, 0),
+            })
+            .collect()
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.from < self.to {
+            self.from <= time && time < self.to
+        } else {
+            time >= self.from || time < self.to
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{}-{}", self.from.format("%H:%M"), self.to.format("%H:%M"))
+    }
+}
+
+/// First time after `now` the local clock shows `time`
+fn next_local_time(tz: Tz, now: DateTime<Utc>, time: NaiveTime) -> DateTime<Utc> {
+    let local_now = now.with_timezone(&tz);
+    let mut date = local_now.date().naive_local();
+    loop {
+        // Skipped by a DST switch when none
+        if let Some(at) = tz.from_local_datetime(&date.and_time(time)).earliest() {
+            if at > local_now {
+                return at.with_timezone(&Utc);
+            }
+        }
+        date = date.succ();
+    }
+}
+
+impl NotificationSettings {
+    pub fn is_enabled(&self, category: NotificationCategory) -> bool {
+        !self.muted.contains(&category)
+    }
+
+    pub fn toggle(&mut self, category: NotificationCategory) {
+        if self.is_enabled(category) {
+            self.muted.push(category);
+        } else {
+            self.muted.retain(|c| *c != category);
+        }
+    }
+
+    /// Switches quiet hours to the next preset, custom ones are turned off
+    pub fn next_quiet_hours(&mut self) {
+        let presets = QuietHours::presets();
+        self.quiet_hours = match &self.quiet_hours {
+            None => presets.first().cloned(),
+            Some(current) => presets
+                .iter()
+                .position(|p| p == current)
+                .and_then(|i| presets.get(i + 1).cloned()),
+        };
+    }
+
+    pub fn is_quiet(&self, tz: Tz, now: DateTime<Utc>) -> bool {
+        match &self.quiet_hours {
+            Some(quiet) => quiet.contains(now.with_timezone(&tz).time()),
+            None => false,
+        }
+    }
+
+    /// When a notification sent now should be delivered, none means right away
+    pub fn delivery_time(&self, tz: Tz, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match &self.quiet_hours {
+            Some(quiet) if self.is_quiet(tz, now) => Some(next_local_time(tz, now, quiet.to)),
+            _ => match self.batching {
+                Batching::Immediate => None,
+                Batching::Hourly => {
+                    let hour = now.with_minute(0)?.with_second(0)?.with_nanosecond(0)?;
+                    Some(hour + Duration::hours(1))
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quiet(from: u32, to: u32) -> QuietHours {
+        QuietHours {
+            from: NaiveTime::from_hms(from, 0, 0),
+            to: NaiveTime::from_hms(to, 0, 0),
+        }
+    }
+
+    fn settings(quiet_hours: Option<QuietHours>, batching: Batching) -> NotificationSettings {
+        NotificationSettings {
+            muted: Vec::new(),
+            quiet_hours,
+            batching,
+        }
+    }
+
+    #[test]
+    fn parses_quiet_hours() {
+        assert_eq!(QuietHours::parse("22:00-08:00"), Some(quiet(22, 8)));
+        assert_eq!(QuietHours::parse("09:00 - 18:00"), Some(quiet(9, 18)));
+        assert_eq!(QuietHours::parse("22:00-22:00"), None);
+        assert_eq!(QuietHours::parse("22:00"), None);
+        assert_eq!(QuietHours::parse("25:00-08:00"), None);
+    }
+
+    #[test]
+    fn quiet_hours_wrap_over_midnight() {
+        let night = quiet(22, 8);
+        assert!(night.contains(NaiveTime::from_hms(23, 30, 0)));
+        assert!(night.contains(NaiveTime::from_hms(3, 0, 0)));
+        assert!(!night.contains(NaiveTime::from_hms(8, 0, 0)));
+        assert!(!night.contains(NaiveTime::from_hms(12, 0, 0)));
+
+        let day = quiet(9, 18);
+        assert!(day.contains(NaiveTime::from_hms(9, 0, 0)));
+        assert!(!day.contains(NaiveTime::from_hms(18, 0, 0)));
+        assert!(!day.contains(NaiveTime::from_hms(23, 0, 0)));
+    }
+
+    #[test]
+    fn delivers_right_away_outside_quiet_hours() {
+        let now = Utc.ymd(2020, 6, 1).and_hms(12, 0, 0);
+        let settings = settings(Some(quiet(22, 8)), Batching::Immediate);
+        assert_eq!(settings.delivery_time(Tz::UTC, now), None);
+    }
+
+    #[test]
+    fn delays_until_quiet_hours_end() {
+        let settings = settings(Some(quiet(22, 8)), Batching::Hourly);
+        let evening = Utc.ymd(2020, 6, 1).and_hms(23, 0, 0);
+        assert_eq!(
+            settings.delivery_time(Tz::UTC, evening),
+            Some(Utc.ymd(2020, 6, 2).and_hms(8, 0, 0))
+        );
+        let night = Utc.ymd(2020, 6, 2).and_hms(3, 0, 0);
+        assert_eq!(
+            settings.delivery_time(Tz::UTC, night),
+            Some(Utc.ymd(2020, 6, 2).and_hms(8, 0, 0))
+        );
+    }
+
+    #[test]
+    fn quiet_hours_follow_the_time_zone() {
+        // 20:00 UTC is 23:00 in Moscow
+        let now = Utc.ymd(2020, 6, 1).and_hms(20, 0, 0);
+        let settings = settings(Some(quiet(22, 8)), Batching::Immediate);
+        assert!(settings.is_quiet(chrono_tz::Europe::Moscow, now));
+        assert_eq!(
+            settings.delivery_time(chrono_tz::Europe::Moscow, now),
+            Some(Utc.ymd(2020, 6, 2).and_hms(5, 0, 0))
+        );
+    }
+
+    #[test]
+    fn batches_until_the_next_hour() {
+        let now = Utc.ymd(2020, 6, 1).and_hms(12, 34, 56);
+        let settings = settings(None, Batching::Hourly);
+        assert_eq!(
+            settings.delivery_time(Tz::UTC, now),
+            Some(Utc.ymd(2020, 6, 1).and_hms(13, 0, 0))
+        );
+    }
+}
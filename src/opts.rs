@@ -10,6 +10,7 @@ use url::Url;
 use youtrack_rs::client::YouTrack;
 
 use super::errors::*;
+use super::files::{HttpClient, ProxyConnector};
 
 #[derive(StructOpt, Debug, Clone)]
 #[structopt(name = "truepositive-assistant")]
@@ -44,31 +45,56 @@ pub struct BotOpt {
     #[structopt(long, env = "REDIS_URL")]
     pub redis_url: String,
 
+    /// JSON file with issue templates offered by /new_issue
+    #[structopt(long, env = "ISSUE_TEMPLATES")]
+    pub issue_templates: Option<String>,
+
+    /// Seconds of inactivity after which an unfinished issue draft is discarded
+    #[structopt(default_value = "3600", long, env = "WIZARD_TIMEOUT")]
+    pub wizard_timeout: u64,
+
+    /// Seconds to keep user state in redis
+    #[structopt(default_value = "604800", long, env = "STATE_TTL")]
+    pub state_ttl: usize,
+
+    /// Seconds between polls of queries watched with /watch
+    #[structopt(default_value = "60", long, env = "WATCH_INTERVAL")]
+    pub watch_interval: u64,
+
+    /// Shared secret of the /hooks/youtrack endpoint, which is disabled without it
+    #[structopt(long, env = "HOOK_SECRET")]
+    pub hook_secret: Option<String>,
+
     #[structopt(default_value = "0.0.0.0:5000", long, env = "BIND_ADDR")]
     pub addr: String,
 }
 
 impl BotOpt {
+    fn proxy_connector(&self) -> Option<ProxyConnector> {
+        self.socks5_proxy.as_ref().map(|socks5_proxy| {
+            let auth = {
+                let url = Url::parse(&socks5_proxy).expect("Invalid proxy url");
+                let username = url.username();
+                if let Some(password) = url.password() {
+                    Some(Auth::new(username, password))
+                } else {
+                    None
+                }
+            };
+
+            let connector = HttpsConnector::new();
+            let proxy = SocksConnector {
+                proxy_addr: socks5_proxy.parse().expect("Could not parse proxy url"),
+                auth,
+                connector,
+            };
+            proxy.with_tls().unwrap()
+        })
+    }
+
     pub fn telegram_api(&self) -> Api {
-        match &self.socks5_proxy {
-            Some(socks5_proxy) => {
-                let auth = {
-                    let url = Url::parse(&socks5_proxy).expect("Invalid proxy url");
-                    let username = url.username();
-                    if let Some(password) = url.password() {
-                        Some(Auth::new(username, password))
-                    } else {
-                        None
-                    }
-                };
-
-                let connector = HttpsConnector::new();
-                let proxy = SocksConnector {
-                    proxy_addr: socks5_proxy.parse().expect("Could not parse proxy url"),
-                    auth,
-                    connector,
-                };
-                let proxy = proxy.with_tls().unwrap();
+        match self.proxy_connector() {
+            Some(proxy) => {
                 let connector = Box::new(HyperConnector::new(Client::builder().build(proxy)));
                 Api::with_connector(self.telegram_token.clone(), connector)
             }
@@ -76,6 +102,14 @@ impl BotOpt {
         }
     }
 
+    /// Client for Telegram file downloads
+    pub fn telegram_client(&self) -> HttpClient {
+        match self.proxy_connector() {
+            Some(proxy) => HttpClient::Proxy(Client::builder().build(proxy)),
+            None => HttpClient::Direct(Client::builder().build(HttpsConnector::new())),
+        }
+    }
+
     pub fn youtrack_api(&self) -> Result<YouTrack> {
         YouTrack::new(self.youtrack_url.clone(), self.youtrack_token.clone()).map_err(|e| e.into())
     }
@@ -0,0 +1,67 @@
+use super::models::IssueHeader;
+use super::states::PrioritySession;
+
+/// Telegram polls have 2 to 10 options
+pub const MIN_OPTIONS: usize = 2;
+pub const MAX_OPTIONS: usize = 10;
+pub const DEFAULT_OPTIONS: usize = 5;
+
+/// Seconds a closed poll is kept to apply its result as votes
+pub const SESSION_TTL: usize = 7 * 24 * 3600;
+
+/// Poll option text, options are limited to 100 characters
+pub fn poll_option(issue: &IssueHeader) -> String {
+    let text = format!("{} {}", issue.id_readable, issue.summary);
+    if text.chars().count() > 100 {
+        format!("{}…", text.chars().take(99).collect::<String>())
+    } else {
+        text
+    }
+}
+
+impl PrioritySession {
+    pub fn key(&self) -> String {
+        format!("poll:{}", self.poll)
+    }
+
+    /// Replaces the participant choice, retracted votes come with no options
+    pub fn answer(&mut self, user: i64, options: Vec<usize>) {
+        let options: Vec<usize> = options
+            .into_iter()
+            .filter(|o| *o < self.issues.len())
+            .collect();
+        if options.is_empty() {
+            self.answers.remove(&user);
+        } else {
+            self.answers.insert(user, options);
+        }
+    }
+
+    /// Issues with their vote counts, most voted first, ties keep the query order
+    pub fn ranking(&self) -> Vec<(IssueHeader, usize)> {
+        let mut ranking: Vec<(IssueHeader, usize)> = self
+            .issues
+            .iter()
+            .enumerate()
+            .map(|(i, issue)| {
+                let votes = self.answers.values().filter(|a| a.contains(&i)).count();
+                (issue.clone(), votes)
+            })
+            .collect();
+        ranking.sort_by(|a, b| b.1.cmp(&a.1));
+        ranking
+    }
+
+    /// Issue ids the participant voted for
+    pub fn choice(&self, user: i64) -> Vec<String> {
+        self.answers
+            .get(&user)
+            .map(|options| {
+                options
+                    .iter()
+                    .map(|o| self.issues[*o].id_readable.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,22 @@
+use redis::Commands;
+
+use super::errors::*;
+
+// Sorted set of job names scored by the unix time they are due at
+const SCHEDULE_KEY: &str = "schedule";
+
+/// Schedules the job to run at the given unix time, replacing earlier schedule of it
+pub fn schedule(con: &mut redis::Connection, job: &str, at: i64) -> Result<()> {
+    let _: () = con.zadd(SCHEDULE_KEY, job, at)?;
+    Ok(())
+}
+
+pub fn cancel(con: &mut redis::Connection, job: &str) -> Result<()> {
+    let _: () = con.zrem(SCHEDULE_KEY, job)?;
+    Ok(())
+}
+
+/// Jobs due by the given unix time, including ones missed while the bot was down
+pub fn due(con: &mut redis::Connection, now: i64) -> Result<Vec<String>> {
+    Ok(con.zrangebyscore(SCHEDULE_KEY, "-inf", now)?)
+}
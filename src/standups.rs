@@ -0,0 +1,94 @@
+use chrono::{DateTime, Datelike, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+
+use super::states::Standup;
+
+pub const QUESTIONS: [&str; 3] = [
+    "What did you do yesterday?",
+    "What are you going to do today?",
+    "Is anything blocking you?",
+];
+
+// Seconds members have to answer before the summary is posted without them
+pub const ANSWER_WINDOW: i64 = 2 * 3600;
+
+impl Standup {
+    pub fn job(&self) -> String {
+        format!("standup:{}", self.chat)
+    }
+
+    pub fn summary_job(&self) -> String {
+        format!("standup_summary:{}", self.chat)
+    }
+
+    /// First weekday time after `now` the stand-up starts at, in the chat time zone
+    pub fn next_run(&self, tz: Tz, now: DateTime<Utc>) -> DateTime<Utc> {
+        let local_now = now.with_timezone(&tz);
+        let mut date = local_now.date().naive_local();
+        loop {
+            let weekend = date.weekday() == Weekday::Sat || date.weekday() == Weekday::Sun;
+            if !weekend {
+                // Skipped by a DST switch when none
+                if let Some(run) = tz.from_local_datetime(&date.and_time(self.time)).earliest() {
+                    if run > local_now {
+                        return run.with_timezone(&Utc);
+                    }
+                }
+            }
+            date = date.succ();
+        }
+    }
+
+    pub fn is_member(&self, id: i64) -> bool {
+        self.members.iter().any(|m| m.id == id)
+    }
+}
+
+/// Issue ids like `TP-123` mentioned in the text, in order of appearance
+pub fn issue_ids(text: &str) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::new();
+    for word in text.split(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_')) {
+        let mut parts = word.rsplitn(2, '-');
+        let (number, project) = match (parts.next(), parts.next()) {
+            (Some(number), Some(project)) => (number, project),
+            _ => continue,
+        };
+        let valid = !number.is_empty()
+            && number.chars().all(|c| c.is_ascii_digit())
+            && project.starts_with(|c: char| c.is_ascii_uppercase())
+            && project
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+        if valid && !ids.iter().any(|id| id == word) {
+            ids.push(word.to_string());
+        }
+    }
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_issue_ids_in_order() {
+        assert_eq!(
+            issue_ids("Fixed TP-12, reviewing WEB_2-7 now. Blocked by TP-3."),
+            vec!["TP-12", "WEB_2-7", "TP-3"]
+        );
+    }
+
+    #[test]
+    fn skips_repeated_ids() {
+        assert_eq!(
+            issue_ids("TP-1 and again TP-1 then TP-2"),
+            vec!["TP-1", "TP-2"]
+        );
+    }
+
+    #[test]
+    fn ignores_words_that_are_not_ids() {
+        assert!(issue_ids("tp-1 TP- -12 TP-1a 2TP-3 up-to-date").is_empty());
+        assert!(issue_ids("").is_empty());
+    }
+}
@@ -1,17 +1,9 @@
-use crate::commands::BacklogParams;
-
+use chrono::{NaiveTime, Weekday};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-use crate::models::Project;
-
-#[derive(Clone, Debug, PartialEq)]
-pub struct StartBacklog(pub BacklogParams);
-
-#[derive(Clone, Debug, PartialEq)]
-pub struct BacklogPage(pub BacklogParams);
-
-#[derive(Clone, Debug, PartialEq)]
-pub struct StopBacklog;
+use crate::issue_templates::IssueTemplate;
+use crate::models::{IssueHeader, IssueTag, IssueTags, Project, YouTrackUser};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Save;
@@ -42,10 +34,13 @@ macro_rules! on_noop {
 pub struct CreateNewIssue;
 
 #[derive(Clone, Debug, PartialEq)]
-pub struct IssueSummary(pub String);
+pub struct IssueTemplateSelected(pub IssueTemplate);
 
 #[derive(Clone, Debug, PartialEq)]
-pub struct IssueSummaryProject(pub String, pub Project);
+pub struct IssueSummary(pub Option<IssueTemplate>, pub String);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IssueSummaryProject(pub Option<IssueTemplate>, pub String, pub Project);
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct IssueStream(pub String, pub String);
@@ -53,112 +48,246 @@ pub struct IssueStream(pub String, pub String);
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct IssueType(pub String, pub String);
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IssueAssignee(pub String, pub YouTrackUser);
+
 #[derive(Clone, Debug, PartialEq)]
-pub struct IssueSummaryProjectStream(pub String, pub Project, pub IssueStream);
+pub struct IssueTagsSelection(pub IssueTags);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IssueSummaryProjectStream(
+    pub Option<IssueTemplate>,
+    pub String,
+    pub Project,
+    pub IssueStream,
+);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IssueSummaryProjectStreamType(
+    pub Option<IssueTemplate>,
+    pub String,
+    pub Project,
+    pub IssueStream,
+    pub IssueType,
+);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IssueSummaryProjectStreamTypeAssignee(
+    pub Option<IssueTemplate>,
+    pub String,
+    pub Project,
+    pub IssueStream,
+    pub IssueType,
+    pub Option<IssueAssignee>,
+);
 
 #[derive(Clone, Debug, PartialEq)]
-pub struct IssueSummaryProjectStreamType(pub String, pub Project, pub IssueStream, pub IssueType);
+pub struct IssueSummaryProjectStreamTypeAssigneeTags(
+    pub Option<IssueTemplate>,
+    pub String,
+    pub Project,
+    pub IssueStream,
+    pub IssueType,
+    pub Option<IssueAssignee>,
+    pub IssueTags,
+);
 
 #[derive(Clone, Debug, PartialEq)]
-pub struct IssueSummaryProjectStreamTypeDesc(
+pub struct IssueSummaryProjectStreamTypeAssigneeTagsDesc(
+    pub Option<IssueTemplate>,
     pub String,
     pub Project,
     pub IssueStream,
     pub IssueType,
+    pub Option<IssueAssignee>,
+    pub IssueTags,
     pub String,
 );
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct StartCommand(pub String, pub String, pub Vec<String>);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandQuery(pub String);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StopCommand;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StartStandup(pub i64);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StandupAnswer(pub String);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StandupDone;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StartDuplicate(pub String, pub i64);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DuplicateDone;
+
 machine!(
     #[derive(Clone, Debug, Deserialize, Serialize)]
     enum UserState {
         Idle,
-        InBacklog {
-            pub top: i32,
-            pub skip: i32,
+        NewIssue {
+            pub template: Option<IssueTemplate>,
         },
-        NewIssue,
         NewIssueSummary {
+            pub template: Option<IssueTemplate>,
             pub summary: String,
         },
         NewIssueSummaryProject {
+            pub template: Option<IssueTemplate>,
             pub summary: String,
             pub project: Project,
         },
         NewIssueSummaryProjectStream {
+            pub template: Option<IssueTemplate>,
             pub summary: String,
             pub project: Project,
             pub stream: IssueStream,
         },
         NewIssueSummaryProjectStreamType {
+            pub template: Option<IssueTemplate>,
             pub summary: String,
             pub project: Project,
             pub stream: IssueStream,
             pub issue_type: IssueType,
         },
-        NewIssueSummaryProjectStreamTypeDesc {
+        NewIssueSummaryProjectStreamTypeAssignee {
+            pub template: Option<IssueTemplate>,
             pub summary: String,
             pub project: Project,
             pub stream: IssueStream,
             pub issue_type: IssueType,
+            pub assignee: Option<IssueAssignee>,
+            pub tags: IssueTags,
+        },
+        NewIssueSummaryProjectStreamTypeAssigneeTags {
+            pub template: Option<IssueTemplate>,
+            pub summary: String,
+            pub project: Project,
+            pub stream: IssueStream,
+            pub issue_type: IssueType,
+            pub assignee: Option<IssueAssignee>,
+            pub tags: IssueTags,
+        },
+        NewIssueSummaryProjectStreamTypeAssigneeTagsDesc {
+            pub template: Option<IssueTemplate>,
+            pub summary: String,
+            pub project: Project,
+            pub stream: IssueStream,
+            pub issue_type: IssueType,
+            pub assignee: Option<IssueAssignee>,
+            pub tags: IssueTags,
             pub desc: String,
         },
+        ComposingCommand {
+            pub issue: String,
+            pub query: String,
+            /// Backlog selection the command goes to, the issue is one of them.
+            /// Added after the first composer states were stored
+            #[serde(default)]
+            pub selection: Vec<String>,
+        },
+        AnsweringStandup {
+            pub chat: i64,
+            pub answers: Vec<String>,
+        },
+        MarkingDuplicate {
+            pub issue: String,
+            /// Triage message to advance once the issue is marked
+            pub triage: i64,
+        },
     }
 );
 
 transitions!(UserState, [
-    (Idle, StartBacklog) => InBacklog,
-    (InBacklog, StopBacklog) => Idle,
-    (InBacklog, BacklogPage) => InBacklog,
     (Idle, Noop) => Idle,
     (Idle, CreateNewIssue) => NewIssue,
+    (NewIssue, IssueTemplateSelected) => NewIssue,
     (NewIssue, IssueSummary) => NewIssueSummary,
+    (NewIssue, IssueSummaryProject) => NewIssueSummaryProject,
+    (NewIssue, IssueSummaryProjectStream) => NewIssueSummaryProjectStream,
+    (NewIssue, IssueSummaryProjectStreamType) => NewIssueSummaryProjectStreamType,
+    (NewIssue, IssueSummaryProjectStreamTypeAssignee) => NewIssueSummaryProjectStreamTypeAssignee,
     (NewIssue, Cancel) => Idle,
     (NewIssue, Noop) => NewIssue,
     (NewIssueSummary, IssueSummaryProject) => NewIssueSummaryProject,
+    (NewIssueSummary, IssueSummaryProjectStream) => NewIssueSummaryProjectStream,
+    (NewIssueSummary, IssueSummaryProjectStreamType) => NewIssueSummaryProjectStreamType,
+    (NewIssueSummary, IssueSummaryProjectStreamTypeAssignee) => NewIssueSummaryProjectStreamTypeAssignee,
     (NewIssueSummary, Cancel) => Idle,
     (NewIssueSummary, Noop) => NewIssueSummary,
     (NewIssueSummaryProject, IssueSummaryProjectStream) => NewIssueSummaryProjectStream,
+    (NewIssueSummaryProject, IssueSummaryProjectStreamType) => NewIssueSummaryProjectStreamType,
+    (NewIssueSummaryProject, IssueSummaryProjectStreamTypeAssignee) => NewIssueSummaryProjectStreamTypeAssignee,
     (NewIssueSummaryProject, Cancel) => Idle,
     (NewIssueSummaryProject, Noop) => NewIssueSummaryProject,
     (NewIssueSummaryProjectStream, IssueSummaryProjectStreamType) => NewIssueSummaryProjectStreamType,
+    (NewIssueSummaryProjectStream, IssueSummaryProjectStreamTypeAssignee) => NewIssueSummaryProjectStreamTypeAssignee,
     (NewIssueSummaryProjectStream, Cancel) => Idle,
     (NewIssueSummaryProjectStream, Noop) => NewIssueSummaryProjectStream,
-    (NewIssueSummaryProjectStreamType, IssueSummaryProjectStreamTypeDesc) => NewIssueSummaryProjectStreamTypeDesc,
+    (NewIssueSummaryProjectStreamType, IssueSummaryProjectStreamTypeAssignee) => NewIssueSummaryProjectStreamTypeAssignee,
     (NewIssueSummaryProjectStreamType, Cancel) => Idle,
     (NewIssueSummaryProjectStreamType, Noop) => NewIssueSummaryProjectStreamType,
-    (NewIssueSummaryProjectStreamTypeDesc, Save) => Idle,
-    (NewIssueSummaryProjectStreamTypeDesc, Cancel) => Idle,
-    (NewIssueSummaryProjectStreamTypeDesc, Noop) => NewIssueSummaryProjectStreamTypeDesc
+    (NewIssueSummaryProjectStreamTypeAssignee, IssueTagsSelection) => NewIssueSummaryProjectStreamTypeAssignee,
+    (NewIssueSummaryProjectStreamTypeAssignee, IssueSummaryProjectStreamTypeAssigneeTags) => NewIssueSummaryProjectStreamTypeAssigneeTags,
+    (NewIssueSummaryProjectStreamTypeAssignee, Cancel) => Idle,
+    (NewIssueSummaryProjectStreamTypeAssignee, Noop) => NewIssueSummaryProjectStreamTypeAssignee,
+    (NewIssueSummaryProjectStreamTypeAssigneeTags, IssueSummaryProjectStreamTypeAssigneeTagsDesc) => NewIssueSummaryProjectStreamTypeAssigneeTagsDesc,
+    (NewIssueSummaryProjectStreamTypeAssigneeTags, Cancel) => Idle,
+    (NewIssueSummaryProjectStreamTypeAssigneeTags, Noop) => NewIssueSummaryProjectStreamTypeAssigneeTags,
+    (NewIssueSummaryProjectStreamTypeAssigneeTagsDesc, Save) => Idle,
+    (NewIssueSummaryProjectStreamTypeAssigneeTagsDesc, Cancel) => Idle,
+    (NewIssueSummaryProjectStreamTypeAssigneeTagsDesc, Noop) => NewIssueSummaryProjectStreamTypeAssigneeTagsDesc,
+    (Idle, StartCommand) => ComposingCommand,
+    (ComposingCommand, CommandQuery) => ComposingCommand,
+    (ComposingCommand, StopCommand) => Idle,
+    (ComposingCommand, Cancel) => Idle,
+    (ComposingCommand, Noop) => ComposingCommand,
+    (Idle, StartStandup) => AnsweringStandup,
+    (AnsweringStandup, StandupAnswer) => AnsweringStandup,
+    (AnsweringStandup, StandupDone) => Idle,
+    (AnsweringStandup, Cancel) => Idle,
+    (AnsweringStandup, Noop) => AnsweringStandup,
+    (Idle, StartDuplicate) => MarkingDuplicate,
+    (MarkingDuplicate, DuplicateDone) => Idle,
+    (MarkingDuplicate, Cancel) => Idle,
+    (MarkingDuplicate, Noop) => MarkingDuplicate
 ]);
 
 impl Idle {
-    pub fn on_start_backlog(&self, m: StartBacklog) -> InBacklog {
-        let StartBacklog(p) = m;
-        InBacklog {
-            top: p.top,
-            skip: p.skip,
-        }
-    }
-
     pub fn on_create_new_issue(&self, _: CreateNewIssue) -> NewIssue {
-        NewIssue {}
+        NewIssue { template: None }
     }
 
-    on_noop!();
-}
-
-impl InBacklog {
-    pub fn on_stop_backlog(&self, _: StopBacklog) -> Idle {
-        Idle {}
+    pub fn on_start_command(&self, m: StartCommand) -> ComposingCommand {
+        let StartCommand(issue, query, selection) = m;
+        ComposingCommand {
+            issue,
+            query,
+            selection,
+        }
     }
 
-    pub fn on_backlog_page(&self, p: BacklogPage) -> InBacklog {
-        let BacklogPage(p) = p;
-        InBacklog {
-            top: p.top,
-            skip: p.skip,
+    pub fn on_start_standup(&self, m: StartStandup) -> AnsweringStandup {
+        let StartStandup(chat) = m;
+        AnsweringStandup {
+            chat,
+            answers: Vec::new(),
         }
     }
+
+    pub fn on_start_duplicate(&self, m: StartDuplicate) -> MarkingDuplicate {
+        let StartDuplicate(issue, triage) = m;
+        MarkingDuplicate { issue, triage }
+    }
+
+    on_noop!();
 }
 
 macro_rules! on_issue_message {
@@ -172,6 +301,34 @@ macro_rules! on_issue_message {
     };
 }
 
+// Tag selection starts empty, so this one can't be generated by on_issue_message!
+macro_rules! on_issue_assignee {
+    () => {
+        pub fn on_issue_summary_project_stream_type_assignee(
+            &self,
+            m: IssueSummaryProjectStreamTypeAssignee,
+        ) -> NewIssueSummaryProjectStreamTypeAssignee {
+            let IssueSummaryProjectStreamTypeAssignee(
+                template,
+                summary,
+                project,
+                stream,
+                issue_type,
+                assignee,
+            ) = m;
+            NewIssueSummaryProjectStreamTypeAssignee {
+                template,
+                summary,
+                project,
+                stream,
+                issue_type,
+                assignee,
+                tags: Vec::new(),
+            }
+        }
+    };
+}
+
 macro_rules! make_forward {
     ($msg:tt, $n:ident, $t:ty) => {
         pub fn $n(&self, $n: $t) -> UserStateMessages {
@@ -204,43 +361,169 @@ macro_rules! impl_new_issue_state {
     };
 }
 
-impl_new_issue_state!(NewIssue, IssueSummary, summary, String);
+impl_new_issue_state!(NewIssue, IssueSummary, summary, String, template);
+
+// Values prefilled by issue template let the wizard skip steps
+impl NewIssue {
+    pub fn on_issue_template_selected(&self, m: IssueTemplateSelected) -> NewIssue {
+        let IssueTemplateSelected(template) = m;
+        NewIssue {
+            template: Some(template),
+        }
+    }
+
+    on_issue_message!(IssueSummaryProject, template, summary, project);
+    on_issue_message!(
+        IssueSummaryProjectStream,
+        template,
+        summary,
+        project,
+        stream
+    );
+    on_issue_message!(
+        IssueSummaryProjectStreamType,
+        template,
+        summary,
+        project,
+        stream,
+        issue_type
+    );
+    on_issue_assignee!();
+}
+
 impl_new_issue_state!(
     NewIssueSummary,
     IssueSummaryProject,
     project,
     Project,
+    template,
     summary
 );
+
+impl NewIssueSummary {
+    on_issue_message!(
+        IssueSummaryProjectStream,
+        template,
+        summary,
+        project,
+        stream
+    );
+    on_issue_message!(
+        IssueSummaryProjectStreamType,
+        template,
+        summary,
+        project,
+        stream,
+        issue_type
+    );
+    on_issue_assignee!();
+}
+
 impl_new_issue_state!(
     NewIssueSummaryProject,
     IssueSummaryProjectStream,
     stream,
     IssueStream,
+    template,
     summary,
     project
 );
+
+impl NewIssueSummaryProject {
+    on_issue_message!(
+        IssueSummaryProjectStreamType,
+        template,
+        summary,
+        project,
+        stream,
+        issue_type
+    );
+    on_issue_assignee!();
+}
+
 impl_new_issue_state!(
     NewIssueSummaryProjectStream,
     IssueSummaryProjectStreamType,
     issue_type,
     IssueType,
+    template,
     summary,
     project,
     stream
 );
+
+// Projects without an Assignee field skip the assignee step
+impl NewIssueSummaryProjectStream {
+    on_issue_assignee!();
+}
+
+impl NewIssueSummaryProjectStreamType {
+    on_issue_assignee!();
+
+    on_cancel!();
+    on_noop!();
+    make_forward!(
+        IssueSummaryProjectStreamTypeAssignee,
+        assignee,
+        Option<IssueAssignee>,
+        template,
+        summary,
+        project,
+        stream,
+        issue_type
+    );
+}
+
 impl_new_issue_state!(
-    NewIssueSummaryProjectStreamType,
-    IssueSummaryProjectStreamTypeDesc,
+    NewIssueSummaryProjectStreamTypeAssignee,
+    IssueSummaryProjectStreamTypeAssigneeTags,
+    tags,
+    IssueTags,
+    template,
+    summary,
+    project,
+    stream,
+    issue_type,
+    assignee
+);
+
+impl NewIssueSummaryProjectStreamTypeAssignee {
+    pub fn on_issue_tags_selection(&self, m: IssueTagsSelection) -> Self {
+        let IssueTagsSelection(tags) = m;
+        Self {
+            tags,
+            ..self.clone()
+        }
+    }
+
+    /// Selection with the given tag toggled
+    pub fn toggle_tag(&self, tag: IssueTag) -> IssueTagsSelection {
+        let mut tags = self.tags.clone();
+        match tags.iter().position(|t| t.id == tag.id) {
+            Some(pos) => {
+                tags.remove(pos);
+            }
+            None => tags.push(tag),
+        }
+        IssueTagsSelection(tags)
+    }
+}
+
+impl_new_issue_state!(
+    NewIssueSummaryProjectStreamTypeAssigneeTags,
+    IssueSummaryProjectStreamTypeAssigneeTagsDesc,
     desc,
     String,
+    template,
     summary,
     project,
     stream,
-    issue_type
+    issue_type,
+    assignee,
+    tags
 );
 
-impl NewIssueSummaryProjectStreamTypeDesc {
+impl NewIssueSummaryProjectStreamTypeAssigneeTagsDesc {
     pub fn on_save(&self, _: Save) -> Idle {
         Idle {}
     }
@@ -249,24 +532,391 @@ impl NewIssueSummaryProjectStreamTypeDesc {
     on_noop!();
 }
 
-impl redis::FromRedisValue for UserState {
-    fn from_redis_value(v: &redis::Value) -> redis::RedisResult<Self> {
-        match v {
-            redis::Value::Status(ref s) => serde_json::from_str(s)
-                .map_err(|_| (redis::ErrorKind::TypeError, "Unable to parse value").into()),
-            redis::Value::Data(ref bytes) => serde_json::from_slice(bytes)
-                .map_err(|_| (redis::ErrorKind::TypeError, "Unable to parse value").into()),
-            _ => Err((redis::ErrorKind::TypeError, "Unable to parse value").into()),
+impl ComposingCommand {
+    /// What the command is applied to, as shown to the user
+    pub fn target(&self) -> String {
+        if self.selection.is_empty() {
+            self.issue.clone()
+        } else {
+            self.selection.join(", ")
+        }
+    }
+
+    pub fn on_command_query(&self, m: CommandQuery) -> Self {
+        let CommandQuery(query) = m;
+        Self {
+            query,
+            ..self.clone()
+        }
+    }
+
+    pub fn on_stop_command(&self, _: StopCommand) -> Idle {
+        Idle {}
+    }
+
+    on_cancel!();
+    on_noop!();
+}
+
+impl AnsweringStandup {
+    pub fn on_standup_answer(&self, m: StandupAnswer) -> Self {
+        let StandupAnswer(answer) = m;
+        let mut answers = self.answers.clone();
+        answers.push(answer);
+        Self {
+            answers,
+            ..self.clone()
+        }
+    }
+
+    pub fn on_standup_done(&self, _: StandupDone) -> Idle {
+        Idle {}
+    }
+
+    on_cancel!();
+    on_noop!();
+}
+
+impl MarkingDuplicate {
+    pub fn on_duplicate_done(&self, _: DuplicateDone) -> Idle {
+        Idle {}
+    }
+
+    on_cancel!();
+    on_noop!();
+}
+
+impl UserState {
+    /// Whether the user is in the middle of the new issue wizard
+    pub fn is_issue_draft(&self) -> bool {
+        match self {
+            UserState::NewIssue(_)
+            | UserState::NewIssueSummary(_)
+            | UserState::NewIssueSummaryProject(_)
+            | UserState::NewIssueSummaryProjectStream(_)
+            | UserState::NewIssueSummaryProjectStreamType(_)
+            | UserState::NewIssueSummaryProjectStreamTypeAssignee(_)
+            | UserState::NewIssueSummaryProjectStreamTypeAssigneeTags(_)
+            | UserState::NewIssueSummaryProjectStreamTypeAssigneeTagsDesc(_) => true,
+            // Stand-up answers have their own deadline
+            UserState::Idle(_)
+            | UserState::ComposingCommand(_)
+            | UserState::AnsweringStandup(_)
+            | UserState::MarkingDuplicate(_)
+            | UserState::Error => false,
+        }
+    }
+}
+
+/// User state along with the time of the last user activity
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StoredUserState {
+    pub state: UserState,
+    pub updated_at: u64,
+}
+
+/// Work timer started by /timer, kept apart from the conversation state
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RunningTimer {
+    pub issue: String,
+    pub started_at: u64,
+}
+
+/// Issue query watched by a chat, along with the position in its activity stream
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Watch {
+    pub query: String,
+    /// Milliseconds since epoch, activities before the watch was added are skipped
+    pub since: i64,
+    pub cursor: Option<String>,
+    /// Query restricting the watch to what the user who added it can see,
+    /// watches without one are not polled
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum DigestPeriod {
+    Daily,
+    Weekly(Weekday),
+}
+
+/// Summary of a query posted to a chat on schedule, see /digest
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Digest {
+    pub id: u32,
+    pub chat: i64,
+    pub period: DigestPeriod,
+    /// Local time in the chat time zone
+    pub time: NaiveTime,
+    pub query: String,
+    /// Access of the user who added the digest, see Watch::scope
+    #[serde(default)]
+    pub scope: Option<String>,
+    /// Milliseconds since epoch, the next digest starts from here
+    #[serde(default)]
+    pub last_run: Option<i64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum NotificationCategory {
+    #[serde(rename = "m")]
+    Mentions,
+    #[serde(rename = "a")]
+    Assignments,
+    #[serde(rename = "r")]
+    Reported,
+    #[serde(rename = "w")]
+    Watches,
+    #[serde(rename = "d")]
+    Digests,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Batching {
+    Immediate,
+    Hourly,
+}
+
+/// Local time range in the chat time zone, may wrap over midnight
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct QuietHours {
+    pub from: NaiveTime,
+    pub to: NaiveTime,
+}
+
+/// What and when the chat wants to be notified about, see /settings
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct NotificationSettings {
+    pub muted: Vec<NotificationCategory>,
+    pub quiet_hours: Option<QuietHours>,
+    pub batching: Batching,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        NotificationSettings {
+            muted: Vec::new(),
+            quiet_hours: None,
+            batching: Batching::Immediate,
         }
     }
 }
 
-impl redis::ToRedisArgs for UserState {
-    fn write_redis_args<W>(&self, out: &mut W)
-    where
-        W: ?Sized + redis::RedisWrite,
-    {
-        let v = serde_json::to_string(self).unwrap();
-        out.write_arg(v.as_bytes());
+/// Notification held back by quiet hours or batching
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PendingNotification {
+    pub issue: IssueHeader,
+    pub author: String,
+    pub text: String,
+    /// Comment author to mention when the user replies to the notification
+    pub reply_to: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct StandupMember {
+    pub id: i64,
+    pub name: String,
+}
+
+/// Daily stand-up of a team chat, see /standup
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Standup {
+    pub chat: i64,
+    /// Local time in the chat time zone, stand-ups run on weekdays
+    pub time: NaiveTime,
+    pub members: Vec<StandupMember>,
+    /// Whether updates are added as comments to the issues they mention
+    pub comment_issues: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StandupReport {
+    pub member: StandupMember,
+    pub answers: Vec<String>,
+    /// Mentioned issues the member can see, looked up with their token when answering
+    #[serde(default)]
+    pub issues: Vec<IssueHeader>,
+}
+
+/// Command groups chat admins can turn off
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Feature {
+    Backlog,
+    Issues,
+    TimeTracking,
+    Watches,
+    Boards,
+    Standups,
+    Estimation,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum EstimationScale {
+    Fibonacci,
+    TShirt,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Language {
+    #[serde(rename = "ru")]
+    Ru,
+    #[serde(rename = "en")]
+    En,
+}
+
+/// Chat configuration managed by chat admins, see /config
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ChatConfig {
+    /// Replaces the global backlog query
+    pub backlog_query: Option<String>,
+    /// Project name or short name of new issues
+    pub project: Option<String>,
+    /// Custom field values of new issues by field name, issue templates take precedence
+    pub fields: HashMap<String, String>,
+    pub language: Language,
+    pub disabled: Vec<Feature>,
+    /// Added after the first configs were stored
+    #[serde(default = "default_estimation_scale")]
+    pub estimation_scale: EstimationScale,
+}
+
+fn default_estimation_scale() -> EstimationScale {
+    EstimationScale::Fibonacci
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        ChatConfig {
+            backlog_query: None,
+            project: None,
+            fields: HashMap::new(),
+            language: Language::Ru,
+            disabled: Vec::new(),
+            estimation_scale: EstimationScale::Fibonacci,
+        }
     }
 }
+
+/// Backlog prioritization poll, see /prioritize
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PrioritySession {
+    pub poll: String,
+    pub chat: i64,
+    pub message: i64,
+    /// User who started the poll, the only one allowed to apply its votes
+    #[serde(default)]
+    pub starter: i64,
+    pub query: String,
+    /// Issues in the order of poll options
+    pub issues: Vec<IssueHeader>,
+    /// Chosen options by participant id
+    pub answers: HashMap<i64, Vec<usize>>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EstimationVote {
+    pub id: i64,
+    pub name: String,
+    pub value: String,
+}
+
+/// Planning poker round on an issue in a chat, see /estimate
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EstimationSession {
+    pub chat: i64,
+    /// Message of the round, its buttons act on this session only
+    pub message: i64,
+    pub issue: IssueHeader,
+    /// User who started the round, only they reveal it and apply the result
+    pub host: i64,
+    pub scale: EstimationScale,
+    /// Number of votes that reveals the round by itself
+    pub expected: usize,
+    /// Hidden until revealed
+    pub votes: Vec<EstimationVote>,
+    pub revealed: bool,
+}
+
+/// Change applied to all issues selected in the backlog through the command composer
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum BulkAction {
+    #[serde(rename = "t")]
+    Tag,
+    #[serde(rename = "a")]
+    Assign,
+    #[serde(rename = "s")]
+    State,
+    #[serde(rename = "c")]
+    Command,
+}
+
+/// Issues picked in a backlog message, kept while it is in selection mode
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct BacklogSelection {
+    pub issues: Vec<String>,
+}
+
+/// Issue field set from the triage card
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum TriageField {
+    #[serde(rename = "p")]
+    Priority,
+    #[serde(rename = "t")]
+    Type,
+    #[serde(rename = "a")]
+    Assignee,
+}
+
+/// Issues of a query shown one at a time in a single message, see /triage
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TriageSession {
+    pub chat: i64,
+    pub message: i64,
+    pub query: String,
+    /// Issue on the card, none before the first one is fetched
+    pub issue: Option<String>,
+    /// Skipped and triaged issues, the query may still match them
+    pub seen: Vec<String>,
+}
+
+macro_rules! impl_redis_json {
+    ($t:ty) => {
+        impl redis::FromRedisValue for $t {
+            fn from_redis_value(v: &redis::Value) -> redis::RedisResult<Self> {
+                match v {
+                    redis::Value::Status(ref s) => serde_json::from_str(s)
+                        .map_err(|_| (redis::ErrorKind::TypeError, "Unable to parse value").into()),
+                    redis::Value::Data(ref bytes) => serde_json::from_slice(bytes)
+                        .map_err(|_| (redis::ErrorKind::TypeError, "Unable to parse value").into()),
+                    _ => Err((redis::ErrorKind::TypeError, "Unable to parse value").into()),
+                }
+            }
+        }
+
+        impl redis::ToRedisArgs for $t {
+            fn write_redis_args<W>(&self, out: &mut W)
+            where
+                W: ?Sized + redis::RedisWrite,
+            {
+                let v = serde_json::to_string(self).unwrap();
+                out.write_arg(v.as_bytes());
+            }
+        }
+    };
+}
+
+impl_redis_json!(UserState);
+impl_redis_json!(StoredUserState);
+impl_redis_json!(RunningTimer);
+impl_redis_json!(Watch);
+impl_redis_json!(Digest);
+impl_redis_json!(NotificationSettings);
+impl_redis_json!(PendingNotification);
+impl_redis_json!(Standup);
+impl_redis_json!(StandupReport);
+impl_redis_json!(ChatConfig);
+impl_redis_json!(PrioritySession);
+impl_redis_json!(EstimationSession);
+impl_redis_json!(TriageSession);
+impl_redis_json!(BacklogSelection);
@@ -0,0 +1,129 @@
+use chrono::{Datelike, Duration, NaiveDate, TimeZone, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use super::commands::TimesheetPeriod;
+use super::models::WorkItem;
+
+pub fn format_minutes(minutes: u64) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{}m", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h{}m", h, m),
+    }
+}
+
+/// First and last day of the current week or month
+pub fn period_range(period: &TimesheetPeriod, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    match period {
+        TimesheetPeriod::Week => {
+            let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+            (monday, monday + Duration::days(6))
+        }
+        TimesheetPeriod::Month => {
+            let first = today.with_day(1).unwrap();
+            let next = if first.month() == 12 {
+                NaiveDate::from_ymd(first.year() + 1, 1, 1)
+            } else {
+                NaiveDate::from_ymd(first.year(), first.month() + 1, 1)
+            };
+            (first, next - Duration::days(1))
+        }
+    }
+}
+
+fn work_item_date(item: &WorkItem) -> NaiveDate {
+    Utc.timestamp_millis(item.date).naive_utc().date()
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimesheetRow {
+    pub name: String,
+    pub summary: Option<String>,
+    pub time: String,
+}
+
+/// Logged time summed up per day, issue and author
+#[derive(Debug, Serialize)]
+pub struct Timesheet {
+    pub from: String,
+    pub to: String,
+    pub total: String,
+    pub days: Vec<TimesheetRow>,
+    pub issues: Vec<TimesheetRow>,
+    pub authors: Vec<TimesheetRow>,
+}
+
+impl Timesheet {
+    pub fn new(from: NaiveDate, to: NaiveDate, items: &[WorkItem]) -> Self {
+        let mut days: BTreeMap<NaiveDate, u64> = BTreeMap::new();
+        let mut issues: BTreeMap<&str, (&str, u64)> = BTreeMap::new();
+        let mut authors: BTreeMap<String, u64> = BTreeMap::new();
+        for item in items {
+            let minutes = item.duration.minutes;
+            *days.entry(work_item_date(item)).or_insert(0) += minutes;
+            issues
+                .entry(item.issue.id_readable.as_str())
+                .or_insert((item.issue.summary.as_str(), 0))
+                .1 += minutes;
+            *authors.entry(item.author.display_name()).or_insert(0) += minutes;
+        }
+
+        Self {
+            from: from.format("%Y-%m-%d").to_string(),
+            to: to.format("%Y-%m-%d").to_string(),
+            total: format_minutes(items.iter().map(|i| i.duration.minutes).sum()),
+            days: days
+                .into_iter()
+                .map(|(day, minutes)| TimesheetRow {
+                    name: day.format("%a %Y-%m-%d").to_string(),
+                    summary: None,
+                    time: format_minutes(minutes),
+                })
+                .collect(),
+            issues: issues
+                .into_iter()
+                .map(|(id, (summary, minutes))| TimesheetRow {
+                    name: id.to_string(),
+                    summary: Some(summary.to_string()),
+                    time: format_minutes(minutes),
+                })
+                .collect(),
+            authors: authors
+                .into_iter()
+                .map(|(name, minutes)| TimesheetRow {
+                    name,
+                    summary: None,
+                    time: format_minutes(minutes),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(|c: char| c == ',' || c == '"' || c == '\n') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Work items one per line, for spreadsheets
+pub fn to_csv(items: &[WorkItem]) -> String {
+    let mut csv = String::from("date,issue,summary,author,minutes,comment\n");
+    for item in items {
+        let row = [
+            work_item_date(item).format("%Y-%m-%d").to_string(),
+            item.issue.id_readable.clone(),
+            item.issue.summary.clone(),
+            item.author.login.clone(),
+            item.duration.minutes.to_string(),
+            item.text.clone().unwrap_or_default(),
+        ];
+        let row: Vec<String> = row.iter().map(|v| csv_field(v)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
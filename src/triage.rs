@@ -0,0 +1,127 @@
+use super::commands::BacklogParams;
+use super::models::Issues;
+use super::states::{TriageField, TriageSession};
+
+/// Seconds an idle triage session is kept
+pub const SESSION_TTL: usize = 24 * 3600;
+
+/// Comment posted by the "need info" button
+pub const NEED_INFO_COMMENT: &str =
+    "Could you add more details on this issue? It can't be triaged without them.";
+
+impl TriageField {
+    pub fn all() -> Vec<TriageField> {
+        vec![
+            TriageField::Priority,
+            TriageField::Type,
+            TriageField::Assignee,
+        ]
+    }
+
+    /// YouTrack field name
+    pub fn name(self) -> &'static str {
+        match self {
+            TriageField::Priority => "Priority",
+            TriageField::Type => "Type",
+            TriageField::Assignee => "Assignee",
+        }
+    }
+}
+
+pub fn session_key(chat: i64, message: i64) -> String {
+    format!("triage:{}:{}", chat, message)
+}
+
+impl TriageSession {
+    pub fn key(&self) -> String {
+        session_key(self.chat, self.message)
+    }
+
+    /// Issues changed by triage may drop out of the query, so the cursor
+    /// always starts from the top and reaches past every seen issue
+    pub fn page(&self) -> BacklogParams {
+        BacklogParams::new(self.seen.len() as i32 + 1)
+    }
+
+    /// Moves to the first issue of the page not seen yet
+    pub fn next(&mut self, issues: &Issues) {
+        self.issue = issues
+            .iter()
+            .map(|i| &i.id_readable)
+            .find(|id| !self.seen.contains(*id))
+            .cloned();
+    }
+
+    /// Marks the current issue as done with
+    pub fn advance(&mut self) {
+        if let Some(issue) = self.issue.take() {
+            self.seen.push(issue);
+        }
+    }
+
+    pub fn is_current(&self, issue: &str) -> bool {
+        self.issue.as_ref().map(|i| i.as_str()) == Some(issue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Issue, IssueVoters};
+
+    fn issues(ids: &[&str]) -> Issues {
+        ids.iter()
+            .map(|id| Issue {
+                id_readable: id.to_string(),
+                summary: format!("Summary of {}", id),
+                votes: 0,
+                voters: IssueVoters { has_vote: false },
+            })
+            .collect()
+    }
+
+    fn session(seen: &[&str]) -> TriageSession {
+        TriageSession {
+            chat: 1,
+            message: 2,
+            query: "#Unresolved".to_string(),
+            issue: None,
+            seen: seen.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn starts_with_the_first_issue() {
+        let mut session = session(&[]);
+        session.next(&issues(&["TP-1", "TP-2"]));
+        assert_eq!(session.issue, Some("TP-1".to_string()));
+        assert!(session.is_current("TP-1"));
+    }
+
+    #[test]
+    fn skips_seen_issues() {
+        let mut session = session(&["TP-1", "TP-3"]);
+        session.next(&issues(&["TP-1", "TP-3", "TP-2", "TP-4"]));
+        assert_eq!(session.issue, Some("TP-2".to_string()));
+    }
+
+    #[test]
+    fn advances_past_the_current_issue() {
+        let mut session = session(&[]);
+        let page = issues(&["TP-1", "TP-2"]);
+        session.next(&page);
+        session.advance();
+        assert_eq!(session.seen, vec!["TP-1".to_string()]);
+        session.next(&page);
+        assert_eq!(session.issue, Some("TP-2".to_string()));
+    }
+
+    #[test]
+    fn ends_when_every_issue_was_seen() {
+        let mut session = session(&["TP-1", "TP-2"]);
+        session.next(&issues(&["TP-1", "TP-2"]));
+        assert_eq!(session.issue, None);
+        session.next(&issues(&[]));
+        assert_eq!(session.issue, None);
+    }
+}
@@ -1,16 +1,18 @@
 use super::errors::*;
 
-use actix_web::{dev::Server, middleware, web, App, HttpResponse, HttpServer};
+use actix_web::{dev::Server, middleware, web, App, HttpRequest, HttpResponse, HttpServer};
 use serde::Deserialize;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tera::Context;
 
 use super::bot::Bot;
+use super::hooks::{self, HookPayload};
 
 #[derive(Clone)]
 struct AppState {
     bot: Arc<Mutex<Box<Bot>>>,
+    hook_secret: Option<String>,
 }
 
 #[derive(Deserialize, Clone)]
@@ -44,14 +46,52 @@ async fn auth2(data: web::Data<AppState>, params: web::Query<AuthRequest>) -> Ht
     HttpResponse::Ok().body(html.unwrap())
 }
 
-pub fn run(bot: Arc<Mutex<Box<Bot>>>, addr: String) -> Result<Server> {
+fn header<'a>(req: &'a HttpRequest, name: &str) -> Option<&'a str> {
+    req.headers().get(name).and_then(|v| v.to_str().ok())
+}
+
+async fn youtrack_hook(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> HttpResponse {
+    let secret = match &data.hook_secret {
+        Some(secret) => secret,
+        None => return HttpResponse::NotFound().finish(),
+    };
+    let authorization = header(&req, "Authorization");
+    let signature = header(&req, "X-Hook-Signature");
+    if !hooks::verify(secret, authorization, signature, &body) {
+        warn!("Rejected unauthenticated YouTrack hook");
+        return HttpResponse::Unauthorized().finish();
+    }
+    let payload = match serde_json::from_slice::<HookPayload>(&body) {
+        Ok(payload) => payload,
+        Err(e) => return HttpResponse::BadRequest().body(format!("Invalid payload: {}", e)),
+    };
+
+    let bot = data.bot.lock().unwrap();
+    match bot.on_hook(payload).await {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e) => {
+            warn!("Could not handle YouTrack hook: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+pub fn run(bot: Arc<Mutex<Box<Bot>>>, addr: String, hook_secret: Option<String>) -> Result<Server> {
     Ok(HttpServer::new(move || {
-        let data = AppState { bot: bot.clone() };
+        let data = AppState {
+            bot: bot.clone(),
+            hook_secret: hook_secret.clone(),
+        };
         App::new()
             .data(data)
             .wrap(middleware::Logger::default())
             .route("/auth", web::get().to(auth))
             .route("/auth2", web::get().to(auth2))
+            .route("/hooks/youtrack", web::post().to(youtrack_hook))
     })
     .bind(addr)?
     .run())